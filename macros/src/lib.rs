@@ -0,0 +1,455 @@
+//! Procedural macros for [`airfrog-rpc`](https://docs.rs/airfrog-rpc).
+//!
+//! Use via the `macros` feature of `airfrog-rpc`, which re-exports these
+//! macros, rather than depending on this crate directly.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    FnArg, Ident, ItemTrait, LitInt, Pat, ReturnType, TraitItem, TraitItemFn, Type,
+    parse_macro_input,
+};
+
+/// Generates a Host client stub and Target command table from a single
+/// service definition, so the two can't drift apart.
+///
+/// Apply to a trait whose methods take no `self`, and whose arguments and
+/// return types implement `serde::Serialize` and `serde::Deserialize`.
+///
+/// Attribute arguments:
+/// - `base` - Command ID of the service's fingerprint command, default 0.
+///   Methods use the following command IDs, in definition order.  All must
+///   be below `0xF0`, as IDs from there are reserved for the system
+///   service.
+/// - `module` - Name of the generated module, default the trait name in
+///   snake case.
+///
+/// Methods must not be named `base`, `fingerprint` or `connect`, which the
+/// generated module uses itself.
+///
+/// Generates:
+/// - The trait, with `&mut self` added to each method, for the Target to
+///   implement.
+/// - A module containing:
+///   - `BASE` and `FINGERPRINT` - the fingerprint is a hash of the method
+///     names, argument names and types, and return types, and changes if the
+///     definition changes.
+///   - A command ID constant per method, the method name in upper case.
+///   - `commands::<S>()` - the command table for an `RpcServer` whose
+///     context `S` implements the trait.
+///   - `Client` (with the `async` feature) - wraps an `AsyncRpcClient`, with
+///     an async method per service method.  `Client::connect()` checks the
+///     Target's fingerprint, returning `Error::ServiceMismatch` if it
+///     differs.
+///
+/// Arguments and return values are encoded with `DefaultCodec`.
+///
+/// ```rust,ignore
+/// #[airfrog_rpc::service(base = 0x10)]
+/// pub trait Led {
+///     fn set(index: u8, on: bool);
+///     fn get(index: u8) -> Option<bool>;
+/// }
+///
+/// // Target
+/// impl Led for App { ... }
+/// let mut server: RpcServer<_, App, 64> = RpcServer::new(cmd_ch, rsp_ch, led::commands());
+///
+/// // Host
+/// let mut led = led::Client::connect(&mut client).await?;
+/// led.set(0, true).await?;
+/// ```
+#[proc_macro_attribute]
+pub fn service(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = ServiceArgs::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("base") {
+            let lit: LitInt = meta.value()?.parse()?;
+            args.base = lit.base10_parse()?;
+            Ok(())
+        } else if meta.path.is_ident("module") {
+            args.module = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported service attribute, expected `base` or `module`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+
+    let item = parse_macro_input!(item as ItemTrait);
+    match expand(args, item) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+// First command ID reserved for the system service.  Must match
+// `airfrog_rpc::system::RESERVED_BASE`.
+const RESERVED_BASE: u8 = 0xF0;
+
+#[derive(Default)]
+struct ServiceArgs {
+    base: u8,
+    module: Option<Ident>,
+}
+
+struct Method {
+    name: Ident,
+    attrs: Vec<syn::Attribute>,
+    args: Vec<(Ident, Type)>,
+    output: Type,
+}
+
+fn expand(args: ServiceArgs, item: ItemTrait) -> syn::Result<TokenStream2> {
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            item.generics.span(),
+            "services must not be generic",
+        ));
+    }
+
+    let methods = item
+        .items
+        .iter()
+        .map(|item| match item {
+            TraitItem::Fn(method) => parse_method(method),
+            other => Err(syn::Error::new(
+                other.span(),
+                "services may only contain methods",
+            )),
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    // Method names must not clash with the generated module's own items
+    for method in &methods {
+        let name = method.name.to_string();
+        if matches!(name.to_uppercase().as_str(), "BASE" | "FINGERPRINT") || name == "connect" {
+            return Err(syn::Error::new(
+                method.name.span(),
+                format!("method name `{name}` is reserved by the generated service module"),
+            ));
+        }
+    }
+
+    // Method IDs follow the fingerprint command at the base ID, and must
+    // not reach the IDs reserved for the system service
+    if args.base >= RESERVED_BASE {
+        return Err(syn::Error::new(
+            item.ident.span(),
+            format!("service base ID must be below {RESERVED_BASE:#04X}, which is reserved"),
+        ));
+    }
+    if args.base as usize + methods.len() >= RESERVED_BASE as usize {
+        return Err(syn::Error::new(
+            item.ident.span(),
+            format!(
+                "too many methods for the service's base ID - command IDs from {RESERVED_BASE:#04X} are reserved"
+            ),
+        ));
+    }
+
+    let vis = &item.vis;
+    let attrs = &item.attrs;
+    let trait_name = &item.ident;
+    let module = args
+        .module
+        .unwrap_or_else(|| Ident::new(&snake_case(&trait_name.to_string()), Span::call_site()));
+    let base = args.base;
+    let fingerprint = fingerprint(trait_name, &methods);
+
+    let trait_methods = methods.iter().map(|method| {
+        let Method {
+            name,
+            attrs,
+            args,
+            output,
+        } = method;
+        let arg_names = args.iter().map(|(name, _)| name);
+        let arg_types = args.iter().map(|(_, ty)| ty);
+        quote! {
+            #(#attrs)*
+            fn #name(&mut self, #(#arg_names: #arg_types),*) -> #output;
+        }
+    });
+
+    let id_consts = methods.iter().enumerate().map(|(index, method)| {
+        let konst = format_ident!("{}", method.name.to_string().to_uppercase());
+        let id = base + 1 + index as u8;
+        let doc = format!("Command ID of `{}`", method.name);
+        quote! {
+            #[doc = #doc]
+            pub const #konst: u8 = #id;
+        }
+    });
+
+    let handlers = methods.iter().map(|method| {
+        let name = &method.name;
+        let handler = format_ident!("__handle_{}", name);
+        let arg_names: Vec<_> = method.args.iter().map(|(name, _)| name).collect();
+        let arg_types = method.args.iter().map(|(_, ty)| ty);
+        quote! {
+            fn #handler<S: super::#trait_name>(
+                service: &mut S,
+                request: &mut ::airfrog_rpc::server::Request<'_>,
+                response: &mut [u8],
+            ) -> ::core::result::Result<usize, ::airfrog_rpc::server::Status> {
+                let (#(#arg_names,)*): (#(#arg_types,)*) =
+                    request.decode::<::airfrog_rpc::codec::DefaultCodec, _>()?;
+                let result = service.#name(#(#arg_names),*);
+                ::airfrog_rpc::server::encode_response::<::airfrog_rpc::codec::DefaultCodec, _>(
+                    &result, response,
+                )
+            }
+        }
+    });
+
+    let table = methods.iter().map(|method| {
+        let name = method.name.to_string();
+        let konst = format_ident!("{}", name.to_uppercase());
+        let handler = format_ident!("__handle_{}", method.name);
+        let request_schema = method
+            .args
+            .iter()
+            .map(|(name, ty)| format!("{name}: {}", type_string(ty)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let request_schema = format!("({request_schema})");
+        let response_schema = type_string(&method.output);
+        quote! {
+            ::airfrog_rpc::server::Command::new(#konst, #name, #handler::<S>)
+                .with_schema(#request_schema, #response_schema)
+        }
+    });
+
+    let client = if cfg!(feature = "client") {
+        let client_methods = methods.iter().map(|method| {
+            let Method {
+                name,
+                attrs,
+                args,
+                output,
+            } = method;
+            let konst = format_ident!("{}", name.to_string().to_uppercase());
+            let arg_names: Vec<_> = args.iter().map(|(name, _)| name).collect();
+            let arg_types = args.iter().map(|(_, ty)| ty);
+            quote! {
+                #(#attrs)*
+                pub async fn #name(
+                    &mut self,
+                    #(#arg_names: #arg_types),*
+                ) -> ::airfrog_rpc::Result<#output> {
+                    self.client
+                        .call_typed::<_, #output>(#konst, &(#(&#arg_names,)*))
+                        .await
+                }
+            }
+        });
+
+        quote! {
+            /// Client stub, calling the service's methods on the Target.
+            pub struct Client<'c, 'a, R, W, D>
+            where
+                R: ::airfrog_rpc::io::Reader,
+                W: ::airfrog_rpc::io::Writer,
+                D: ::airfrog_rpc::client::AsyncDelay,
+            {
+                client: &'c mut ::airfrog_rpc::client::AsyncRpcClient<'a, R, W, D>,
+            }
+
+            impl<'c, 'a, R, W, D> Client<'c, 'a, R, W, D>
+            where
+                R: ::airfrog_rpc::io::Reader,
+                W: ::airfrog_rpc::io::Writer,
+                D: ::airfrog_rpc::client::AsyncDelay,
+            {
+                /// Connect to the service, checking that the Target's
+                /// definition matches this one.
+                ///
+                /// Returns [`::airfrog_rpc::Error::ServiceMismatch`] if the
+                /// Target's service definition differs, or the Target doesn't
+                /// implement the service.
+                pub async fn connect(
+                    client: &'c mut ::airfrog_rpc::client::AsyncRpcClient<'a, R, W, D>,
+                ) -> ::airfrog_rpc::Result<Self> {
+                    match client.call_typed::<_, u32>(BASE, &()).await {
+                        Ok(FINGERPRINT) => Ok(Self { client }),
+                        Ok(_)
+                        | Err(::airfrog_rpc::Error::Codec)
+                        | Err(::airfrog_rpc::Error::Status(_)) => {
+                            Err(::airfrog_rpc::Error::ServiceMismatch)
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+
+                #(#client_methods)*
+            }
+        }
+    } else {
+        TokenStream2::new()
+    };
+
+    let module_doc = format!("Generated items for the [`{trait_name}`] service.");
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis trait #trait_name {
+            #(#trait_methods)*
+        }
+
+        #[doc = #module_doc]
+        #vis mod #module {
+            #![allow(clippy::unused_unit)]
+
+            /// Command ID of the fingerprint command.  Method command IDs
+            /// follow it.
+            pub const BASE: u8 = #base;
+
+            /// Fingerprint of the service definition, used to detect
+            /// mismatched Host and Target definitions.
+            pub const FINGERPRINT: u32 = #fingerprint;
+
+            #(#id_consts)*
+
+            /// Command table for [`::airfrog_rpc::server::RpcServer`],
+            /// dispatching to the service implementation, which is the
+            /// server's context.
+            pub fn commands<S: super::#trait_name + 'static>()
+            -> &'static [::airfrog_rpc::server::Command<S>] {
+                Commands::<S>::LIST
+            }
+
+            struct Commands<S>(::core::marker::PhantomData<S>);
+
+            impl<S: super::#trait_name + 'static> Commands<S> {
+                const LIST: &'static [::airfrog_rpc::server::Command<S>] = &[
+                    ::airfrog_rpc::server::Command::new(BASE, "fingerprint", __handle_fingerprint::<S>)
+                        .with_schema("()", "u32"),
+                    #(#table),*
+                ];
+            }
+
+            fn __handle_fingerprint<S: super::#trait_name>(
+                _service: &mut S,
+                _request: &mut ::airfrog_rpc::server::Request<'_>,
+                response: &mut [u8],
+            ) -> ::core::result::Result<usize, ::airfrog_rpc::server::Status> {
+                ::airfrog_rpc::server::encode_response::<::airfrog_rpc::codec::DefaultCodec, _>(
+                    &FINGERPRINT, response,
+                )
+            }
+
+            #(#handlers)*
+
+            #client
+        }
+    })
+}
+
+fn parse_method(method: &TraitItemFn) -> syn::Result<Method> {
+    let sig = &method.sig;
+    if method.default.is_some() {
+        return Err(syn::Error::new(
+            method.span(),
+            "service methods must not have a body",
+        ));
+    }
+    if !sig.generics.params.is_empty() || sig.asyncness.is_some() {
+        return Err(syn::Error::new(
+            sig.span(),
+            "service methods must not be generic or async",
+        ));
+    }
+
+    let args = sig
+        .inputs
+        .iter()
+        .map(|arg| match arg {
+            FnArg::Receiver(receiver) => Err(syn::Error::new(
+                receiver.span(),
+                "service methods must not take self - `&mut self` is added automatically",
+            )),
+            FnArg::Typed(typed) => match &*typed.pat {
+                Pat::Ident(ident) => Ok((ident.ident.clone(), (*typed.ty).clone())),
+                other => Err(syn::Error::new(
+                    other.span(),
+                    "service method arguments must be identifiers",
+                )),
+            },
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let output = match &sig.output {
+        ReturnType::Default => syn::parse_quote!(()),
+        ReturnType::Type(_, ty) => (**ty).clone(),
+    };
+
+    Ok(Method {
+        name: sig.ident.clone(),
+        attrs: method.attrs.clone(),
+        args,
+        output,
+    })
+}
+
+// FNV-1a hash of a canonical form of the service definition.  Whitespace is
+// removed, so only changes to names, types and order affect it.
+fn fingerprint(trait_name: &Ident, methods: &[Method]) -> u32 {
+    let mut canonical = trait_name.to_string();
+    for method in methods {
+        canonical.push(';');
+        canonical.push_str(&method.name.to_string());
+        canonical.push('(');
+        for (name, ty) in &method.args {
+            canonical.push_str(&name.to_string());
+            canonical.push(':');
+            canonical.push_str(&quote!(#ty).to_string());
+            canonical.push(',');
+        }
+        canonical.push_str(")->");
+        let output = &method.output;
+        canonical.push_str(&quote!(#output).to_string());
+    }
+    canonical.retain(|c| !c.is_whitespace());
+
+    canonical.bytes().fold(0x811c_9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+// Renders a type as it would typically be written, for schema descriptions
+fn type_string(ty: &Type) -> String {
+    let mut string = quote!(#ty).to_string();
+    for (from, to) in [
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        (" :: ", "::"),
+        ("& ", "&"),
+    ] {
+        string = string.replace(from, to);
+    }
+    string
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
@@ -0,0 +1,168 @@
+//! Tests for the code generated by the service macro.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use std::sync::atomic::AtomicU32;
+
+use airfrog_rpc::channel::{AtomicChannelIo, ChannelActor, OwnedChannel};
+use airfrog_rpc::codec::{Codec, DefaultCodec};
+use airfrog_rpc::server::{RpcServer, Status};
+
+#[airfrog_rpc::service(base = 0x10)]
+pub trait Led {
+    /// Set an LED
+    fn set(index: u8, on: bool);
+    fn get(index: u8) -> Option<bool>;
+}
+
+#[airfrog_rpc::service(base = 0x40, module = counter_service)]
+pub trait Counter {
+    fn add(by: u32) -> u32;
+}
+
+// The highest ID below those reserved for the system service
+#[airfrog_rpc::service(base = 0xEE)]
+pub trait Edge {
+    fn last();
+}
+
+// Same definition as Led, laid out differently
+#[rustfmt::skip]
+mod reformatted {
+    #[airfrog_rpc::service(base = 0x10)]
+    pub trait Led {
+        fn set(index : u8,on : bool) -> ();
+        fn get(index: u8)
+            -> Option< bool >;
+    }
+}
+
+// Led with a changed argument type
+mod changed {
+    #[airfrog_rpc::service(base = 0x10)]
+    pub trait Led {
+        fn set(index: u16, on: bool);
+        fn get(index: u8) -> Option<bool>;
+    }
+}
+
+#[derive(Default)]
+struct App {
+    leds: [bool; 4],
+}
+
+impl Led for App {
+    fn set(&mut self, index: u8, on: bool) {
+        self.leds[index as usize] = on;
+    }
+
+    fn get(&mut self, index: u8) -> Option<bool> {
+        self.leds.get(index as usize).copied()
+    }
+}
+
+// FNV-1a, as used for fingerprints
+fn fnv1a(s: &str) -> u32 {
+    s.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[test]
+fn command_ids_follow_base() {
+    assert_eq!(led::BASE, 0x10);
+    assert_eq!(led::SET, 0x11);
+    assert_eq!(led::GET, 0x12);
+
+    assert_eq!(counter_service::BASE, 0x40);
+    assert_eq!(counter_service::ADD, 0x41);
+
+    assert_eq!(edge::BASE, 0xEE);
+    assert_eq!(edge::LAST, 0xEF);
+}
+
+#[test]
+fn fingerprint_covers_names_and_types_only() {
+    // The fingerprint is part of the wire protocol, so must not change
+    // between versions of the macro
+    assert_eq!(
+        led::FINGERPRINT,
+        fnv1a("Led;set(index:u8,on:bool,)->();get(index:u8,)->Option<bool>")
+    );
+    assert_eq!(led::FINGERPRINT, reformatted::led::FINGERPRINT);
+    assert_ne!(led::FINGERPRINT, changed::led::FINGERPRINT);
+}
+
+#[test]
+fn command_table_describes_service() {
+    let commands = led::commands::<App>();
+    let entries = commands
+        .iter()
+        .map(|c| (c.id, c.name, c.request_schema, c.response_schema))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        entries,
+        [
+            (0x10, "fingerprint", "()", "u32"),
+            (0x11, "set", "(index: u8, on: bool)", "()"),
+            (0x12, "get", "(index: u8)", "Option<bool>"),
+        ]
+    );
+}
+
+#[test]
+fn server_dispatches_to_service() {
+    const BASE_ADDR: u32 = 0x2000_0000;
+    const CMD_CH: u32 = BASE_ADDR;
+    const RSP_CH: u32 = BASE_ADDR + 128;
+    let mem = (0..64).map(|_| AtomicU32::new(0)).collect::<Vec<_>>();
+    let io = AtomicChannelIo::new(&mem, BASE_ADDR);
+
+    let cmd_ch = OwnedChannel::new(io, ChannelActor::Consumer, CMD_CH, 128).unwrap();
+    let rsp_ch = OwnedChannel::new(io, ChannelActor::Producer, RSP_CH, 128).unwrap();
+    let mut server: RpcServer<_, App, 64, _> = RpcServer::new(cmd_ch, rsp_ch, led::commands());
+    let mut app = App::default();
+
+    let mut host_cmd = OwnedChannel::from_target(io, ChannelActor::Producer, CMD_CH).unwrap();
+    let mut host_rsp = OwnedChannel::from_target(io, ChannelActor::Consumer, RSP_CH).unwrap();
+    let mut call = |app: &mut App, id: u8, payload: &[u8]| {
+        let mut command = vec![id];
+        command.extend_from_slice(payload);
+        host_cmd.publish_bytes(&command).unwrap();
+        assert!(server.poll(app).unwrap());
+        let mut response = [0u8; 64];
+        let len = host_rsp.consume_bytes(&mut response).unwrap();
+        (Status::from(response[0]), response[1..len].to_vec())
+    };
+    let mut buf = [0u8; 16];
+
+    let (status, payload) = call(&mut app, led::BASE, &[]);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        DefaultCodec::decode::<u32>(&payload).unwrap(),
+        led::FINGERPRINT
+    );
+
+    let len = DefaultCodec::encode(&(2u8, true), &mut buf).unwrap();
+    assert_eq!(call(&mut app, led::SET, &buf[..len]).0, Status::Ok);
+    assert_eq!(app.leds, [false, false, true, false]);
+
+    let len = DefaultCodec::encode(&(2u8,), &mut buf).unwrap();
+    let (status, payload) = call(&mut app, led::GET, &buf[..len]);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        DefaultCodec::decode::<Option<bool>>(&payload).unwrap(),
+        Some(true)
+    );
+
+    // Arguments which don't decode are rejected
+    assert_eq!(call(&mut app, led::SET, &[]).0, Status::InvalidRequest);
+}
+
+#[test]
+fn invalid_services_are_rejected() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
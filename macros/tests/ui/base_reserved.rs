@@ -0,0 +1,6 @@
+#[airfrog_rpc::service(base = 0xF0)]
+pub trait Clash {
+    fn ping();
+}
+
+fn main() {}
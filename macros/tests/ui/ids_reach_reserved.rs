@@ -0,0 +1,7 @@
+#[airfrog_rpc::service(base = 0xEE)]
+pub trait Crowded {
+    fn first();
+    fn second();
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[airfrog_rpc::service]
+pub trait Led {
+    async fn get(index: u8) -> bool;
+}
+
+fn main() {}
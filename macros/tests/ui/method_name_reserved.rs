@@ -0,0 +1,7 @@
+#[airfrog_rpc::service(base = 0x10)]
+pub trait Clash {
+    fn ping();
+    fn fingerprint() -> u32;
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[airfrog_rpc::service]
+pub trait Led {
+    fn get(&self, index: u8) -> bool;
+}
+
+fn main() {}
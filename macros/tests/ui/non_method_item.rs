@@ -0,0 +1,6 @@
+#[airfrog_rpc::service]
+pub trait Led {
+    const COUNT: u8;
+}
+
+fn main() {}
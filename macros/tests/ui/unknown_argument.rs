@@ -0,0 +1,6 @@
+#[airfrog_rpc::service(version = 2)]
+pub trait Led {
+    fn get(index: u8) -> bool;
+}
+
+fn main() {}
@@ -0,0 +1,114 @@
+//! Channel I/O using atomics, for channels between the cores of a dual-core
+//! MCU, such as the RP2040, RP2350 or ESP32, or between threads.
+//!
+//! No debug interface is involved - both ends access the channel's memory
+//! directly, using [`AtomicChannelIo`], with the same protocol used between
+//! Host and Target.
+//!
+//! Control block words are read with `Acquire` and written with `Release`
+//! ordering.  Payloads are accessed with `Relaxed` ordering, followed by a
+//! `Release` fence when written, and preceded by an `Acquire` fence when
+//! read.  So once a consumer sees the producer's sequence number change, the
+//! payload it published is visible, and once the producer sees the
+//! consumer's sequence number change, the consumer has finished reading it.
+//!
+//! Only atomic loads and stores are used, so cores without compare-and-swap,
+//! such as the RP2040's Cortex-M0+ cores, are supported.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use core::sync::atomic::{AtomicU32, Ordering, fence};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::ChannelIo;
+use crate::{Error, Result};
+
+/// Channel I/O implementation using atomic accesses to memory shared between
+/// cores or threads.  See [`crate::channel::atomic`].
+///
+/// The memory is a slice of [`AtomicU32`], which channel addresses are
+/// relative to, starting at `base_addr`.  Each end of a channel has its own
+/// AtomicChannelIo, referring to the same memory.
+///
+/// ```rust,ignore
+/// use core::sync::atomic::AtomicU32;
+/// use airfrog_rpc::channel::{AtomicChannelIo, ChannelActor, OwnedChannel};
+///
+/// // Memory for two 512 byte channels
+/// static MEM: [AtomicU32; 256] = [const { AtomicU32::new(0) }; 256];
+///
+/// // Core 0
+/// let io = AtomicChannelIo::new(&MEM, 0);
+/// let mut tx = OwnedChannel::new(io, ChannelActor::Producer, 0, 512)?;
+/// let mut rx = OwnedChannel::new(io, ChannelActor::Consumer, 512, 512)?;
+///
+/// // Core 1, once core 0 has created the channels
+/// let io = AtomicChannelIo::new(&MEM, 0);
+/// let mut rx = OwnedChannel::from_target(io, ChannelActor::Consumer, 0)?;
+/// let mut tx = OwnedChannel::from_target(io, ChannelActor::Producer, 512)?;
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct AtomicChannelIo<'a> {
+    mem: &'a [AtomicU32],
+    base_addr: u32,
+}
+
+impl<'a> AtomicChannelIo<'a> {
+    /// Create a new AtomicChannelIo
+    ///
+    /// Arguments:
+    /// - `mem` - Memory shared by both ends of the channels
+    /// - `base_addr` - Address of the start of `mem`, as used by the
+    ///   channels.  Often 0, but may be the real address of `mem`, so
+    ///   addresses are the same as when using [`crate::channel::RamChannelIo`].
+    pub const fn new(mem: &'a [AtomicU32], base_addr: u32) -> Self {
+        Self { mem, base_addr }
+    }
+
+    // Gets the words starting at an address.  Returns Error::Io for
+    // addresses outside the memory.
+    fn words(&self, addr: u32, len: usize) -> Result<&'a [AtomicU32]> {
+        if !addr.is_multiple_of(4) {
+            return Err(Error::NotAligned);
+        }
+        let offset = addr.checked_sub(self.base_addr).ok_or(Error::Io)?;
+        let index = offset as usize / 4;
+        self.mem.get(index..index + len).ok_or(Error::Io)
+    }
+
+    fn word(&self, addr: u32) -> Result<&'a AtomicU32> {
+        Ok(&self.words(addr, 1)?[0])
+    }
+}
+
+impl ChannelIo for AtomicChannelIo<'_> {
+    fn read_u32(&mut self, addr: u32) -> Result<u32> {
+        Ok(self.word(addr)?.load(Ordering::Acquire))
+    }
+
+    fn write_u32(&mut self, addr: u32, value: u32) -> Result<()> {
+        self.word(addr)?.store(value, Ordering::Release);
+        Ok(())
+    }
+
+    fn read_bulk(&mut self, addr: u32, buf: &mut [u32]) -> Result<()> {
+        let words = self.words(addr, buf.len())?;
+        fence(Ordering::Acquire);
+        for (word, value) in buf.iter_mut().zip(words) {
+            *word = value.load(Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    fn write_bulk(&mut self, addr: u32, data: &[u32]) -> Result<()> {
+        let words = self.words(addr, data.len())?;
+        for (word, value) in data.iter().zip(words) {
+            value.store(*word, Ordering::Relaxed);
+        }
+        fence(Ordering::Release);
+        Ok(())
+    }
+}
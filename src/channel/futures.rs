@@ -6,11 +6,17 @@
 
 use alloc::boxed::Box;
 use async_trait::async_trait;
+use core::borrow::BorrowMut;
+use core::marker::PhantomData;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
-use crate::channel::{ChannelActor, ChannelCb, ChannelFlags};
-use crate::channel::{check_base_addr, check_channel_size, consumer_only, producer_only};
+use crate::channel::{
+    AsyncDelay, ChannelActor, ChannelCb, ChannelFlags, Deadline, PollPolicy, Poller,
+};
+use crate::channel::{
+    bytes_to_word, check_base_addr, check_channel_size, consumer_only, producer_only, word_to_bytes,
+};
 use crate::io::{Reader, Writer};
 use crate::{Error, Result};
 
@@ -33,14 +39,30 @@ pub trait AsyncChannelIo {
     async fn write_bulk(&mut self, addr: u32, data: &[u32]) -> Result<()>;
 }
 
-/// Asynchronous unidirectional communication channel
-pub struct AsyncChannel<'a, I: AsyncChannelIo> {
-    io: &'a mut I,
+/// Asynchronous unidirectional communication channel, accessing the shared
+/// medium through `B`, which either borrows or owns the [`AsyncChannelIo`]
+/// object.
+///
+/// Normally used through one of its aliases:
+/// - [`AsyncChannel`] - borrows the [`AsyncChannelIo`] object
+/// - [`OwnedAsyncChannel`] - owns the [`AsyncChannelIo`] object, so it can be
+///   stored in structs and tasks, rather than created around every use
+pub struct GenericAsyncChannel<I: AsyncChannelIo, B: BorrowMut<I>> {
+    io: B,
     actor: ChannelActor,
     base_addr: u32,
+    poll_policy: PollPolicy,
+    // Only B holds an I, so the channel's auto traits follow B
+    _io: PhantomData<fn() -> I>,
 }
 
-impl<'a, I: AsyncChannelIo> AsyncChannel<'a, I> {
+/// Asynchronous channel, borrowing its [`AsyncChannelIo`] object
+pub type AsyncChannel<'a, I> = GenericAsyncChannel<I, &'a mut I>;
+
+/// Asynchronous channel, owning its [`AsyncChannelIo`] object
+pub type OwnedAsyncChannel<I> = GenericAsyncChannel<I, I>;
+
+impl<I: AsyncChannelIo, B: BorrowMut<I>> GenericAsyncChannel<I, B> {
     /// Create new channel with given size.  Used by the Target to initialize
     /// the channel.
     ///
@@ -51,12 +73,7 @@ impl<'a, I: AsyncChannelIo> AsyncChannel<'a, I> {
     /// - `base_addr` - Base address of the channel on that medium
     /// - `size` - Total size of the channel in bytes, including Control Block
     ///   and data portions.
-    pub async fn new(
-        io: &'a mut I,
-        actor: ChannelActor,
-        base_addr: u32,
-        size: usize,
-    ) -> Result<Self> {
+    pub async fn new(io: B, actor: ChannelActor, base_addr: u32, size: usize) -> Result<Self> {
         check_base_addr(base_addr)?;
         check_channel_size(size)?;
 
@@ -64,6 +81,8 @@ impl<'a, I: AsyncChannelIo> AsyncChannel<'a, I> {
             io,
             base_addr,
             actor,
+            poll_policy: PollPolicy::DEFAULT,
+            _io: PhantomData,
         };
 
         // Set channel size to 0 first.  Channel is only valid once size is non-zero.
@@ -91,13 +110,15 @@ impl<'a, I: AsyncChannelIo> AsyncChannel<'a, I> {
     ///   medium
     /// - `actor` - Whether the user is a Consumer or Producer
     /// - `base_addr` - Base address of the channel on that medium
-    pub async fn from_target(io: &'a mut I, actor: ChannelActor, base_addr: u32) -> Result<Self> {
+    pub async fn from_target(io: B, actor: ChannelActor, base_addr: u32) -> Result<Self> {
         check_base_addr(base_addr)?;
 
         let mut channel = Self {
             io,
             actor,
             base_addr,
+            poll_policy: PollPolicy::DEFAULT,
+            _io: PhantomData,
         };
 
         // Validate existing control block
@@ -111,6 +132,14 @@ impl<'a, I: AsyncChannelIo> AsyncChannel<'a, I> {
         }
     }
 
+    /// Set the policy used when waiting for the channel, for example by
+    /// [`Self::publish_bytes_when_ready()`].  Defaults to
+    /// [`PollPolicy::DEFAULT`].
+    pub fn with_poll_policy(mut self, policy: PollPolicy) -> Self {
+        self.poll_policy = policy;
+        self
+    }
+
     /// Producer: Atomically publish word-aligned data.
     ///
     /// Alternatively use [`Self::publish_bytes()`], which makes no
@@ -146,51 +175,55 @@ impl<'a, I: AsyncChannelIo> AsyncChannel<'a, I> {
     /// This is less efficient than [`Self::publish_data()`] where the data is
     /// guaranteed word aligned.
     pub async fn publish_bytes(&mut self, data: &[u8]) -> Result<()> {
-        producer_only(self.actor)?;
-
-        if data.len() > self.data_capacity().await? {
-            return Err(Error::PayloadTooLarge);
-        }
-
-        // Check availability
-        self.check_idle().await?;
+        self.publish_bytes_with_flags(data, ChannelFlags::Ok).await
+    }
 
-        let data_addr = self.data_start_addr();
+    /// Producer: Publish an empty response indicating that processing of the
+    /// last command was abandoned, following a cancel request from the
+    /// consumer of the command channel.  See
+    /// [`Self::cancel_requested()`].
+    pub async fn publish_cancelled(&mut self) -> Result<()> {
+        self.publish_bytes_with_flags(&[], ChannelFlags::Cancelled)
+            .await
+    }
 
-        // Write aligned portion with individual writes (convert bytes to words)
-        let word_count = data.len() / 4;
-        for word_idx in 0..word_count {
-            let byte_offset = word_idx * 4;
-            let word = u32::from_le_bytes([
-                data[byte_offset],
-                data[byte_offset + 1],
-                data[byte_offset + 2],
-                data[byte_offset + 3],
-            ]);
-            self.write_u32(data_addr + (word_idx as u32 * 4), word)
-                .await?;
-        }
+    /// Producer: Ask the consumer to abandon processing of the last published
+    /// data.  Does not publish any new data.
+    ///
+    /// The consumer is expected to acknowledge using
+    /// [`Self::publish_cancelled()`] on its response channel, although it
+    /// may have already completed processing, in which case the normal
+    /// response is sent instead.
+    pub async fn cancel(&mut self) -> Result<()> {
+        producer_only(self.actor)?;
+        self.write_flags(ChannelFlags::Cancel).await
+    }
 
-        // Handle remaining 1-3 bytes
-        let remaining = data.len() % 4;
-        if remaining > 0 {
-            let mut final_word = 0u32;
-            let base_offset = word_count * 4;
-            for i in 0..remaining {
-                final_word |= (data[base_offset + i] as u32) << (i * 8);
-            }
-            self.write_u32(data_addr + (base_offset as u32), final_word)
-                .await?;
-        }
+    /// Consumer: Check whether the producer has asked for the last published
+    /// data to be abandoned.  Typically polled by a Target while handling a
+    /// long-running command.
+    ///
+    /// Remains set until the producer next publishes.
+    pub async fn cancel_requested(&mut self) -> Result<bool> {
+        consumer_only(self.actor)?;
+        Ok(self.read_flags().await? == ChannelFlags::Cancel)
+    }
 
-        // Write metadata before publishing
-        self.write_data_size(data.len()).await?;
-        self.write_flags(ChannelFlags::Ok).await?;
+    /// Get the flags associated with the currently published data.
+    pub async fn flags(&mut self) -> Result<ChannelFlags> {
+        self.read_flags().await
+    }
 
-        // Atomically publish by incrementing producer_seq last
-        self.inc_producer_seq().await?;
+    /// Get the producer sequence number - the number of times data has been
+    /// published on this channel (wrapping).
+    pub async fn producer_seq(&mut self) -> Result<u32> {
+        self.read_producer_seq().await
+    }
 
-        Ok(())
+    /// Get the consumer sequence number - the producer sequence number of the
+    /// last data consumed from this channel.
+    pub async fn consumer_seq(&mut self) -> Result<u32> {
+        self.read_consumer_seq().await
     }
 
     /// Producer: Check if channel is available for publishing.
@@ -198,6 +231,63 @@ impl<'a, I: AsyncChannelIo> AsyncChannel<'a, I> {
         self.idle().await
     }
 
+    /// Producer: Wait until the channel is available, and then publish
+    /// word-aligned data, as [`Self::publish_data()`].
+    ///
+    /// Polls [`Self::can_publish()`], waiting in between according to the
+    /// channel's [`PollPolicy`].
+    ///
+    /// Arguments:
+    /// - `data` - Data to publish
+    /// - `deadline` - When to stop waiting, for example
+    ///   [`crate::channel::NoDeadline`]
+    ///
+    /// Returns [`Error::Timeout`] if the deadline passed before the channel
+    /// became available.
+    pub async fn publish_data_when_ready<D: AsyncDelay>(
+        &mut self,
+        data: &[u32],
+        deadline: impl Deadline,
+    ) -> Result<()> {
+        self.wait_until_ready::<D>(deadline).await?;
+        self.publish_data(data).await
+    }
+
+    /// Producer: Wait until the channel is available, and then publish byte
+    /// data, as [`Self::publish_bytes()`].
+    ///
+    /// Polls [`Self::can_publish()`], waiting in between according to the
+    /// channel's [`PollPolicy`].
+    ///
+    /// Arguments:
+    /// - `data` - Data to publish
+    /// - `deadline` - When to stop waiting, for example
+    ///   [`crate::channel::NoDeadline`]
+    ///
+    /// Returns [`Error::Timeout`] if the deadline passed before the channel
+    /// became available.
+    pub async fn publish_bytes_when_ready<D: AsyncDelay>(
+        &mut self,
+        data: &[u8],
+        deadline: impl Deadline,
+    ) -> Result<()> {
+        self.wait_until_ready::<D>(deadline).await?;
+        self.publish_bytes(data).await
+    }
+
+    // Waits until the channel can be published to
+    async fn wait_until_ready<D: AsyncDelay>(&mut self, mut deadline: impl Deadline) -> Result<()> {
+        producer_only(self.actor)?;
+        let mut poller = Poller::new(self.poll_policy);
+        while !self.can_publish().await? {
+            if deadline.expired() {
+                return Err(Error::Timeout);
+            }
+            poller.wait::<D>().await;
+        }
+        Ok(())
+    }
+
     /// Consumer: Atomically consume data as bytes.
     ///
     /// Less efficient than [`Self::consume_data()`], but handles numbers of
@@ -217,23 +307,7 @@ impl<'a, I: AsyncChannelIo> AsyncChannel<'a, I> {
 
         let data_addr = self.data_start_addr();
 
-        // Read aligned portion with individual u32 reads (convert to bytes)
-        let word_count = data_size / 4;
-        for word_idx in 0..word_count {
-            let word = self.read_u32(data_addr + (word_idx as u32 * 4)).await?;
-            let bytes = word.to_le_bytes();
-            let base_offset = word_idx * 4;
-            buf[base_offset..base_offset + 4].copy_from_slice(&bytes);
-        }
-
-        // Handle remaining 1-3 bytes
-        let remaining = data_size % 4;
-        if remaining > 0 {
-            let final_word = self.read_u32(data_addr + (word_count as u32 * 4)).await?;
-            let bytes = final_word.to_le_bytes();
-            let base_offset = word_count * 4;
-            buf[base_offset..base_offset + remaining].copy_from_slice(&bytes[..remaining]);
-        }
+        self.read_bytes(data_addr, &mut buf[..data_size]).await?;
 
         // Atomically consume by updating consumer_seq last
         self.set_consumer_seq_to_producer().await?;
@@ -271,6 +345,17 @@ impl<'a, I: AsyncChannelIo> AsyncChannel<'a, I> {
         Ok(word_size)
     }
 
+    /// Consumer: Discard the available data without reading it.  Used, for
+    /// example, when the data is too large for the consumer's buffer.
+    pub async fn discard(&mut self) -> Result<()> {
+        consumer_only(self.actor)?;
+
+        self.check_busy().await?;
+
+        // Atomically consume by updating consumer_seq
+        self.set_consumer_seq_to_producer().await
+    }
+
     /// Consumer: Check available data size.  Use to both check if there is
     /// data available to be read, and also how much.
     pub async fn data_available(&mut self) -> Result<Option<usize>> {
@@ -282,10 +367,29 @@ impl<'a, I: AsyncChannelIo> AsyncChannel<'a, I> {
         }
     }
 
+    /// Get an [`AsyncChannel`] borrowing this channel's [`AsyncChannelIo`]
+    /// object, for use with functions taking an [`AsyncChannel`].
+    pub fn as_channel(&mut self) -> AsyncChannel<'_, I> {
+        GenericAsyncChannel {
+            io: self.io.borrow_mut(),
+            actor: self.actor,
+            base_addr: self.base_addr,
+            poll_policy: self.poll_policy,
+            _io: PhantomData,
+        }
+    }
+
+    /// Consume the channel, returning its [`AsyncChannelIo`] object, or
+    /// borrow of it
+    pub fn into_io(self) -> B {
+        self.io
+    }
+
     /// Get data capacity for this channel
     pub async fn data_capacity(&mut self) -> Result<usize> {
         let channel_size = self
             .io
+            .borrow_mut()
             .read_u32(self.base_addr + ChannelCb::channel_size_offset())
             .await? as usize;
         Ok(channel_size - (ChannelCb::data_offset() as usize))
@@ -293,9 +397,20 @@ impl<'a, I: AsyncChannelIo> AsyncChannel<'a, I> {
 }
 
 // Internal functions
-impl<I: AsyncChannelIo> AsyncChannel<'_, I> {
+impl<I: AsyncChannelIo, B: BorrowMut<I>> GenericAsyncChannel<I, B> {
+    #[cfg(feature = "stream")]
+    pub(crate) fn actor(&self) -> ChannelActor {
+        self.actor
+    }
+
+    #[cfg(feature = "stream")]
+    pub(crate) fn poll_policy(&self) -> PollPolicy {
+        self.poll_policy
+    }
+
     async fn write_channel_size(&mut self, size: usize) -> Result<()> {
         self.io
+            .borrow_mut()
             .write_u32(
                 self.base_addr + ChannelCb::channel_size_offset(),
                 size as u32,
@@ -305,24 +420,28 @@ impl<I: AsyncChannelIo> AsyncChannel<'_, I> {
 
     async fn write_producer_seq(&mut self, seq: u32) -> Result<()> {
         self.io
+            .borrow_mut()
             .write_u32(self.base_addr + ChannelCb::producer_seq_offset(), seq)
             .await
     }
 
     async fn write_consumer_seq(&mut self, seq: u32) -> Result<()> {
         self.io
+            .borrow_mut()
             .write_u32(self.base_addr + ChannelCb::consumer_seq_offset(), seq)
             .await
     }
 
     async fn write_flags(&mut self, flags: ChannelFlags) -> Result<()> {
         self.io
+            .borrow_mut()
             .write_u32(self.base_addr + ChannelCb::flags_offset(), flags as u32)
             .await
     }
 
     async fn write_data_size(&mut self, size: usize) -> Result<()> {
         self.io
+            .borrow_mut()
             .write_u32(self.base_addr + ChannelCb::data_size_offset(), size as u32)
             .await
     }
@@ -330,6 +449,7 @@ impl<I: AsyncChannelIo> AsyncChannel<'_, I> {
     async fn read_channel_size(&mut self) -> Result<usize> {
         let channel_size = self
             .io
+            .borrow_mut()
             .read_u32(self.base_addr + ChannelCb::channel_size_offset())
             .await? as usize;
         Ok(channel_size)
@@ -337,20 +457,22 @@ impl<I: AsyncChannelIo> AsyncChannel<'_, I> {
 
     async fn read_producer_seq(&mut self) -> Result<u32> {
         self.io
+            .borrow_mut()
             .read_u32(self.base_addr + ChannelCb::producer_seq_offset())
             .await
     }
 
     async fn read_consumer_seq(&mut self) -> Result<u32> {
         self.io
+            .borrow_mut()
             .read_u32(self.base_addr + ChannelCb::consumer_seq_offset())
             .await
     }
 
-    #[allow(dead_code)]
     async fn read_flags(&mut self) -> Result<ChannelFlags> {
         let flags = self
             .io
+            .borrow_mut()
             .read_u32(self.base_addr + ChannelCb::flags_offset())
             .await?;
         Ok(ChannelFlags::from(flags))
@@ -359,29 +481,72 @@ impl<I: AsyncChannelIo> AsyncChannel<'_, I> {
     async fn read_data_size(&mut self) -> Result<usize> {
         let data_size = self
             .io
+            .borrow_mut()
             .read_u32(self.base_addr + ChannelCb::data_size_offset())
             .await? as usize;
         Ok(data_size)
     }
 
+    async fn publish_bytes_with_flags(&mut self, data: &[u8], flags: ChannelFlags) -> Result<()> {
+        producer_only(self.actor)?;
+
+        if data.len() > self.data_capacity().await? {
+            return Err(Error::PayloadTooLarge);
+        }
+
+        // Check availability
+        self.check_idle().await?;
+
+        let data_addr = self.data_start_addr();
+
+        self.write_bytes(data_addr, data).await?;
+
+        // Write metadata before publishing
+        self.write_data_size(data.len()).await?;
+        self.write_flags(flags).await?;
+
+        // Atomically publish by incrementing producer_seq last
+        self.inc_producer_seq().await?;
+
+        Ok(())
+    }
+
+    // Writes bytes to a word-aligned address, using word writes
+    async fn write_bytes(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        for (word_idx, chunk) in data.chunks(4).enumerate() {
+            self.write_u32(addr + (word_idx as u32 * 4), bytes_to_word(chunk))
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Reads bytes from a word-aligned address, using word reads
+    async fn read_bytes(&mut self, addr: u32, buf: &mut [u8]) -> Result<()> {
+        for (word_idx, chunk) in buf.chunks_mut(4).enumerate() {
+            let word = self.read_u32(addr + (word_idx as u32 * 4)).await?;
+            word_to_bytes(word, chunk);
+        }
+        Ok(())
+    }
+
     fn data_start_addr(&mut self) -> u32 {
         self.base_addr + ChannelCb::data_offset()
     }
 
     async fn write_bulk(&mut self, addr: u32, data: &[u32]) -> Result<()> {
-        self.io.write_bulk(addr, data).await
+        self.io.borrow_mut().write_bulk(addr, data).await
     }
 
     async fn read_bulk(&mut self, addr: u32, buf: &mut [u32]) -> Result<()> {
-        self.io.read_bulk(addr, buf).await
+        self.io.borrow_mut().read_bulk(addr, buf).await
     }
 
     async fn write_u32(&mut self, addr: u32, value: u32) -> Result<()> {
-        self.io.write_u32(addr, value).await
+        self.io.borrow_mut().write_u32(addr, value).await
     }
 
     async fn read_u32(&mut self, addr: u32) -> Result<u32> {
-        self.io.read_u32(addr).await
+        self.io.borrow_mut().read_u32(addr).await
     }
 
     async fn idle(&mut self) -> Result<bool> {
@@ -422,6 +587,15 @@ impl<I: AsyncChannelIo> AsyncChannel<'_, I> {
 // lifetimes - this allows borrowing of both to be decoupled from each other.
 pub type ReaderWriterChannel<'a, 'b, R, W> = AsyncChannel<'a, ReaderWriterChannelIo<'b, R, W>>;
 
+/// Async Reader/Writer channel type, owning its [`ReaderWriterChannelIo`], so
+/// it can be kept for as long as the reader and writer are borrowed.
+/// Typically used by a Host.
+///
+/// To keep several channels to the same Target, give each its own reader and
+/// writer, for example handles to a shared debug interface from
+/// `client::SharedIo`.
+pub type OwnedReaderWriterChannel<'a, R, W> = OwnedAsyncChannel<ReaderWriterChannelIo<'a, R, W>>;
+
 /// Channel I/O implementation using [`crate::io::Reader`] and
 /// [`crate::io::Writer`] traits.
 pub struct ReaderWriterChannelIo<'a, R: Reader, W: Writer> {
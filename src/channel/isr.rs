@@ -0,0 +1,160 @@
+//! Interrupt-safe channels, for use by a Target.
+//!
+//! A [`crate::channel::Channel`] needs `&mut` access, so can't be shared
+//! between an interrupt handler and the main loop.  [`IsrChannel`] wraps an
+//! [`OwnedChannel`] in a `critical-section` mutex, so it can be stored in a
+//! `static` and used from both, for example publishing from an ISR and
+//! consuming in the main loop, or the other way round.
+//!
+//! Every operation runs within a short critical section and never waits for
+//! the other side - the `try_*` methods return immediately if the channel is
+//! busy or has no data.
+//!
+//! The application must provide a `critical-section` implementation.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{ChannelIo, OwnedChannel, RamChannelIo};
+use crate::{Error, Result};
+
+/// Channel which can be shared between interrupt and thread contexts.  See
+/// [`crate::channel::isr`].
+///
+/// ```rust,ignore
+/// use airfrog_rpc::channel::{ChannelActor, IsrChannel, OwnedRamChannel, RamChannelIo};
+///
+/// static EVENT_CH: IsrChannel = IsrChannel::new();
+///
+/// // In main, before enabling the interrupt
+/// EVENT_CH.init(OwnedRamChannel::new(RamChannelIo::new(), ChannelActor::Producer, EVENT_CH_ADDR, 256)?);
+///
+/// #[interrupt]
+/// fn GPIO() {
+///     // Drop the event if the Host hasn't consumed the last one
+///     let _ = EVENT_CH.try_publish_bytes(&[BUTTON_PRESSED]);
+/// }
+/// ```
+pub struct IsrChannel<I: ChannelIo = RamChannelIo> {
+    channel: Mutex<RefCell<Option<OwnedChannel<I>>>>,
+}
+
+impl<I: ChannelIo> IsrChannel<I> {
+    /// Create a new IsrChannel, without a channel.  Operations return
+    /// [`Error::Uninit`] until [`Self::init()`] is called.
+    // We need a new() rather than a default() as it must be const.
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self {
+            channel: Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    /// Create a new IsrChannel, wrapping a channel
+    pub fn from_channel(channel: OwnedChannel<I>) -> Self {
+        Self {
+            channel: Mutex::new(RefCell::new(Some(channel))),
+        }
+    }
+
+    /// Set the channel, returning any previous one
+    pub fn init(&self, channel: OwnedChannel<I>) -> Option<OwnedChannel<I>> {
+        critical_section::with(|cs| self.channel.borrow_ref_mut(cs).replace(channel))
+    }
+
+    /// Remove the channel, returning it
+    pub fn take(&self) -> Option<OwnedChannel<I>> {
+        critical_section::with(|cs| self.channel.borrow_ref_mut(cs).take())
+    }
+
+    /// Run a function on the channel within a critical section, for
+    /// operations not provided by IsrChannel.  The function should return
+    /// quickly, as interrupts are disabled while it runs.
+    ///
+    /// Returns:
+    /// - `Ok(value)`: The function's return value
+    /// - `Err(Error::Uninit)`: No channel has been set
+    /// - `Err(Error::Busy)`: The channel is already in use, by a function
+    ///   passed to `with()` which has called back into this IsrChannel
+    pub fn with<T>(&self, f: impl FnOnce(&mut OwnedChannel<I>) -> Result<T>) -> Result<T> {
+        critical_section::with(|cs| {
+            let mut channel = self
+                .channel
+                .borrow(cs)
+                .try_borrow_mut()
+                .map_err(|_| Error::Busy)?;
+            f(channel.as_mut().ok_or(Error::Uninit)?)
+        })
+    }
+
+    /// Producer: Publish byte data, if the channel is available.
+    ///
+    /// Returns:
+    /// - `Ok(true)`: Data published
+    /// - `Ok(false)`: The consumer has not yet consumed the last data
+    /// - `Err(error)`: Error occurred, as [`OwnedChannel::publish_bytes()`],
+    ///   or [`Self::with()`]
+    pub fn try_publish_bytes(&self, data: &[u8]) -> Result<bool> {
+        self.with(|channel| published(channel.publish_bytes(data)))
+    }
+
+    /// Producer: Publish word-aligned data, if the channel is available.
+    ///
+    /// Returns as [`Self::try_publish_bytes()`].
+    pub fn try_publish_data(&self, data: &[u32]) -> Result<bool> {
+        self.with(|channel| published(channel.publish_data(data)))
+    }
+
+    /// Consumer: Consume data as bytes, if available.
+    ///
+    /// Returns:
+    /// - `Ok(Some(len))`: Data consumed, `len` bytes
+    /// - `Ok(None)`: No data available
+    /// - `Err(error)`: Error occurred, as [`OwnedChannel::consume_bytes()`],
+    ///   or [`Self::with()`]
+    pub fn try_consume_bytes(&self, buf: &mut [u8]) -> Result<Option<usize>> {
+        self.with(|channel| consumed(channel.consume_bytes(buf)))
+    }
+
+    /// Consumer: Consume data as words, if available.
+    ///
+    /// Returns as [`Self::try_consume_bytes()`], with the number of full words
+    /// consumed.
+    pub fn try_consume_data(&self, buf: &mut [u32]) -> Result<Option<usize>> {
+        self.with(|channel| consumed(channel.consume_data(buf)))
+    }
+
+    /// Producer: Check if channel is available for publishing.
+    pub fn can_publish(&self) -> Result<bool> {
+        self.with(|channel| channel.can_publish())
+    }
+
+    /// Consumer: Check available data size in bytes.
+    pub fn data_available(&self) -> Result<Option<usize>> {
+        self.with(|channel| channel.data_available())
+    }
+}
+
+// Converts the result of a publish into whether the data was published
+fn published(result: Result<()>) -> Result<bool> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(Error::Busy) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// Converts the result of a consume into the length consumed, if any
+fn consumed(result: Result<usize>) -> Result<Option<usize>> {
+    match result {
+        Ok(len) => Ok(Some(len)),
+        Err(Error::NoData) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
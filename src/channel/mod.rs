@@ -6,13 +6,38 @@
 //
 // MIT License
 
+pub mod atomic;
 #[cfg(feature = "async")]
 pub mod futures;
+#[cfg(feature = "isr")]
+pub mod isr;
+#[cfg(feature = "async")]
+pub mod poll;
+pub mod queue;
+pub mod region;
+#[cfg(feature = "stream")]
+pub mod stream;
 pub mod sync;
+pub mod wait;
 
+pub use atomic::AtomicChannelIo;
+#[cfg(feature = "async")]
+pub use futures::{
+    AsyncChannel, AsyncChannelIo, GenericAsyncChannel, OwnedAsyncChannel, OwnedReaderWriterChannel,
+    ReaderWriterChannel, ReaderWriterChannelIo,
+};
+#[cfg(feature = "isr")]
+pub use isr::IsrChannel;
 #[cfg(feature = "async")]
-pub use futures::{AsyncChannel, AsyncChannelIo, ReaderWriterChannel, ReaderWriterChannelIo};
-pub use sync::{Channel, ChannelIo, RamChannel, RamChannelIo};
+pub use poll::{AsyncDelay, PollPolicy, Poller};
+pub use queue::{CommandQueue, Polled};
+pub use region::StaticChannelRegion;
+#[cfg(feature = "stream")]
+pub use stream::{ChannelSink, ChannelStream};
+pub use sync::{
+    Channel, ChannelIo, GenericChannel, OwnedChannel, OwnedRamChannel, RamChannel, RamChannelIo,
+};
+pub use wait::{Deadline, MaxWaits, NoDeadline, WaitHook};
 
 use crate::{Error, Result};
 
@@ -37,7 +62,7 @@ pub struct ChannelCb {
     /// Consumer sequence number - incremented when data is consumed
     pub consumer_seq: u32,
 
-    /// Status flags - currently unused
+    /// Status flags - see [`ChannelFlags`]
     pub flags: ChannelFlags,
 
     /// Size of data payload in bytes
@@ -90,8 +115,19 @@ impl ChannelCb {
     }
 }
 
-/// Channel status flags
+/// Channel status flags.  Written by the producer.
+///
+/// [`ChannelFlags::Cancel`] and [`ChannelFlags::Cancelled`] implement
+/// cancellation of in-flight requests:
+/// - The Host raises [`ChannelFlags::Cancel`] on the command channel, without
+///   publishing new data.  The Target polls for this while handling a
+///   command.
+/// - The Target acknowledges by publishing an empty response with
+///   [`ChannelFlags::Cancelled`] on the response channel.
+///
+/// The flags are reset to [`ChannelFlags::Ok`] by the next publish.
 #[repr(u32)]
+#[non_exhaustive]
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub enum ChannelFlags {
     #[default]
@@ -99,6 +135,10 @@ pub enum ChannelFlags {
     Busy = 1,
     Error = 2,
     Timeout = 3,
+    /// Producer has asked the consumer to abandon the last published data
+    Cancel = 4,
+    /// Producer abandoned processing, in response to a cancel request
+    Cancelled = 5,
 }
 
 impl From<u32> for ChannelFlags {
@@ -108,6 +148,8 @@ impl From<u32> for ChannelFlags {
             1 => ChannelFlags::Busy,
             2 => ChannelFlags::Error,
             3 => ChannelFlags::Timeout,
+            4 => ChannelFlags::Cancel,
+            5 => ChannelFlags::Cancelled,
             _ => ChannelFlags::Error,
         }
     }
@@ -120,7 +162,7 @@ const fn min_channel_size() -> usize {
 }
 
 fn check_base_addr(addr: u32) -> Result<()> {
-    if addr % 4 != 0 {
+    if !addr.is_multiple_of(4) {
         Err(Error::NotAligned)
     } else {
         Ok(())
@@ -150,3 +192,16 @@ fn producer_only(actor: ChannelActor) -> Result<()> {
         Ok(())
     }
 }
+
+// Packs up to 4 bytes into a little-endian word, zero padding the remainder
+fn bytes_to_word(bytes: &[u8]) -> u32 {
+    let mut word = [0u8; 4];
+    word[..bytes.len()].copy_from_slice(bytes);
+    u32::from_le_bytes(word)
+}
+
+// Unpacks a little-endian word into up to 4 bytes, dropping the remainder
+fn word_to_bytes(word: u32, bytes: &mut [u8]) {
+    let len = bytes.len();
+    bytes.copy_from_slice(&word.to_le_bytes()[..len]);
+}
@@ -0,0 +1,210 @@
+//! Polling policy, controlling how a Host waits for a Target.
+//!
+//! Used by asynchronous Channels, and the clients built on them, so lives
+//! here rather than in [`crate::client`], which re-exports it.
+//!
+//! Hosts wait by polling the Target's memory, delaying in between using
+//! [`AsyncDelay`].  A [`PollPolicy`] decides how long each delay should be:
+//!
+//! - First, poll a number of times without delaying, so fast operations
+//!   complete with minimal latency.  The task yields to the executor between
+//!   these polls, so other tasks still run.
+//! - Then delay, starting at an initial interval and doubling after each
+//!   poll, up to a cap, so slow operations don't flood the debug interface.
+//! - Start again from the beginning whenever data arrives.
+//!
+//! The delay is passed to [`AsyncDelay::delay_for()`] as a hint.
+//!
+//! The default policy, [`PollPolicy::DEFAULT`], never spins and always uses
+//! the same interval, so [`AsyncDelay`] implementations which only implement
+//! [`AsyncDelay::delay()`] behave as before.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use core::future::poll_fn;
+use core::task::Poll;
+use core::time::Duration;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+/// Yield delay for async polling loops.
+///
+/// Application must provide an implementation of this trait in order for the
+/// async client to be able to yield, waiting for a response from the other
+/// side of the channel.
+///
+/// This trait keeps `airfrog-rpc` free of any specific async runtime.
+///
+/// Example:
+///
+/// ```rust,ignore
+/// use embassy_time::{Duration, Timer};
+/// struct Delay;
+/// impl AsyncDelay for Delay {
+///     async fn delay() {
+///         Timer::after(Duration::from_millis(50)).await;
+///     }
+/// }
+/// ```
+pub trait AsyncDelay {
+    fn delay() -> impl Future<Output = ()>;
+
+    /// Delay for approximately `duration`, a hint from the
+    /// [`PollPolicy`] in use.
+    ///
+    /// The default implementation ignores the hint, and calls
+    /// [`Self::delay()`].  Implement this to support adaptive polling:
+    ///
+    /// ```rust,ignore
+    /// impl AsyncDelay for Delay {
+    ///     async fn delay() {
+    ///         Timer::after(Duration::from_millis(50)).await;
+    ///     }
+    ///
+    ///     async fn delay_for(duration: core::time::Duration) {
+    ///         Timer::after(Duration::from_micros(duration.as_micros() as u64)).await;
+    ///     }
+    /// }
+    /// ```
+    fn delay_for(duration: Duration) -> impl Future<Output = ()> {
+        let _ = duration;
+        Self::delay()
+    }
+}
+
+/// How a Host waits between polls of a Target.  See [`crate::channel::poll`].
+///
+/// ```rust,ignore
+/// use core::time::Duration;
+/// use airfrog_rpc::client::PollPolicy;
+///
+/// // Poll 10 times immediately, then after 100us, 200us, 400us, ... 20ms
+/// let policy = PollPolicy::adaptive(10, Duration::from_micros(100), Duration::from_millis(20));
+/// let mut client = AsyncRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, config)
+///     .with_poll_policy(policy);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollPolicy {
+    /// Number of polls before delaying, yielding to the executor in between
+    pub spins: u32,
+
+    /// First delay, after spinning
+    pub initial: Duration,
+
+    /// Maximum delay
+    pub max: Duration,
+}
+
+impl PollPolicy {
+    /// Default policy - a fixed 50ms delay between polls, without spinning
+    pub const DEFAULT: Self = Self::fixed(Duration::from_millis(50));
+
+    /// Policy with a fixed delay between polls, without spinning
+    pub const fn fixed(interval: Duration) -> Self {
+        Self {
+            spins: 0,
+            initial: interval,
+            max: interval,
+        }
+    }
+
+    /// Policy which spins, then backs off exponentially
+    ///
+    /// Arguments:
+    /// - `spins` - Number of polls before delaying
+    /// - `initial` - First delay, after spinning
+    /// - `max` - Maximum delay.  Values less than `initial` are treated as
+    ///   `initial`.
+    pub const fn adaptive(spins: u32, initial: Duration, max: Duration) -> Self {
+        let max = if max.as_nanos() < initial.as_nanos() {
+            initial
+        } else {
+            max
+        };
+        Self {
+            spins,
+            initial,
+            max,
+        }
+    }
+}
+
+impl Default for PollPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Waits between polls according to a [`PollPolicy`], for use in polling
+/// loops.
+///
+/// ```rust,ignore
+/// let mut poller = Poller::new(policy);
+/// let size = loop {
+///     if let Some(size) = channel.data_available().await? {
+///         break size;
+///     }
+///     poller.wait::<Delay>().await;
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct Poller {
+    policy: PollPolicy,
+    polls: u32,
+    delay: Duration,
+}
+
+impl Poller {
+    /// Create a new Poller
+    pub const fn new(policy: PollPolicy) -> Self {
+        Self {
+            policy,
+            polls: 0,
+            delay: policy.initial,
+        }
+    }
+
+    /// Wait before polling again - spinning, or delaying for the current
+    /// interval and then backing off.  Spinning yields to the executor once,
+    /// so other tasks can run.
+    pub async fn wait<D: AsyncDelay>(&mut self) {
+        self.polls = self.polls.saturating_add(1);
+        if self.polls <= self.policy.spins {
+            yield_now().await;
+            return;
+        }
+        D::delay_for(self.delay).await;
+        self.delay = self.delay.saturating_mul(2).min(self.policy.max);
+    }
+
+    /// Start again from the beginning of the policy, for example because
+    /// data has arrived
+    pub fn reset(&mut self) {
+        self.polls = 0;
+        self.delay = self.policy.initial;
+    }
+
+    /// Number of times [`Self::wait()`] has been called since the Poller was
+    /// created or reset
+    pub fn polls(&self) -> u32 {
+        self.polls
+    }
+}
+
+// Returns Pending once, waking the task immediately, so the executor can run
+// other tasks before polling this one again
+async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
@@ -0,0 +1,209 @@
+//! Command queue - typically used by a Target to handle pipelined commands.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use core::borrow::BorrowMut;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{ChannelIo, GenericChannel};
+use crate::envelope::Envelope;
+use crate::{Error, Result};
+
+/// Fixed-size queue of enveloped commands, allowing a Target to accept
+/// multiple commands from a pipelined Host and answer them out of order.
+///
+/// Does not allocate.  Holds up to `SLOTS` commands, each with a payload of
+/// up to `SIZE` bytes.
+///
+/// Typical usage in a Target's main loop:
+/// 1. Call [`Self::poll()`] to move a command from the command channel into
+///    the queue, freeing the command channel for the Host's next command.
+///    Commands too large for the queue are discarded, and should be answered
+///    with [`Envelope::FLAG_ERROR`] so the Host isn't left waiting.
+/// 2. Process queued commands in whatever order suits, using
+///    [`Self::iter()`] or [`Self::get()`].
+/// 3. Call [`Self::respond()`] to publish a response to a queued command,
+///    which frees its slot.
+///
+/// When the queue is full, commands remain in the command channel, and the
+/// Host waits before sending more.
+///
+/// ```rust,ignore
+/// let mut queue: CommandQueue<4, 256> = CommandQueue::new();
+///
+/// loop {
+///     if let Polled::Discarded(envelope) = queue.poll(&mut cmd_ch)? {
+///         rsp_ch.publish_response(&envelope, Envelope::FLAG_ERROR, &[])?;
+///     }
+///     if let Some((envelope, payload)) = queue.iter().next() {
+///         let request_id = envelope.request_id;
+///         let len = handle(envelope.msg_type, payload, &mut rsp_buf);
+///         queue.respond(&mut rsp_ch, request_id, 0, &rsp_buf[..len])?;
+///     }
+/// }
+/// ```
+pub struct CommandQueue<const SLOTS: usize, const SIZE: usize> {
+    slots: [Slot<SIZE>; SLOTS],
+}
+
+/// Outcome of [`CommandQueue::poll()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polled {
+    /// No command was queued, as none was available or the queue is full
+    Idle,
+
+    /// A command was moved into the queue
+    Queued,
+
+    /// A command with a payload larger than the queue's slots was removed
+    /// from the command channel and discarded.  Answer it, for example with
+    /// [`Envelope::FLAG_ERROR`], so the Host isn't left waiting.
+    Discarded(Envelope),
+}
+
+struct Slot<const SIZE: usize> {
+    envelope: Option<Envelope>,
+    len: usize,
+    buf: [u8; SIZE],
+}
+
+impl<const SIZE: usize> Slot<SIZE> {
+    const fn new() -> Self {
+        Self {
+            envelope: None,
+            len: 0,
+            buf: [0; SIZE],
+        }
+    }
+}
+
+impl<const SLOTS: usize, const SIZE: usize> CommandQueue<SLOTS, SIZE> {
+    /// Create a new, empty, CommandQueue
+    // We need a new() rather than a default() as it must be const.
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { Slot::new() }; SLOTS],
+        }
+    }
+
+    /// Move a command from the command channel into the queue, if one is
+    /// available and there is a free slot.
+    ///
+    /// Returns:
+    /// - `Ok(Polled::Queued)`: A command was queued
+    /// - `Ok(Polled::Idle)`: No command was available, or the queue is full
+    /// - `Ok(Polled::Discarded(envelope))`: The command's payload was larger
+    ///   than `SIZE`, so it was discarded.  The Target should answer it.
+    /// - `Err(Error::Malformed)`: The command did not have a valid envelope,
+    ///   and was discarded
+    /// - `Err(error)`: Other error accessing the channel
+    pub fn poll<I: ChannelIo, B: BorrowMut<I>>(
+        &mut self,
+        cmd_ch: &mut GenericChannel<I, B>,
+    ) -> Result<Polled> {
+        let Some(slot) = self.slots.iter_mut().find(|slot| slot.envelope.is_none()) else {
+            return Ok(Polled::Idle);
+        };
+
+        match cmd_ch.consume_envelope(&mut slot.buf) {
+            Ok((envelope, len)) => {
+                trace!("Queued command {}", envelope.request_id);
+                slot.envelope = Some(envelope);
+                slot.len = len;
+                Ok(Polled::Queued)
+            }
+            Err(Error::NoData) => Ok(Polled::Idle),
+            Err(Error::BufferTooSmall) => {
+                let envelope = cmd_ch.discard_envelope()?;
+                warn!(
+                    "Discarded command {}, with {} byte payload",
+                    envelope.request_id, envelope.length
+                );
+                Ok(Polled::Discarded(envelope))
+            }
+            Err(Error::Malformed | Error::PayloadTooLarge) => {
+                warn!("Discarded malformed command");
+                cmd_ch.discard()?;
+                Err(Error::Malformed)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Number of queued commands
+    pub fn len(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| slot.envelope.is_some())
+            .count()
+    }
+
+    /// Whether there are no queued commands
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether all slots are in use
+    pub fn is_full(&self) -> bool {
+        self.len() == SLOTS
+    }
+
+    /// Iterate over the queued commands, returning each command's envelope
+    /// and payload.
+    pub fn iter(&self) -> impl Iterator<Item = (&Envelope, &[u8])> {
+        self.slots.iter().filter_map(|slot| {
+            slot.envelope
+                .as_ref()
+                .map(|envelope| (envelope, &slot.buf[..slot.len]))
+        })
+    }
+
+    /// Get the queued command with the given request ID
+    pub fn get(&self, request_id: u32) -> Option<(&Envelope, &[u8])> {
+        self.iter()
+            .find(|(envelope, _)| envelope.request_id == request_id)
+    }
+
+    /// Publish a response to a queued command, and remove it from the queue.
+    ///
+    /// Arguments:
+    /// - `rsp_ch` - Response channel
+    /// - `request_id` - Request ID of the queued command
+    /// - `flags` - Envelope flags, for example [`Envelope::FLAG_ERROR`]
+    /// - `payload` - Response payload
+    ///
+    /// Returns [`Error::Busy`] if the Host has not yet consumed the previous
+    /// response, in which case the command remains queued.  Returns
+    /// [`Error::InvalidOperation`] if there is no such queued command.
+    pub fn respond<I: ChannelIo, B: BorrowMut<I>>(
+        &mut self,
+        rsp_ch: &mut GenericChannel<I, B>,
+        request_id: u32,
+        flags: u8,
+        payload: &[u8],
+    ) -> Result<()> {
+        let slot = self.slot_mut(request_id).ok_or(Error::InvalidOperation)?;
+        let envelope = slot.envelope.ok_or(Error::InvalidOperation)?;
+
+        rsp_ch.publish_response(&envelope, flags, payload)?;
+        slot.envelope = None;
+        Ok(())
+    }
+
+    /// Remove a queued command without responding to it
+    pub fn remove(&mut self, request_id: u32) -> Option<Envelope> {
+        self.slot_mut(request_id)
+            .and_then(|slot| slot.envelope.take())
+    }
+
+    fn slot_mut(&mut self, request_id: u32) -> Option<&mut Slot<SIZE>> {
+        self.slots.iter_mut().find(|slot| {
+            slot.envelope
+                .is_some_and(|envelope| envelope.request_id == request_id)
+        })
+    }
+}
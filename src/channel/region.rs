@@ -0,0 +1,200 @@
+//! Statically allocated channel regions, for use by a Target.
+//!
+//! Rather than reserving SRAM for each channel by hand, and passing its
+//! address and size to [`crate::channel::RamChannel::new()`], a Target can
+//! declare a [`StaticChannelRegion`].  Its size is checked at compile time,
+//! it is always suitably aligned, and it can be placed in a named linker
+//! section so the linker script can fix its location.
+//!
+//! The [`crate::static_channel`] macro declares a region and creates its
+//! channel in one line, exporting the region's address and length as symbols
+//! for the Host to find, for example from the Target's ELF file.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::Result;
+use crate::channel::{ChannelActor, OwnedRamChannel, RamChannelIo, min_channel_size};
+
+/// Default linker section for regions declared by [`crate::static_channel`].
+///
+/// `cortex-m-rt`'s linker script places `.uninit.*` sections in RAM, without
+/// initializing them at boot, which is all a channel needs.
+pub const DEFAULT_SECTION: &str = crate::__static_channel_default_section!();
+
+// The default section as a macro, so that static_channel!() can use it in
+// an attribute, where a const can't be used
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __static_channel_default_section {
+    () => {
+        ".uninit.airfrog_rpc"
+    };
+}
+
+/// Statically allocated memory for a channel of `N` bytes, including its
+/// control block.
+///
+/// `N` is checked when the region is created - a region too small to hold a
+/// control block and at least one word of data, or whose size is not a
+/// multiple of 4 bytes, fails to compile.  The region is always 4-byte
+/// aligned.
+///
+/// ```rust,ignore
+/// use airfrog_rpc::channel::{ChannelActor, StaticChannelRegion};
+///
+/// #[unsafe(link_section = ".uninit.airfrog_rpc")]
+/// #[unsafe(no_mangle)]
+/// static CMD_CH: StaticChannelRegion<1024> = StaticChannelRegion::new();
+///
+/// let cmd_ch = CMD_CH.channel(ChannelActor::Consumer)?;
+/// ```
+///
+/// Alternatively use [`crate::static_channel`] to do this in one line.
+///
+/// A region too small for a control block and data doesn't compile:
+///
+/// ```rust,compile_fail
+/// use airfrog_rpc::channel::StaticChannelRegion;
+///
+/// static REGION: StaticChannelRegion<16> = StaticChannelRegion::new();
+/// ```
+///
+/// Nor does one whose size is not a multiple of 4 bytes:
+///
+/// ```rust,compile_fail
+/// use airfrog_rpc::channel::StaticChannelRegion;
+///
+/// static REGION: StaticChannelRegion<66> = StaticChannelRegion::new();
+/// ```
+#[repr(C, align(4))]
+pub struct StaticChannelRegion<const N: usize> {
+    buf: UnsafeCell<MaybeUninit<[u8; N]>>,
+}
+
+// The region is only accessed through channels, using volatile reads and
+// writes, as it is by the Host.
+unsafe impl<const N: usize> Sync for StaticChannelRegion<N> {}
+
+impl<const N: usize> StaticChannelRegion<N> {
+    /// Size of the region in bytes, including the channel's control block
+    pub const SIZE: usize = N;
+
+    // Evaluated by new(), so invalid sizes fail to compile
+    const VALID: () = {
+        assert!(
+            N >= min_channel_size(),
+            "Channel region too small for a control block and data"
+        );
+        assert!(
+            N.is_multiple_of(4),
+            "Channel region size must be a multiple of 4 bytes"
+        );
+        assert!(
+            N <= u32::MAX as usize,
+            "Channel region size must fit in a u32"
+        );
+    };
+
+    /// Create a new region.  Its contents are uninitialized until a channel
+    /// is created from it.
+    // We need a new() rather than a default() as it must be const.
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        let () = Self::VALID;
+        Self {
+            buf: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Address of the region, as used by [`crate::channel::ChannelIo`].
+    ///
+    /// For Targets only, which have a 32-bit address space.  Elsewhere, for
+    /// example on a 64-bit Host, the address may not fit in a `u32`, which
+    /// debug builds check for.
+    pub fn addr(&'static self) -> u32 {
+        let addr = self.buf.get() as usize;
+        debug_assert!(
+            u32::try_from(addr).is_ok(),
+            "Channel region outside the 32-bit address space"
+        );
+        addr as u32
+    }
+
+    /// Size of the region in bytes, including the channel's control block
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// Always false - a region holds at least a control block
+    pub const fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Create the channel, initializing its control block.
+    ///
+    /// Only one channel should be created from a region.  Creating another
+    /// resets the channel, as [`crate::channel::RamChannel::new()`] does.
+    ///
+    /// Arguments:
+    /// - `actor` - Whether the user is a Consumer or Producer
+    pub fn channel(&'static self, actor: ChannelActor) -> Result<OwnedRamChannel> {
+        OwnedRamChannel::new(RamChannelIo::new(), actor, self.addr(), N)
+    }
+}
+
+/// Declare a [`crate::channel::StaticChannelRegion`] and create its channel,
+/// in one line.  Used by a Target.
+///
+/// Arguments:
+/// - name - Name of the region's static, also exported as a symbol holding
+///   the region, so the Host can find its address.  The region's length is
+///   exported as the `u32` symbol `<name>_LEN`.
+/// - size - Size of the region in bytes, including the control block,
+///   checked at compile time
+/// - actor - [`crate::channel::ChannelActor`] of the channel
+/// - section (optional) - Linker section to place the region in.  Defaults to
+///   [`crate::channel::region::DEFAULT_SECTION`].
+///
+/// Evaluates to a `Result<OwnedRamChannel>`.  As the symbols must be unique,
+/// each region must have a different name.
+///
+/// ```rust,ignore
+/// use airfrog_rpc::channel::ChannelActor;
+/// use airfrog_rpc::static_channel;
+///
+/// let cmd_ch = static_channel!(AIRFROG_CMD_CH, 1024, ChannelActor::Consumer)?;
+/// let rsp_ch = static_channel!(AIRFROG_RSP_CH, 1024, ChannelActor::Producer, ".channels")?;
+/// ```
+#[macro_export]
+macro_rules! static_channel {
+    ($name:ident, $size:expr, $actor:expr $(,)?) => {
+        $crate::static_channel!(
+            @section $crate::__static_channel_default_section!(),
+            $name,
+            $size,
+            $actor
+        )
+    };
+    ($name:ident, $size:expr, $actor:expr, $section:literal $(,)?) => {
+        $crate::static_channel!(@section $section, $name, $size, $actor)
+    };
+    (@section $section:expr, $name:ident, $size:expr, $actor:expr) => {{
+        #[unsafe(link_section = $section)]
+        #[unsafe(no_mangle)]
+        static $name: $crate::channel::StaticChannelRegion<{ $size }> =
+            $crate::channel::StaticChannelRegion::new();
+
+        #[unsafe(export_name = concat!(stringify!($name), "_LEN"))]
+        #[used]
+        static LEN: u32 = $size as u32;
+
+        $name.channel($actor)
+    }};
+}
@@ -0,0 +1,228 @@
+//! `futures` [`Stream`] and [`Sink`] adapters for asynchronous Channels -
+//! typically used by a Host.
+//!
+//! - [`ChannelStream`] - a [`Stream`] of the messages received on a Consumer
+//!   channel
+//! - [`ChannelSink`] - a [`Sink`] publishing messages on a Producer channel
+//!
+//! Both wait for the channel to become ready by polling it, waiting in between
+//! according to the channel's [`crate::channel::PollPolicy`], set using
+//! [`AsyncChannel::with_poll_policy()`].  Where the Target can signal the
+//! Host, for example using a doorbell interrupt, implement
+//! [`AsyncDelay::delay_for()`] to wait for the doorbell rather than a fixed
+//! time.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_core::Stream;
+use futures_sink::Sink;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{
+    AsyncChannel, AsyncChannelIo, AsyncDelay, NoDeadline, Poller, consumer_only, producer_only,
+};
+use crate::{Error, Result};
+
+// Operation in progress, which owns the channel until it completes
+type Operation<'a, I, T> = Pin<Box<dyn Future<Output = (AsyncChannel<'a, I>, Result<T>)> + 'a>>;
+
+enum State<'a, I: AsyncChannelIo, T> {
+    Idle(AsyncChannel<'a, I>),
+    Busy(Operation<'a, I, T>),
+    // Only while changing state, or if starting an operation panicked, in
+    // which case the channel has been lost
+    Invalid,
+}
+
+impl<'a, I: AsyncChannelIo, T> State<'a, I, T> {
+    // Polls the operation in progress, if any, returning its result
+    fn poll_operation(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T>>> {
+        let operation = match self {
+            State::Idle(_) => return Poll::Ready(None),
+            State::Busy(operation) => operation,
+            State::Invalid => return Poll::Ready(Some(Err(Error::InvalidOperation))),
+        };
+        match operation.as_mut().poll(cx) {
+            Poll::Ready((channel, result)) => {
+                *self = State::Idle(channel);
+                Poll::Ready(Some(result))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn into_channel(self) -> Option<AsyncChannel<'a, I>> {
+        match self {
+            State::Idle(channel) => Some(channel),
+            _ => None,
+        }
+    }
+}
+
+/// [`Stream`] of the messages received on a Consumer channel.
+///
+/// Never ends.  Errors are returned as items, after which the stream can
+/// continue.
+///
+/// ```rust,ignore
+/// use futures::StreamExt;
+///
+/// let channel = ReaderWriterChannel::from_target(&mut io, ChannelActor::Consumer, RSP_CH).await?;
+/// let mut messages = channel.into_stream::<Delay>()?;
+/// while let Some(message) = messages.next().await {
+///     let message = message?;
+///     // Process message...
+/// }
+/// ```
+pub struct ChannelStream<'a, I: AsyncChannelIo, D: AsyncDelay> {
+    state: State<'a, I, Vec<u8>>,
+    _delay: PhantomData<fn() -> D>,
+}
+
+/// [`Sink`] publishing messages on a Producer channel.
+///
+/// Each message sent waits for the consumer to have consumed the previous
+/// one.
+///
+/// ```rust,ignore
+/// use futures::SinkExt;
+///
+/// let channel = ReaderWriterChannel::from_target(&mut io, ChannelActor::Producer, CMD_CH).await?;
+/// let mut sink = channel.into_sink::<Delay>()?;
+/// sink.send(&[0x01, 0x02]).await?;
+/// ```
+pub struct ChannelSink<'a, I: AsyncChannelIo, D: AsyncDelay> {
+    state: State<'a, I, ()>,
+    _delay: PhantomData<fn() -> D>,
+}
+
+impl<'a, I: AsyncChannelIo> AsyncChannel<'a, I> {
+    /// Convert a Consumer channel into a [`ChannelStream`] of received
+    /// messages.
+    ///
+    /// Returns [`Error::InvalidOperation`] if this is not a Consumer channel.
+    pub fn into_stream<D: AsyncDelay>(self) -> Result<ChannelStream<'a, I, D>> {
+        consumer_only(self.actor())?;
+        Ok(ChannelStream {
+            state: State::Idle(self),
+            _delay: PhantomData,
+        })
+    }
+
+    /// Convert a Producer channel into a [`ChannelSink`] of messages to
+    /// publish.
+    ///
+    /// Returns [`Error::InvalidOperation`] if this is not a Producer channel.
+    pub fn into_sink<D: AsyncDelay>(self) -> Result<ChannelSink<'a, I, D>> {
+        producer_only(self.actor())?;
+        Ok(ChannelSink {
+            state: State::Idle(self),
+            _delay: PhantomData,
+        })
+    }
+}
+
+impl<'a, I: AsyncChannelIo, D: AsyncDelay> ChannelStream<'a, I, D> {
+    /// Get the channel back, unless a message is part way through being
+    /// received, in which case `None` is returned.
+    pub fn into_inner(self) -> Option<AsyncChannel<'a, I>> {
+        self.state.into_channel()
+    }
+}
+
+impl<'a, I: AsyncChannelIo + 'a, D: AsyncDelay + 'a> Stream for ChannelStream<'a, I, D> {
+    type Item = Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let State::Idle(_) = this.state {
+            let State::Idle(channel) = core::mem::replace(&mut this.state, State::Invalid) else {
+                unreachable!()
+            };
+            this.state = State::Busy(Box::pin(receive::<I, D>(channel)));
+        }
+        this.state.poll_operation(cx)
+    }
+}
+
+impl<'a, I: AsyncChannelIo, D: AsyncDelay> ChannelSink<'a, I, D> {
+    /// Get the channel back, unless a message is part way through being
+    /// published, in which case `None` is returned.
+    pub fn into_inner(self) -> Option<AsyncChannel<'a, I>> {
+        self.state.into_channel()
+    }
+}
+
+impl<'a, I: AsyncChannelIo + 'a, D: AsyncDelay + 'a> Sink<&[u8]> for ChannelSink<'a, I, D> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut()
+            .state
+            .poll_operation(cx)
+            .map(|result| result.unwrap_or(Ok(())))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: &[u8]) -> Result<()> {
+        let this = self.get_mut();
+        if !matches!(this.state, State::Idle(_)) {
+            // poll_ready() was not called, or did not complete.  Leave any
+            // message being published alone.
+            return Err(Error::Busy);
+        }
+        let State::Idle(channel) = core::mem::replace(&mut this.state, State::Invalid) else {
+            unreachable!()
+        };
+        this.state = State::Busy(Box::pin(publish::<I, D>(channel, item.to_vec())));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.poll_ready(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.poll_ready(cx)
+    }
+}
+
+// Waits for a message and consumes it
+async fn receive<I: AsyncChannelIo, D: AsyncDelay>(
+    mut channel: AsyncChannel<'_, I>,
+) -> (AsyncChannel<'_, I>, Result<Vec<u8>>) {
+    let mut poller = Poller::new(channel.poll_policy());
+    let result = loop {
+        match channel.data_available().await {
+            Ok(Some(size)) => {
+                let mut buf = vec![0u8; size];
+                break channel.consume_bytes(&mut buf).await.map(|len| {
+                    buf.truncate(len);
+                    buf
+                });
+            }
+            Ok(None) => poller.wait::<D>().await,
+            Err(e) => break Err(e),
+        }
+    };
+    (channel, result)
+}
+
+// Waits for the channel to be free and publishes a message
+async fn publish<I: AsyncChannelIo, D: AsyncDelay>(
+    mut channel: AsyncChannel<'_, I>,
+    data: Vec<u8>,
+) -> (AsyncChannel<'_, I>, Result<()>) {
+    let result = channel
+        .publish_bytes_when_ready::<D>(&data, NoDeadline)
+        .await;
+    (channel, result)
+}
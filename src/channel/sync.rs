@@ -4,12 +4,22 @@
 //
 // MIT License
 
+use core::borrow::BorrowMut;
+use core::marker::PhantomData;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
-use crate::channel::{ChannelActor, ChannelCb, ChannelFlags};
-use crate::channel::{check_base_addr, check_channel_size, consumer_only, producer_only};
+use crate::channel::{ChannelActor, ChannelCb, ChannelFlags, Deadline, WaitHook};
+use crate::channel::{
+    bytes_to_word, check_base_addr, check_channel_size, consumer_only, producer_only, word_to_bytes,
+};
+#[cfg(feature = "codec")]
+use crate::codec::Codec;
+use crate::envelope::Envelope;
+use crate::event::EventHeader;
 use crate::{Error, Result};
+#[cfg(feature = "codec")]
+use serde::{Deserialize, Serialize};
 
 /// Trait for accessing channel in a shared medium (usually RAM).
 ///
@@ -29,14 +39,29 @@ pub trait ChannelIo {
     fn write_bulk(&mut self, addr: u32, data: &[u32]) -> Result<()>;
 }
 
-/// Synchronous unidirectional communication channel
-pub struct Channel<'a, I: ChannelIo> {
-    io: &'a mut I,
+/// Synchronous unidirectional communication channel, accessing the shared
+/// medium through `B`, which either borrows or owns the [`ChannelIo`]
+/// object.
+///
+/// Normally used through one of its aliases:
+/// - [`Channel`] - borrows the [`ChannelIo`] object
+/// - [`OwnedChannel`] - owns the [`ChannelIo`] object, so it can be stored in
+///   structs and tasks without a borrow, for example [`OwnedRamChannel`]
+pub struct GenericChannel<I: ChannelIo, B: BorrowMut<I>> {
+    io: B,
     actor: ChannelActor,
     base_addr: u32,
+    // Only B holds an I, so the channel's auto traits follow B
+    _io: PhantomData<fn() -> I>,
 }
 
-impl<'a, I: ChannelIo> Channel<'a, I> {
+/// Synchronous channel, borrowing its [`ChannelIo`] object
+pub type Channel<'a, I> = GenericChannel<I, &'a mut I>;
+
+/// Synchronous channel, owning its [`ChannelIo`] object
+pub type OwnedChannel<I> = GenericChannel<I, I>;
+
+impl<I: ChannelIo, B: BorrowMut<I>> GenericChannel<I, B> {
     /// Create new channel with given size.  Used by the Target to initialize
     /// the channel.
     ///
@@ -47,7 +72,7 @@ impl<'a, I: ChannelIo> Channel<'a, I> {
     /// - `base_addr` - Base address of the channel on that medium
     /// - `size` - Total size of the channel in bytes, including Control Block
     ///   and data portions.
-    pub fn new(io: &'a mut I, actor: ChannelActor, base_addr: u32, size: usize) -> Result<Self> {
+    pub fn new(io: B, actor: ChannelActor, base_addr: u32, size: usize) -> Result<Self> {
         check_base_addr(base_addr)?;
         check_channel_size(size)?;
 
@@ -55,6 +80,7 @@ impl<'a, I: ChannelIo> Channel<'a, I> {
             io,
             base_addr,
             actor,
+            _io: PhantomData,
         };
 
         // Set channel size to 0 first.  Channel is only valid once size is non-zero.
@@ -82,13 +108,14 @@ impl<'a, I: ChannelIo> Channel<'a, I> {
     ///   medium
     /// - `actor` - Whether the user is a Consumer or Producer
     /// - `base_addr` - Base address of the channel on that medium
-    pub fn from_target(io: &'a mut I, actor: ChannelActor, base_addr: u32) -> Result<Self> {
+    pub fn from_target(io: B, actor: ChannelActor, base_addr: u32) -> Result<Self> {
         check_base_addr(base_addr)?;
 
         let mut channel = Self {
             io,
             actor,
             base_addr,
+            _io: PhantomData,
         };
 
         // Validate existing control block
@@ -137,9 +164,144 @@ impl<'a, I: ChannelIo> Channel<'a, I> {
     /// This is less efficient than [`Self::publish_data()`] where the data is
     /// guaranteed word aligned.
     pub fn publish_bytes(&mut self, data: &[u8]) -> Result<()> {
+        self.publish_bytes_with_flags(data, ChannelFlags::Ok)
+    }
+
+    /// Producer: Publish an empty response indicating that processing of the
+    /// last command was abandoned, following a cancel request from the
+    /// consumer of the command channel.  See
+    /// [`Self::cancel_requested()`].
+    pub fn publish_cancelled(&mut self) -> Result<()> {
+        self.publish_bytes_with_flags(&[], ChannelFlags::Cancelled)
+    }
+
+    /// Producer: Ask the consumer to abandon processing of the last published
+    /// data.  Does not publish any new data.
+    ///
+    /// The consumer is expected to acknowledge using
+    /// [`Self::publish_cancelled()`] on its response channel, although it
+    /// may have already completed processing, in which case the normal
+    /// response is sent instead.
+    pub fn cancel(&mut self) -> Result<()> {
         producer_only(self.actor)?;
+        self.write_flags(ChannelFlags::Cancel)
+    }
 
-        if data.len() > self.data_capacity()? {
+    /// Consumer: Check whether the producer has asked for the last published
+    /// data to be abandoned.  Typically polled by a Target while handling a
+    /// long-running command.
+    ///
+    /// Remains set until the producer next publishes.
+    pub fn cancel_requested(&mut self) -> Result<bool> {
+        consumer_only(self.actor)?;
+        Ok(self.read_flags()? == ChannelFlags::Cancel)
+    }
+
+    /// Get the flags associated with the currently published data.
+    pub fn flags(&mut self) -> Result<ChannelFlags> {
+        self.read_flags()
+    }
+
+    /// Get the producer sequence number - the number of times data has been
+    /// published on this channel (wrapping).
+    pub fn producer_seq(&mut self) -> Result<u32> {
+        self.read_producer_seq()
+    }
+
+    /// Get the consumer sequence number - the producer sequence number of the
+    /// last data consumed from this channel.
+    pub fn consumer_seq(&mut self) -> Result<u32> {
+        self.read_consumer_seq()
+    }
+
+    /// Consumer: Atomically consume a message wrapped in an [`Envelope`].
+    ///
+    /// The envelope header is returned, and the payload written to `buf`.
+    ///
+    /// Returns the envelope header and the number of payload bytes consumed.
+    pub fn consume_envelope(&mut self, buf: &mut [u8]) -> Result<(Envelope, usize)> {
+        consumer_only(self.actor)?;
+
+        self.check_busy()?;
+
+        let data_size = self.read_data_size()?;
+        if data_size > self.data_capacity()? {
+            return Err(Error::PayloadTooLarge);
+        }
+        if data_size < Envelope::SIZE {
+            return Err(Error::Malformed);
+        }
+
+        let data_addr = self.data_start_addr();
+        let mut header = [0u8; Envelope::SIZE];
+        self.read_bytes(data_addr, &mut header)?;
+        let envelope = Envelope::decode(&header)?;
+
+        let payload_size = data_size - Envelope::SIZE;
+        if envelope.length as usize != payload_size {
+            return Err(Error::Malformed);
+        }
+        if payload_size > buf.len() {
+            return Err(Error::BufferTooSmall);
+        }
+        self.read_bytes(data_addr + Envelope::SIZE as u32, &mut buf[..payload_size])?;
+
+        // Atomically consume by updating consumer_seq last
+        self.set_consumer_seq_to_producer()?;
+
+        Ok((envelope, payload_size))
+    }
+
+    /// Consumer: Discard the available message, returning its [`Envelope`]
+    /// header.  Used, for example, when [`Self::consume_envelope()`] returns
+    /// [`Error::BufferTooSmall`], so the consumer can still answer the
+    /// request.
+    ///
+    /// Returns [`Error::Malformed`] if the message does not start with a
+    /// valid envelope.  The message is discarded in either case.
+    pub fn discard_envelope(&mut self) -> Result<Envelope> {
+        consumer_only(self.actor)?;
+
+        self.check_busy()?;
+
+        let data_size = self.read_data_size()?;
+        let mut header = [0u8; Envelope::SIZE];
+        let envelope = if data_size >= Envelope::SIZE && data_size <= self.data_capacity()? {
+            let data_addr = self.data_start_addr();
+            self.read_bytes(data_addr, &mut header)?;
+            Envelope::decode(&header)
+                .ok()
+                .filter(|envelope| envelope.length as usize == data_size - Envelope::SIZE)
+        } else {
+            None
+        };
+
+        // Atomically consume by updating consumer_seq
+        self.set_consumer_seq_to_producer()?;
+
+        envelope.ok_or(Error::Malformed)
+    }
+
+    /// Producer: Atomically publish a response to an enveloped request,
+    /// echoing the request ID and message type from `request`.
+    ///
+    /// Arguments:
+    /// - `request` - Envelope of the request being responded to, as returned
+    ///   by [`Self::consume_envelope()`]
+    /// - `flags` - Envelope flags, for example [`Envelope::FLAG_ERROR`].
+    ///   [`Envelope::FLAG_RESPONSE`] is always set.
+    /// - `payload` - Response payload
+    pub fn publish_response(
+        &mut self,
+        request: &Envelope,
+        flags: u8,
+        payload: &[u8],
+    ) -> Result<()> {
+        producer_only(self.actor)?;
+
+        let envelope = request.response(flags, payload.len())?;
+        let data_size = Envelope::SIZE + payload.len();
+        if data_size > self.data_capacity()? {
             return Err(Error::PayloadTooLarge);
         }
 
@@ -147,33 +309,44 @@ impl<'a, I: ChannelIo> Channel<'a, I> {
         self.check_idle()?;
 
         let data_addr = self.data_start_addr();
+        self.write_bytes(data_addr, &envelope.encode())?;
+        self.write_bytes(data_addr + Envelope::SIZE as u32, payload)?;
 
-        // Write aligned portion with individual writes (convert bytes to words)
-        let word_count = data.len() / 4;
-        for word_idx in 0..word_count {
-            let byte_offset = word_idx * 4;
-            let word = u32::from_le_bytes([
-                data[byte_offset],
-                data[byte_offset + 1],
-                data[byte_offset + 2],
-                data[byte_offset + 3],
-            ]);
-            self.write_u32(data_addr + (word_idx as u32 * 4), word)?;
-        }
+        // Write metadata before publishing
+        self.write_data_size(data_size)?;
+        self.write_flags(ChannelFlags::Ok)?;
 
-        // Handle remaining 1-3 bytes
-        let remaining = data.len() % 4;
-        if remaining > 0 {
-            let mut final_word = 0u32;
-            let base_offset = word_count * 4;
-            for i in 0..remaining {
-                final_word |= (data[base_offset + i] as u32) << (i * 8);
-            }
-            self.write_u32(data_addr + (base_offset as u32), final_word)?;
+        // Atomically publish by incrementing producer_seq last
+        self.inc_producer_seq()?;
+
+        Ok(())
+    }
+
+    /// Producer: Atomically publish an event, with its [`EventHeader`], on an
+    /// event channel.
+    ///
+    /// Most Targets use [`crate::event::EventPublisher`], which numbers
+    /// events and counts those dropped, rather than calling this directly.
+    ///
+    /// Returns [`Error::Busy`] if the Host has not consumed the previous
+    /// event.
+    pub fn publish_event(&mut self, header: &EventHeader, payload: &[u8]) -> Result<()> {
+        producer_only(self.actor)?;
+
+        let data_size = EventHeader::SIZE + payload.len();
+        if data_size > self.data_capacity()? {
+            return Err(Error::PayloadTooLarge);
         }
 
+        // Check availability
+        self.check_idle()?;
+
+        let data_addr = self.data_start_addr();
+        self.write_bytes(data_addr, &header.encode())?;
+        self.write_bytes(data_addr + EventHeader::SIZE as u32, payload)?;
+
         // Write metadata before publishing
-        self.write_data_size(data.len())?;
+        self.write_data_size(data_size)?;
         self.write_flags(ChannelFlags::Ok)?;
 
         // Atomically publish by incrementing producer_seq last
@@ -187,6 +360,66 @@ impl<'a, I: ChannelIo> Channel<'a, I> {
         self.idle()
     }
 
+    /// Producer: Wait until the channel is available, and then publish
+    /// word-aligned data, as [`Self::publish_data()`].
+    ///
+    /// Arguments:
+    /// - `data` - Data to publish
+    /// - `wait` - Called each time the channel is found to be busy, before
+    ///   checking again
+    /// - `deadline` - When to stop waiting, for example
+    ///   [`crate::channel::NoDeadline`]
+    ///
+    /// Returns [`Error::Timeout`] if the deadline passed before the channel
+    /// became available.
+    pub fn publish_data_when_ready(
+        &mut self,
+        data: &[u32],
+        wait: impl WaitHook,
+        deadline: impl Deadline,
+    ) -> Result<()> {
+        self.wait_until_ready(wait, deadline)?;
+        self.publish_data(data)
+    }
+
+    /// Producer: Wait until the channel is available, and then publish byte
+    /// data, as [`Self::publish_bytes()`].
+    ///
+    /// Arguments:
+    /// - `data` - Data to publish
+    /// - `wait` - Called each time the channel is found to be busy, before
+    ///   checking again
+    /// - `deadline` - When to stop waiting, for example
+    ///   [`crate::channel::NoDeadline`]
+    ///
+    /// Returns [`Error::Timeout`] if the deadline passed before the channel
+    /// became available.
+    pub fn publish_bytes_when_ready(
+        &mut self,
+        data: &[u8],
+        wait: impl WaitHook,
+        deadline: impl Deadline,
+    ) -> Result<()> {
+        self.wait_until_ready(wait, deadline)?;
+        self.publish_bytes(data)
+    }
+
+    // Waits until the channel can be published to
+    fn wait_until_ready(
+        &mut self,
+        mut wait: impl WaitHook,
+        mut deadline: impl Deadline,
+    ) -> Result<()> {
+        producer_only(self.actor)?;
+        while !self.can_publish()? {
+            if deadline.expired() {
+                return Err(Error::Timeout);
+            }
+            wait.wait();
+        }
+        Ok(())
+    }
+
     /// Consumer: Atomically consume data as bytes.
     ///
     /// Less efficient than [`Self::consume_data()`], but handles numbers of
@@ -208,24 +441,7 @@ impl<'a, I: ChannelIo> Channel<'a, I> {
         }
 
         let data_addr = self.data_start_addr();
-
-        // Read aligned portion with individual u32 reads (convert to bytes)
-        let word_count = data_size / 4;
-        for word_idx in 0..word_count {
-            let word = self.read_u32(data_addr + (word_idx as u32 * 4))?;
-            let bytes = word.to_le_bytes();
-            let base_offset = word_idx * 4;
-            buf[base_offset..base_offset + 4].copy_from_slice(&bytes);
-        }
-
-        // Handle remaining 1-3 bytes
-        let remaining = data_size % 4;
-        if remaining > 0 {
-            let final_word = self.read_u32(data_addr + (word_count as u32 * 4))?;
-            let bytes = final_word.to_le_bytes();
-            let base_offset = word_count * 4;
-            buf[base_offset..base_offset + remaining].copy_from_slice(&bytes[..remaining]);
-        }
+        self.read_bytes(data_addr, &mut buf[..data_size])?;
 
         // Atomically consume by updating consumer_seq last
         self.set_consumer_seq_to_producer()?;
@@ -265,6 +481,17 @@ impl<'a, I: ChannelIo> Channel<'a, I> {
         Ok(word_size)
     }
 
+    /// Consumer: Discard the available data without reading it.  Used, for
+    /// example, when the data is too large for the consumer's buffer.
+    pub fn discard(&mut self) -> Result<()> {
+        consumer_only(self.actor)?;
+
+        self.check_busy()?;
+
+        // Atomically consume by updating consumer_seq
+        self.set_consumer_seq_to_producer()
+    }
+
     /// Consumer: Check available data size in bytes.  Use to both check if
     /// there is data available to be read, and also how much.
     pub fn data_available(&mut self) -> Result<Option<usize>> {
@@ -276,19 +503,37 @@ impl<'a, I: ChannelIo> Channel<'a, I> {
         }
     }
 
+    /// Get a [`Channel`] borrowing this channel's [`ChannelIo`] object, for
+    /// use with functions taking a [`Channel`].
+    pub fn as_channel(&mut self) -> Channel<'_, I> {
+        GenericChannel {
+            io: self.io.borrow_mut(),
+            actor: self.actor,
+            base_addr: self.base_addr,
+            _io: PhantomData,
+        }
+    }
+
+    /// Consume the channel, returning its [`ChannelIo`] object, or borrow of
+    /// it
+    pub fn into_io(self) -> B {
+        self.io
+    }
+
     /// Get data capacity for this channel
     pub fn data_capacity(&mut self) -> Result<usize> {
         let channel_size =
             self.io
+                .borrow_mut()
                 .read_u32(self.base_addr + ChannelCb::channel_size_offset())? as usize;
         Ok(channel_size - (ChannelCb::data_offset() as usize))
     }
 }
 
 // Internal functions
-impl<I: ChannelIo> Channel<'_, I> {
+impl<I: ChannelIo, B: BorrowMut<I>> GenericChannel<I, B> {
     fn write_channel_size(&mut self, size: usize) -> Result<()> {
-        self.io.write_u32(
+        self.io.borrow_mut().write_u32(
             self.base_addr + ChannelCb::channel_size_offset(),
             size as u32,
         )
@@ -296,45 +541,52 @@ impl<I: ChannelIo> Channel<'_, I> {
 
     fn write_producer_seq(&mut self, seq: u32) -> Result<()> {
         self.io
+            .borrow_mut()
             .write_u32(self.base_addr + ChannelCb::producer_seq_offset(), seq)
     }
 
     fn write_consumer_seq(&mut self, seq: u32) -> Result<()> {
         self.io
+            .borrow_mut()
             .write_u32(self.base_addr + ChannelCb::consumer_seq_offset(), seq)
     }
 
     fn write_flags(&mut self, flags: ChannelFlags) -> Result<()> {
         self.io
+            .borrow_mut()
             .write_u32(self.base_addr + ChannelCb::flags_offset(), flags as u32)
     }
 
     fn write_data_size(&mut self, size: usize) -> Result<()> {
         self.io
+            .borrow_mut()
             .write_u32(self.base_addr + ChannelCb::data_size_offset(), size as u32)
     }
 
     fn read_channel_size(&mut self) -> Result<usize> {
         let channel_size =
             self.io
+                .borrow_mut()
                 .read_u32(self.base_addr + ChannelCb::channel_size_offset())? as usize;
         Ok(channel_size)
     }
 
     fn read_producer_seq(&mut self) -> Result<u32> {
         self.io
+            .borrow_mut()
             .read_u32(self.base_addr + ChannelCb::producer_seq_offset())
     }
 
     fn read_consumer_seq(&mut self) -> Result<u32> {
         self.io
+            .borrow_mut()
             .read_u32(self.base_addr + ChannelCb::consumer_seq_offset())
     }
 
-    #[allow(dead_code)]
     fn read_flags(&mut self) -> Result<ChannelFlags> {
         let flags = self
             .io
+            .borrow_mut()
             .read_u32(self.base_addr + ChannelCb::flags_offset())?;
         Ok(ChannelFlags::from(flags))
     }
@@ -342,28 +594,69 @@ impl<I: ChannelIo> Channel<'_, I> {
     fn read_data_size(&mut self) -> Result<usize> {
         let data_size =
             self.io
+                .borrow_mut()
                 .read_u32(self.base_addr + ChannelCb::data_size_offset())? as usize;
         Ok(data_size)
     }
 
+    fn publish_bytes_with_flags(&mut self, data: &[u8], flags: ChannelFlags) -> Result<()> {
+        producer_only(self.actor)?;
+
+        if data.len() > self.data_capacity()? {
+            return Err(Error::PayloadTooLarge);
+        }
+
+        // Check availability
+        self.check_idle()?;
+
+        let data_addr = self.data_start_addr();
+        self.write_bytes(data_addr, data)?;
+
+        // Write metadata before publishing
+        self.write_data_size(data.len())?;
+        self.write_flags(flags)?;
+
+        // Atomically publish by incrementing producer_seq last
+        self.inc_producer_seq()?;
+
+        Ok(())
+    }
+
+    // Writes bytes to a word-aligned address, using word writes
+    fn write_bytes(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        for (word_idx, chunk) in data.chunks(4).enumerate() {
+            self.write_u32(addr + (word_idx as u32 * 4), bytes_to_word(chunk))?;
+        }
+        Ok(())
+    }
+
+    // Reads bytes from a word-aligned address, using word reads
+    fn read_bytes(&mut self, addr: u32, buf: &mut [u8]) -> Result<()> {
+        for (word_idx, chunk) in buf.chunks_mut(4).enumerate() {
+            let word = self.read_u32(addr + (word_idx as u32 * 4))?;
+            word_to_bytes(word, chunk);
+        }
+        Ok(())
+    }
+
     fn data_start_addr(&mut self) -> u32 {
         self.base_addr + ChannelCb::data_offset()
     }
 
     fn write_bulk(&mut self, addr: u32, data: &[u32]) -> Result<()> {
-        self.io.write_bulk(addr, data)
+        self.io.borrow_mut().write_bulk(addr, data)
     }
 
     fn read_bulk(&mut self, addr: u32, buf: &mut [u32]) -> Result<()> {
-        self.io.read_bulk(addr, buf)
+        self.io.borrow_mut().read_bulk(addr, buf)
     }
 
     fn write_u32(&mut self, addr: u32, value: u32) -> Result<()> {
-        self.io.write_u32(addr, value)
+        self.io.borrow_mut().write_u32(addr, value)
     }
 
     fn read_u32(&mut self, addr: u32) -> Result<u32> {
-        self.io.read_u32(addr)
+        self.io.borrow_mut().read_u32(addr)
     }
 
     fn idle(&mut self) -> Result<bool> {
@@ -399,9 +692,56 @@ impl<I: ChannelIo> Channel<'_, I> {
     }
 }
 
+// Typed functions
+#[cfg(feature = "codec")]
+impl<I: ChannelIo, B: BorrowMut<I>> GenericChannel<I, B> {
+    /// Producer: Encode a value using codec `C`, and atomically publish it.
+    ///
+    /// The value is encoded into `buf`, limited to the channel's data
+    /// capacity, so `buf` needs to be no larger than that.
+    ///
+    /// Returns [`Error::BufferTooSmall`] if the encoded value doesn't fit, or
+    /// [`Error::Codec`] if the value could not be encoded.
+    pub fn publish_typed<C: Codec, T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+        buf: &mut [u8],
+    ) -> Result<()> {
+        let limit = buf.len().min(self.data_capacity()?);
+        let len = C::encode(value, &mut buf[..limit])?;
+        self.publish_bytes(&buf[..len])
+    }
+
+    /// Consumer: Atomically consume data, and decode it as a value using
+    /// codec `C`.
+    ///
+    /// The data is consumed into `buf`.  Whether the returned value may borrow
+    /// from `buf` depends on the codec - see [`Codec::decode`].
+    ///
+    /// Returns [`Error::Codec`] if the data could not be decoded, in which
+    /// case the data has still been consumed.
+    pub fn consume_typed<'b, C: Codec, T: Deserialize<'b>>(
+        &mut self,
+        buf: &'b mut [u8],
+    ) -> Result<T> {
+        let len = self.consume_bytes(buf)?;
+        C::decode(&buf[..len])
+    }
+}
+
 /// RAM channel type.  Typically used by a Target.
 pub type RamChannel = Channel<'static, RamChannelIo>;
 
+/// RAM channel type, owning its [`RamChannelIo`].  Typically used by a Target.
+///
+/// As [`RamChannelIo`] has no state, each channel can own a copy, and be
+/// stored in structs and tasks without needing a `static mut`:
+///
+/// ```rust,ignore
+/// let cmd_ch = OwnedRamChannel::new(RamChannelIo::new(), ChannelActor::Consumer, CMD_CH, 1024)?;
+/// ```
+pub type OwnedRamChannel = OwnedChannel<RamChannelIo>;
+
 /// Channel I/O implementation using direct RAM access
 #[derive(Clone, Copy)]
 pub struct RamChannelIo;
@@ -409,10 +749,12 @@ pub struct RamChannelIo;
 impl RamChannelIo {
     /// Create a new RamChannelIo instances.
     ///
-    /// ```rust
+    /// ```rust,ignore
     /// static mut RAM_CHANNEL_IO: RamChannelIo = RamChannelIo::new();
     /// // Now use it in RamChannel::new()
     /// ```
+    ///
+    /// Alternatively pass it by value to [`OwnedRamChannel::new()`].
     // We need a new() rather than a default() as it must be const.
     #[allow(clippy::new_without_default)]
     pub const fn new() -> Self {
@@ -0,0 +1,91 @@
+//! Waiting for a Channel to become ready.
+//!
+//! Used by the `*_when_ready` methods on [`crate::channel::Channel`] and
+//! [`crate::channel::AsyncChannel`], which wait for the other side to catch
+//! up instead of returning [`crate::Error::Busy`].
+//!
+//! - [`Deadline`] - when to give up waiting, returning
+//!   [`crate::Error::Timeout`]
+//! - [`WaitHook`] - what a synchronous Channel does while waiting, for
+//!   example sleeping until an interrupt.  Asynchronous Channels use
+//!   [`crate::channel::AsyncDelay`] instead.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+/// Deadline for an operation which waits.
+///
+/// Implemented for closures returning whether the deadline has passed, for
+/// example using a timer:
+///
+/// ```rust,ignore
+/// let end = Instant::now() + Duration::from_millis(100);
+/// channel
+///     .publish_bytes_when_ready::<Delay>(&data, || Instant::now() >= end)
+///     .await?;
+/// ```
+pub trait Deadline {
+    /// Whether the deadline has passed.  Called each time the operation
+    /// finds it must wait.
+    fn expired(&mut self) -> bool;
+}
+
+impl<F: FnMut() -> bool> Deadline for F {
+    fn expired(&mut self) -> bool {
+        self()
+    }
+}
+
+/// No deadline - wait indefinitely
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoDeadline;
+
+impl Deadline for NoDeadline {
+    fn expired(&mut self) -> bool {
+        false
+    }
+}
+
+/// Deadline after waiting a number of times, for use without a timer.  With
+/// `MaxWaits(0)` the operation does not wait at all.
+#[derive(Debug, Clone, Copy)]
+pub struct MaxWaits(pub u32);
+
+impl Deadline for MaxWaits {
+    fn expired(&mut self) -> bool {
+        if self.0 == 0 {
+            true
+        } else {
+            self.0 -= 1;
+            false
+        }
+    }
+}
+
+/// Hook called by a synchronous Channel while it waits, for example to
+/// delay briefly, feed a watchdog, or yield to an RTOS.
+///
+/// The hook must return within a bounded time.  The Host accesses the
+/// Target's memory over the debug interface without generating events or
+/// interrupts, so sleeping with `wfe` or `wfi` may never wake unless
+/// something else, such as a timer, wakes the core.
+///
+/// Implemented for closures:
+///
+/// ```rust,ignore
+/// channel.publish_bytes_when_ready(&data, || delay.delay_us(100), NoDeadline)?;
+/// ```
+pub trait WaitHook {
+    /// Wait before checking the Channel again
+    fn wait(&mut self);
+}
+
+impl<F: FnMut()> WaitHook for F {
+    fn wait(&mut self) {
+        self()
+    }
+}
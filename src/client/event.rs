@@ -0,0 +1,328 @@
+//! Event subscriber - typically used by a Host to receive unsolicited events
+//! from a Target.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#[cfg(feature = "stream")]
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "stream")]
+use core::pin::Pin;
+#[cfg(feature = "stream")]
+use core::task::{Context, Poll};
+#[cfg(feature = "stream")]
+use futures_core::Stream;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::Result;
+use crate::channel::{ChannelActor, ReaderWriterChannelIo};
+use crate::client::{AsyncDelay, ChannelConfig, PollPolicy, Poller};
+use crate::event::EventHeader;
+use crate::io::{Reader, Writer};
+
+/// Event received from the Target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// Sequence number
+    pub seq: u32,
+
+    /// Application-defined event type
+    pub event_type: u16,
+
+    /// Number of events the Target dropped immediately before this one.
+    /// The Target can only report drops with the event it next publishes, so
+    /// this may be some time after the events were dropped.
+    pub dropped: u16,
+
+    /// Event payload
+    pub payload: Vec<u8>,
+}
+
+/// Subscriber to the events a Target publishes on its event channel, using
+/// [`crate::event::EventPublisher`].
+///
+/// Call [`Self::next()`] repeatedly to receive events, or with the `stream`
+/// feature, use `into_stream()` to get a `futures` `Stream`.  The
+/// subscriber tracks sequence numbers, counting events the Target reports it
+/// dropped in [`Self::overflow()`], and any other missing events in
+/// [`Self::gaps()`].
+///
+/// The Target reports dropped events with the next event it publishes, so
+/// [`Self::overflow()`] lags behind: events dropped after the last event
+/// received are not counted until another event arrives.
+///
+/// ```rust,ignore
+/// use airfrog_rpc::client::EventSubscriber;
+///
+/// let mut events = EventSubscriber::<_, _, Delay>::new(&mut reader, &mut writer, 0x2000_2000)
+///     .with_filter(&[BUTTON_PRESSED, FAULT]);
+/// loop {
+///     let event = events.next().await?;
+///     // Process event...
+/// }
+/// ```
+pub struct EventSubscriber<'a, R: Reader, W: Writer, D: AsyncDelay> {
+    io: ReaderWriterChannelIo<'a, R, W>,
+    ch_config: ChannelConfig,
+    filter: Option<Vec<u16>>,
+    last_seq: Option<u32>,
+    overflow: u64,
+    gaps: u32,
+    poll_policy: PollPolicy,
+    _delay: core::marker::PhantomData<fn() -> D>,
+}
+
+impl<'a, R: Reader, W: Writer, D: AsyncDelay> EventSubscriber<'a, R, W, D> {
+    /// Create a new EventSubscriber
+    ///
+    /// Arguments:
+    /// - `reader`: Reader object to read from target
+    /// - `writer`: Writer object to write to target
+    /// - `event_ch_ptr`: Pointer to the event channel in target memory.  The
+    ///   channel size is read from the target.
+    pub fn new(reader: &'a mut R, writer: &'a mut W, event_ch_ptr: u32) -> Self {
+        Self {
+            io: ReaderWriterChannelIo::new(reader, writer),
+            ch_config: ChannelConfig::FromTarget { ptr: event_ch_ptr },
+            filter: None,
+            last_seq: None,
+            overflow: 0,
+            gaps: 0,
+            poll_policy: PollPolicy::DEFAULT,
+            _delay: core::marker::PhantomData,
+        }
+    }
+
+    /// Set the policy used when waiting for the Target.  Defaults to
+    /// [`PollPolicy::DEFAULT`].
+    pub fn with_poll_policy(mut self, policy: PollPolicy) -> Self {
+        self.poll_policy = policy;
+        self
+    }
+
+    /// Only return events of the given types.  Other events are consumed
+    /// from the channel and discarded, but still count towards
+    /// [`Self::overflow()`] and [`Self::gaps()`].
+    pub fn with_filter(mut self, event_types: &[u16]) -> Self {
+        self.set_filter(Some(event_types));
+        self
+    }
+
+    /// Change the event types returned, or with `None` return all events
+    pub fn set_filter(&mut self, event_types: Option<&[u16]>) {
+        self.filter = event_types.map(|types| types.to_vec());
+    }
+
+    /// Wait for the next event which passes the filter.
+    ///
+    /// Returns:
+    /// - `Ok(event)`: Event received
+    /// - `Err(Error::Malformed)`: Event had an invalid header.  It is
+    ///   discarded, and the stream can continue.
+    /// - `Err(error)`: Other error occurred
+    pub async fn next(&mut self) -> Result<Event> {
+        let mut poller = Poller::new(self.poll_policy);
+        loop {
+            if let Some(event) = self.try_next().await? {
+                return Ok(event);
+            }
+            poller.wait::<D>().await;
+        }
+    }
+
+    /// Get the next event which passes the filter, if one is available,
+    /// without waiting.
+    pub async fn try_next(&mut self) -> Result<Option<Event>> {
+        while let Some(event) = self.receive().await? {
+            if self.accepts(event.event_type) {
+                return Ok(Some(event));
+            }
+            trace!("Filtered event {} type {}", event.seq, event.event_type);
+        }
+        Ok(None)
+    }
+
+    /// Total number of events the Target reported dropping because the
+    /// previous event had not been consumed.
+    ///
+    /// Drops are reported with the next event the Target publishes, so this
+    /// does not include events dropped since the last event was received.
+    pub fn overflow(&self) -> u64 {
+        self.overflow
+    }
+
+    /// Number of sequence number gaps not explained by events the Target
+    /// reported dropping.  For example if the Target reset, or another Host
+    /// consumed events.
+    pub fn gaps(&self) -> u32 {
+        self.gaps
+    }
+
+    /// Sequence number of the last event received, whether or not it passed
+    /// the filter
+    pub fn last_seq(&self) -> Option<u32> {
+        self.last_seq
+    }
+
+    fn accepts(&self, event_type: u16) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|types| types.contains(&event_type))
+    }
+
+    // Consumes an event from the channel, if one is available, and checks
+    // its sequence number
+    async fn receive(&mut self) -> Result<Option<Event>> {
+        let mut event_ch = self
+            .ch_config
+            .channel(&mut self.io, ChannelActor::Consumer)
+            .await?;
+        let Some(size) = event_ch.data_available().await? else {
+            return Ok(None);
+        };
+        let mut buf = vec![0u8; size];
+        let len = event_ch.consume_bytes(&mut buf).await?;
+        buf.truncate(len);
+
+        let header = match EventHeader::decode(&buf) {
+            Ok(header) => header,
+            Err(e) => {
+                warn!("Discarding malformed event ({len} bytes)");
+                return Err(e);
+            }
+        };
+        self.check_seq(&header);
+
+        buf.drain(..EventHeader::SIZE);
+        Ok(Some(Event {
+            seq: header.seq,
+            event_type: header.event_type,
+            dropped: header.dropped,
+            payload: buf,
+        }))
+    }
+
+    fn check_seq(&mut self, header: &EventHeader) {
+        self.overflow += header.dropped as u64;
+        if header.dropped > 0 {
+            debug!(
+                "Target dropped {} events before {}",
+                header.dropped, header.seq
+            );
+        }
+
+        if let Some(last_seq) = self.last_seq {
+            let expected = last_seq.wrapping_add(1).wrapping_add(header.dropped as u32);
+            // A saturated drop count may not account for the whole gap
+            if header.seq != expected && header.dropped < u16::MAX {
+                warn!(
+                    "Event sequence gap: expected {expected}, got {}",
+                    header.seq
+                );
+                self.gaps = self.gaps.saturating_add(1);
+            }
+        }
+        self.last_seq = Some(header.seq);
+    }
+}
+
+/// `futures` [`Stream`] of the events received by an [`EventSubscriber`],
+/// created using [`EventSubscriber::into_stream()`].
+///
+/// Never ends.  Errors are returned as items, after which the stream can
+/// continue.
+///
+/// ```rust,ignore
+/// use futures::StreamExt;
+///
+/// let mut events = EventSubscriber::<_, _, Delay>::new(&mut reader, &mut writer, 0x2000_2000)
+///     .with_filter(&[BUTTON_PRESSED, FAULT])
+///     .into_stream();
+/// while let Some(event) = events.next().await {
+///     let event = event?;
+///     // Process event...
+/// }
+/// ```
+#[cfg(feature = "stream")]
+pub struct EventStream<'a, R: Reader, W: Writer, D: AsyncDelay> {
+    state: StreamState<'a, R, W, D>,
+}
+
+// Receive in progress, which owns the subscriber until it completes
+#[cfg(feature = "stream")]
+type Receive<'a, R, W, D> =
+    Pin<Box<dyn Future<Output = (EventSubscriber<'a, R, W, D>, Result<Event>)> + 'a>>;
+
+#[cfg(feature = "stream")]
+enum StreamState<'a, R: Reader, W: Writer, D: AsyncDelay> {
+    Idle(EventSubscriber<'a, R, W, D>),
+    Busy(Receive<'a, R, W, D>),
+    // Only while changing state
+    Invalid,
+}
+
+#[cfg(feature = "stream")]
+impl<'a, R: Reader, W: Writer, D: AsyncDelay> EventSubscriber<'a, R, W, D> {
+    /// Convert into an [`EventStream`] of the events which pass the filter
+    pub fn into_stream(self) -> EventStream<'a, R, W, D> {
+        EventStream {
+            state: StreamState::Idle(self),
+        }
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<'a, R: Reader, W: Writer, D: AsyncDelay> EventStream<'a, R, W, D> {
+    /// Get the subscriber, if no event is being received, for example to
+    /// check [`EventSubscriber::overflow()`].
+    pub fn subscriber(&self) -> Option<&EventSubscriber<'a, R, W, D>> {
+        match &self.state {
+            StreamState::Idle(subscriber) => Some(subscriber),
+            _ => None,
+        }
+    }
+
+    /// Get the subscriber back, unless an event is part way through being
+    /// received, in which case `None` is returned.
+    pub fn into_inner(self) -> Option<EventSubscriber<'a, R, W, D>> {
+        match self.state {
+            StreamState::Idle(subscriber) => Some(subscriber),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<'a, R: Reader + 'a, W: Writer + 'a, D: AsyncDelay + 'a> Stream for EventStream<'a, R, W, D> {
+    type Item = Result<Event>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let StreamState::Idle(_) = this.state {
+            let StreamState::Idle(mut subscriber) =
+                core::mem::replace(&mut this.state, StreamState::Invalid)
+            else {
+                unreachable!()
+            };
+            this.state = StreamState::Busy(Box::pin(async move {
+                let result = subscriber.next().await;
+                (subscriber, result)
+            }));
+        }
+
+        let StreamState::Busy(receive) = &mut this.state else {
+            unreachable!()
+        };
+        match receive.as_mut().poll(cx) {
+            Poll::Ready((subscriber, result)) => {
+                this.state = StreamState::Idle(subscriber);
+                Poll::Ready(Some(result))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
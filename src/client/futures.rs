@@ -9,31 +9,19 @@ use alloc::vec::Vec;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
-use crate::channel::{ChannelActor, ReaderWriterChannel, ReaderWriterChannelIo};
-use crate::client::{ChannelConfig, RpcClientConfig};
+use crate::channel::{
+    ChannelActor, ChannelFlags, Deadline, MaxWaits, NoDeadline, ReaderWriterChannel,
+    ReaderWriterChannelIo,
+};
+use crate::client::{AsyncDelay, ChannelConfig, PollPolicy, Poller, RpcClientConfig};
+use crate::envelope::Envelope;
 use crate::io::{Reader, Writer};
-/// Yield delay for async polling loops.
-///
-/// Application must provide an implementation of this trait in order for the
-/// async client to be able to yield, waiting for a response from the other
-/// side of the channel.
-///
-/// This trait keeps `airfrog-rpc` free of any specific async runtime.
-///
-/// Example:
-///
-/// ```rust
-/// use embassy_time::{Duration, Timer};
-/// struct Delay;
-/// impl AsyncDelay for Delay {
-///     async fn delay() {
-///         Timer::after(Duration::from_millis(50)).await;
-///     }
-/// }
-/// ```
-pub trait AsyncDelay {
-    fn delay() -> impl Future<Output = ()>;
-}
+use crate::server::Status;
+
+/// Default number of times [`AsyncRpcClient`] polls for the Target to
+/// acknowledge a cancellation before giving up - 5 seconds with the default
+/// [`PollPolicy`]
+pub const DEFAULT_CANCEL_POLLS: u32 = 100;
 
 /// Async RPC Client for dual-channel command/response communication.
 ///
@@ -41,7 +29,7 @@ pub trait AsyncDelay {
 ///
 /// Example usage:
 ///
-/// ```rust
+/// ```rust,ignore
 /// use airfrog_rpc::client::{AsyncDelay, AsyncRpcClient, RpcClientConfig};
 /// use airfrog_rpc::io::{Reader, Writer};
 ///
@@ -60,9 +48,29 @@ pub struct AsyncRpcClient<'a, R: Reader, W: Writer, D: AsyncDelay> {
     io: ReaderWriterChannelIo<'a, R, W>,
     cmd_ch_config: ChannelConfig,
     rsp_ch_config: ChannelConfig,
+    outstanding: Option<Outstanding>,
+    // Seeded from the Target by the first enveloped request
+    next_request_id: Option<u32>,
+    poll_policy: PollPolicy,
+    polls: u32,
+    cancel_polls: u32,
     _delay: core::marker::PhantomData<D>,
 }
 
+/// Tracks a request whose future may have been dropped before the response
+/// was consumed.  Records the channel sequence numbers from before the command
+/// was published, so that it can later be determined how far the request got.
+#[derive(Debug, Clone, Copy)]
+struct Outstanding {
+    /// Command channel producer sequence number before the command was
+    /// published
+    cmd_seq: u32,
+    /// Response channel consumer sequence number before the response was
+    /// consumed, or `None` if the client has not yet started waiting for the
+    /// response, in which case it cannot have been consumed
+    rsp_seq: Option<u32>,
+}
+
 impl<'a, R: Reader, W: Writer, D: AsyncDelay> AsyncRpcClient<'a, R, W, D> {
     /// Create a new AsyncRpcClient
     ///
@@ -71,36 +79,266 @@ impl<'a, R: Reader, W: Writer, D: AsyncDelay> AsyncRpcClient<'a, R, W, D> {
     /// - `writer`: Writer object to write to target
     /// - `config`: Configuration for creating the client
     pub fn new(reader: &'a mut R, writer: &'a mut W, config: RpcClientConfig) -> Self {
-        let (cmd_ch_config, rsp_ch_config) = Self::get_channel_configs(config);
+        let (cmd_ch_config, rsp_ch_config) = config.channel_configs();
 
         Self {
             io: ReaderWriterChannelIo::new(reader, writer),
             cmd_ch_config,
             rsp_ch_config,
+            outstanding: None,
+            next_request_id: None,
+            poll_policy: PollPolicy::DEFAULT,
+            polls: 0,
+            cancel_polls: DEFAULT_CANCEL_POLLS,
             _delay: core::marker::PhantomData,
         }
     }
 
+    /// Set the policy used when waiting for responses.  Defaults to
+    /// [`PollPolicy::DEFAULT`].
+    pub fn with_poll_policy(mut self, policy: PollPolicy) -> Self {
+        self.poll_policy = policy;
+        self
+    }
+
+    /// Set the number of times to poll for the Target to acknowledge a
+    /// cancellation, by [`Self::cancel()`] or [`Self::request()`], before
+    /// returning [`crate::Error::Timeout`].  Defaults to
+    /// [`DEFAULT_CANCEL_POLLS`].
+    pub fn with_cancel_polls(mut self, polls: u32) -> Self {
+        self.cancel_polls = polls;
+        self
+    }
+
+    /// Number of times the response channel was polled for the last
+    /// response received, including the final, successful, poll.
+    pub fn polls(&self) -> u32 {
+        self.polls
+    }
+
     /// Perform an RPC request by sending a command and waiting for a response
     ///
     /// The format of the command and response data is application-specific.
     ///
+    /// The returned future is cancellation-safe.  If it is dropped before
+    /// completing, for example by a `select!` timeout, the next call to this
+    /// method cancels the stale request and discards its response before
+    /// sending the new command.  Each response returned is therefore always
+    /// the one produced by the command passed in.
+    ///
+    /// This works with both [`RpcClientConfig`] variants.  With
+    /// [`RpcClientConfig::Direct`], the channels are initialized by the first
+    /// request, and only connected to after that.
+    ///
+    /// If the Target does not acknowledge the cancellation of a stale
+    /// request within the limit set by [`Self::with_cancel_polls()`], this
+    /// returns [`crate::Error::Timeout`] without sending the new command.
+    /// The next call tries again.  Use [`Self::abandon()`] to give up on the
+    /// stale request instead.
+    ///
     /// Arguments:
     /// - `command`: Command data to send to target
     ///
     /// Returns:
     /// - `Ok(response_data)`: Response data received from target
+    /// - `Err(Error::Timeout)`: A stale request could not be cancelled
     /// - `Err(error)`: Error occurred during request
     pub async fn request(&mut self, command: &[u8]) -> Result<Vec<u8>, crate::Error> {
         debug!("Starting RPC request ({} bytes)", command.len());
 
-        // Send command phase - create channel, send, drop channel
-        let mut cmd_ch = self.cmd_channel().await?;
+        // Get rid of any response to a previous request whose future was
+        // dropped
+        if self.resolve_outstanding().await? {
+            self.discard_stale_response().await?;
+        }
+
+        // Send command phase - create channel, send, drop channel.  The
+        // sequence number is recorded from the same channel before
+        // publishing, so that if this future is dropped, the next request can
+        // tell whether the command was sent.
+        let mut cmd_ch = self
+            .cmd_ch_config
+            .channel(&mut self.io, ChannelActor::Producer)
+            .await?;
+        let cmd_seq = cmd_ch.producer_seq().await?;
+        self.outstanding = Some(Outstanding {
+            cmd_seq,
+            rsp_seq: None,
+        });
         cmd_ch.publish_bytes(command).await?;
         debug!("Command sent to target");
 
+        self.receive_response(NoDeadline).await
+    }
+
+    /// Perform an RPC request using the standard [`Envelope`].
+    ///
+    /// A request ID is generated and sent in the envelope header with the
+    /// command.  The target must echo it in the response, for example using
+    /// [`crate::channel::Channel::publish_response()`].  The first request ID
+    /// is the command channel's producer sequence number, so it differs from
+    /// those sent by earlier clients.
+    ///
+    /// Arguments:
+    /// - `msg_type`: Application-defined message type
+    /// - `payload`: Command payload to send to target
+    ///
+    /// Returns:
+    /// - `Ok((envelope, response_payload))`: Response envelope and payload
+    ///   received from target
+    /// - `Err(Error::SequenceMismatch)`: Response was for a different request
+    /// - `Err(Error::Malformed)`: Response envelope was invalid
+    /// - `Err(error)`: Other error occurred during request
+    pub async fn request_enveloped(
+        &mut self,
+        msg_type: u8,
+        payload: &[u8],
+    ) -> Result<(Envelope, Vec<u8>), crate::Error> {
+        let request_id = self.take_request_id().await?;
+
+        let envelope = Envelope::request(request_id, msg_type, payload.len())?;
+        let mut command = Vec::with_capacity(Envelope::SIZE + payload.len());
+        command.extend_from_slice(&envelope.encode());
+        command.extend_from_slice(payload);
+
+        let mut response = self.request(&command).await?;
+        let rsp_envelope = Envelope::decode(&response)?;
+        if !rsp_envelope.is_response()
+            || rsp_envelope.length as usize != response.len() - Envelope::SIZE
+        {
+            warn!("Malformed response envelope {rsp_envelope:?}");
+            return Err(crate::Error::Malformed);
+        }
+        if rsp_envelope.request_id != request_id {
+            warn!(
+                "Response request ID {} doesn't match request {}",
+                rsp_envelope.request_id, request_id
+            );
+            return Err(crate::Error::SequenceMismatch);
+        }
+
+        response.drain(..Envelope::SIZE);
+        Ok((rsp_envelope, response))
+    }
+
+    // Allocates a request ID.  The first is seeded from the command
+    // channel's producer sequence number, which every command sent
+    // increments, so it is beyond the IDs any earlier client has sent.
+    async fn take_request_id(&mut self) -> Result<u32, crate::Error> {
+        let request_id = match self.next_request_id {
+            Some(request_id) => request_id,
+            None => {
+                self.cmd_ch_config
+                    .channel(&mut self.io, ChannelActor::Producer)
+                    .await?
+                    .producer_seq()
+                    .await?
+            }
+        };
+        self.next_request_id = Some(request_id.wrapping_add(1));
+        Ok(request_id)
+    }
+
+    /// Call a command on a [`crate::server::RpcServer`].
+    ///
+    /// Sends the command ID followed by the payload, and checks the status
+    /// byte of the response.
+    ///
+    /// Arguments:
+    /// - `command_id`: Command ID
+    /// - `payload`: Command payload
+    ///
+    /// Returns:
+    /// - `Ok(response_payload)`: Response payload, excluding the status byte
+    /// - `Err(Error::Status(status))`: Server returned a non-OK status
+    /// - `Err(error)`: Other error occurred during request
+    pub async fn call(&mut self, command_id: u8, payload: &[u8]) -> Result<Vec<u8>, crate::Error> {
+        let mut command = Vec::with_capacity(1 + payload.len());
+        command.push(command_id);
+        command.extend_from_slice(payload);
+
+        let mut response = self.request(&command).await?;
+        let status = Status::from(*response.first().ok_or(crate::Error::Malformed)?);
+        if status != Status::Ok {
+            debug!("Command {command_id:#04X} failed {status:?}");
+            return Err(crate::Error::Status(status));
+        }
+
+        response.remove(0);
+        Ok(response)
+    }
+
+    /// Returns whether a request is outstanding - that is, a previous
+    /// [`Self::request()`] future was dropped after sending its command,
+    /// and its response has not yet been consumed.
+    pub async fn is_outstanding(&mut self) -> Result<bool, crate::Error> {
+        self.resolve_outstanding().await
+    }
+
+    /// Cancel a request which is still being processed by the target.
+    ///
+    /// Used when the future returned by [`Self::request()`] has been dropped
+    /// before the response arrived, for example following a timeout.  Raises
+    /// a cancel indication on the command channel, and waits for the target
+    /// to respond.
+    ///
+    /// It is not necessary to call this before the next [`Self::request()`],
+    /// which cancels any stale request itself.
+    ///
+    /// Waits for the Target at most the number of polls set by
+    /// [`Self::with_cancel_polls()`].  If it times out, the request remains
+    /// outstanding, so this can be called again.
+    ///
+    /// Returns:
+    /// - `Err(Error::Cancelled)`: Target acknowledged the cancellation
+    /// - `Ok(response_data)`: Target completed the request before seeing the
+    ///   cancel indication
+    /// - `Err(Error::InvalidOperation)`: No request is outstanding
+    /// - `Err(Error::Timeout)`: Target did not respond in time
+    /// - `Err(error)`: Other error occurred
+    pub async fn cancel(&mut self) -> Result<Vec<u8>, crate::Error> {
+        if !self.resolve_outstanding().await? {
+            return Err(crate::Error::InvalidOperation);
+        }
+
+        debug!("Cancelling RPC request");
+
+        let mut cmd_ch = self.cmd_channel().await?;
+        cmd_ch.cancel().await?;
+
+        self.receive_response(MaxWaits(self.cancel_polls)).await
+    }
+
+    /// Forget any outstanding request, without cancelling it, for example
+    /// because the Target has been reset, or has stopped responding.
+    ///
+    /// If the Target later responds to the forgotten request, the response
+    /// will be returned by the next [`Self::request()`] instead of its own.
+    pub fn abandon(&mut self) {
+        if self.outstanding.take().is_some() {
+            debug!("Abandoned outstanding RPC request");
+        }
+    }
+
+    async fn receive_response(
+        &mut self,
+        mut deadline: impl Deadline,
+    ) -> Result<Vec<u8>, crate::Error> {
         // Receive response phase - create channel, wait, read, drop channel
-        let mut rsp_ch = self.rsp_channel().await?;
+        let mut poller = Poller::new(self.poll_policy);
+        let mut rsp_ch = self
+            .rsp_ch_config
+            .channel(&mut self.io, ChannelActor::Consumer)
+            .await?;
+
+        // Record the sequence number before consuming, so that if this
+        // future is dropped, the next request can tell whether the response
+        // was consumed
+        if let Some(outstanding) = self.outstanding.as_mut()
+            && outstanding.rsp_seq.is_none()
+        {
+            outstanding.rsp_seq = Some(rsp_ch.consumer_seq().await?);
+        }
 
         // Wait for response with polling
         let response_size = loop {
@@ -108,14 +346,29 @@ impl<'a, R: Reader, W: Writer, D: AsyncDelay> AsyncRpcClient<'a, R, W, D> {
                 debug!("Response available ({} bytes)", size);
                 break size;
             }
+            if deadline.expired() {
+                debug!("Timed out waiting for response");
+                return Err(crate::Error::Timeout);
+            }
 
-            // Yield with reasonable delay to avoid spinning too fast
-            D::delay().await;
+            // Yield according to the policy, to avoid spinning too fast
+            poller.wait::<D>().await;
         };
 
+        // Check whether the target abandoned the request.  Flags must be read
+        // before consuming, as they then belong to the producer again.
+        let flags = rsp_ch.flags().await?;
+
         // Read the response data
         let mut response_buf = vec![0u8; response_size];
         let received_size = rsp_ch.consume_bytes(&mut response_buf).await?;
+        self.outstanding = None;
+        self.polls = poller.polls() + 1;
+
+        if flags == ChannelFlags::Cancelled {
+            debug!("RPC request cancelled by target");
+            return Err(crate::Error::Cancelled);
+        }
 
         if received_size != response_size {
             warn!(
@@ -129,56 +382,67 @@ impl<'a, R: Reader, W: Writer, D: AsyncDelay> AsyncRpcClient<'a, R, W, D> {
         Ok(response_buf)
     }
 
-    fn get_channel_configs(config: RpcClientConfig) -> (ChannelConfig, ChannelConfig) {
-        match config {
-            RpcClientConfig::Direct {
-                cmd_ch_ptr,
-                cmd_ch_size,
-                rsp_ch_ptr,
-                rsp_ch_size,
-            } => (
-                ChannelConfig::Direct {
-                    ptr: cmd_ch_ptr,
-                    size: cmd_ch_size,
-                },
-                ChannelConfig::Direct {
-                    ptr: rsp_ch_ptr,
-                    size: rsp_ch_size,
-                },
-            ),
-            RpcClientConfig::FromTarget {
-                cmd_ch_ptr,
-                rsp_ch_ptr,
-            } => (
-                ChannelConfig::FromTarget { ptr: cmd_ch_ptr },
-                ChannelConfig::FromTarget { ptr: rsp_ch_ptr },
-            ),
+    /// Get the data capacity of the command channel - the maximum command
+    /// size.
+    pub async fn cmd_capacity(&mut self) -> Result<usize, crate::Error> {
+        self.cmd_channel().await?.data_capacity().await
+    }
+
+    // Works out whether the outstanding request, if any, still has a response
+    // owed, clearing it if not.
+    async fn resolve_outstanding(&mut self) -> Result<bool, crate::Error> {
+        let Some(outstanding) = self.outstanding else {
+            return Ok(false);
+        };
+
+        // If the command was never published, nothing is owed
+        let cmd_seq = self.cmd_channel().await?.producer_seq().await?;
+        if cmd_seq == outstanding.cmd_seq {
+            debug!("Previous command was not sent");
+            self.outstanding = None;
+            return Ok(false);
         }
+
+        // If the response was consumed, nothing is owed
+        if let Some(rsp_seq) = outstanding.rsp_seq
+            && self.rsp_channel().await?.consumer_seq().await? != rsp_seq
+        {
+            debug!("Previous response was already consumed");
+            self.outstanding = None;
+            return Ok(false);
+        }
+
+        Ok(true)
     }
 
-    async fn cmd_channel<'method>(
-        &'method mut self,
-    ) -> Result<ReaderWriterChannel<'method, 'a, R, W>, crate::Error> {
-        match self.cmd_ch_config {
-            ChannelConfig::Direct { ptr, size } => {
-                ReaderWriterChannel::new(&mut self.io, ChannelActor::Producer, ptr, size).await
+    // Cancels the outstanding request and drops its response
+    async fn discard_stale_response(&mut self) -> Result<(), crate::Error> {
+        match self.cancel().await {
+            Ok(response) => {
+                debug!("Discarded stale response ({} bytes)", response.len());
+                Ok(())
             }
-            ChannelConfig::FromTarget { ptr } => {
-                ReaderWriterChannel::from_target(&mut self.io, ChannelActor::Producer, ptr).await
+            Err(crate::Error::Cancelled) => {
+                debug!("Stale request cancelled by target");
+                Ok(())
             }
+            Err(e) => Err(e),
         }
     }
 
+    async fn cmd_channel<'method>(
+        &'method mut self,
+    ) -> Result<ReaderWriterChannel<'method, 'a, R, W>, crate::Error> {
+        self.cmd_ch_config
+            .channel(&mut self.io, ChannelActor::Producer)
+            .await
+    }
+
     async fn rsp_channel<'method>(
         &'method mut self,
     ) -> Result<ReaderWriterChannel<'method, 'a, R, W>, crate::Error> {
-        match self.rsp_ch_config {
-            ChannelConfig::Direct { ptr, size } => {
-                ReaderWriterChannel::new(&mut self.io, ChannelActor::Consumer, ptr, size).await
-            }
-            ChannelConfig::FromTarget { ptr } => {
-                ReaderWriterChannel::from_target(&mut self.io, ChannelActor::Consumer, ptr).await
-            }
-        }
+        self.rsp_ch_config
+            .channel(&mut self.io, ChannelActor::Consumer)
+            .await
     }
 }
@@ -2,20 +2,68 @@
 //! - Command channel: Host writes commands, Target reads
 //! - Response channel: Target writes responses, Host reads
 //!
-//! See [`AsyncRpcClient`] for async client usage, for example on a Host.
+//! See [`AsyncRpcClient`] for async client usage, for example on a Host, and
+//! [`PipelinedRpcClient`] for a client supporting multiple outstanding
+//! requests.  [`EventSubscriber`] receives unsolicited events from a Target.
+//! [`SessionManager`] manages clients for multiple Targets, and [`Router`]
+//! passes messages between Targets.
+//! `SharedIo`, behind the `shared` feature, shares a Target's debug interface
+//! between multiple async tasks.
 
 // Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
 //
 // MIT License
 
+#[cfg(feature = "async")]
+pub mod event;
 #[cfg(feature = "async")]
 pub mod futures;
+#[cfg(feature = "async")]
+pub mod pipelined;
+#[cfg(feature = "async")]
+pub mod router;
+#[cfg(feature = "async")]
+pub mod session;
+#[cfg(feature = "shared")]
+pub mod shared;
+#[cfg(feature = "async")]
+pub mod system;
+#[cfg(all(feature = "async", feature = "codec"))]
+pub mod typed;
+#[cfg(all(feature = "async", feature = "update"))]
+pub mod update;
 
 #[cfg(feature = "async")]
-pub use futures::{AsyncDelay, AsyncRpcClient};
+pub use crate::channel::poll::{AsyncDelay, PollPolicy, Poller};
+#[cfg(feature = "stream")]
+pub use event::EventStream;
+#[cfg(feature = "async")]
+pub use event::{Event, EventSubscriber};
+#[cfg(feature = "async")]
+pub use futures::AsyncRpcClient;
+#[cfg(feature = "async")]
+pub use pipelined::PipelinedRpcClient;
+#[cfg(feature = "async")]
+pub use router::{PortStats, RouteMatch, Router};
+#[cfg(feature = "async")]
+pub use session::{SessionManager, TargetHealth};
+#[cfg(feature = "shared")]
+pub use shared::{SharedIo, SharedIoHandle, SharedIoLock, SharedReader, SharedWriter};
+#[cfg(feature = "async")]
+pub use system::{CommandDescription, FirmwareVersion};
+
+#[cfg(feature = "async")]
+use crate::Result;
+#[cfg(feature = "async")]
+use crate::channel::{ChannelActor, ReaderWriterChannel, ReaderWriterChannelIo};
+#[cfg(feature = "async")]
+use crate::io::{Reader, Writer};
 
 /// Configuration for creating an RPC Client.
-/// - `Direct`: Create channels with explicit sizes
+/// - `Direct`: Create channels with explicit sizes.  The Host initializes
+///   each channel's control block the first time it uses the channel, and
+///   from then on connects to it, as with `FromTarget`, so that data
+///   published but not yet consumed is never discarded.
 /// - `FromTarget`: Create channels by reading sizes from target memory,
 ///   normally used by Hosts.
 #[derive(Debug)]
@@ -38,12 +86,69 @@ pub enum RpcClientConfig {
     },
 }
 
+#[cfg(feature = "async")]
+impl RpcClientConfig {
+    /// Split into command and response channel configurations
+    pub(crate) fn channel_configs(self) -> (ChannelConfig, ChannelConfig) {
+        match self {
+            RpcClientConfig::Direct {
+                cmd_ch_ptr,
+                cmd_ch_size,
+                rsp_ch_ptr,
+                rsp_ch_size,
+            } => (
+                ChannelConfig::Direct {
+                    ptr: cmd_ch_ptr,
+                    size: cmd_ch_size,
+                },
+                ChannelConfig::Direct {
+                    ptr: rsp_ch_ptr,
+                    size: rsp_ch_size,
+                },
+            ),
+            RpcClientConfig::FromTarget {
+                cmd_ch_ptr,
+                rsp_ch_ptr,
+            } => (
+                ChannelConfig::FromTarget { ptr: cmd_ch_ptr },
+                ChannelConfig::FromTarget { ptr: rsp_ch_ptr },
+            ),
+        }
+    }
+}
+
 #[cfg(feature = "async")]
 /// Configuration for how to create a channel
-#[derive(Debug, Clone)]
-enum ChannelConfig {
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ChannelConfig {
     /// Create channel with explicit size
     Direct { ptr: u32, size: usize },
     /// Create channel by reading size from target
     FromTarget { ptr: u32 },
 }
+
+#[cfg(feature = "async")]
+impl ChannelConfig {
+    /// Create a short-lived channel using this configuration.
+    ///
+    /// A `Direct` channel is initialized the first time, after which the
+    /// configuration becomes `FromTarget`, so later channels connect to it
+    /// rather than re-initializing it, which would discard any data
+    /// published but not yet consumed, and reset the sequence numbers.
+    pub(crate) async fn channel<'c, 'a, R: Reader, W: Writer>(
+        &mut self,
+        io: &'c mut ReaderWriterChannelIo<'a, R, W>,
+        actor: ChannelActor,
+    ) -> Result<ReaderWriterChannel<'c, 'a, R, W>> {
+        match *self {
+            ChannelConfig::Direct { ptr, size } => {
+                let channel = ReaderWriterChannel::new(io, actor, ptr, size).await?;
+                *self = ChannelConfig::FromTarget { ptr };
+                Ok(channel)
+            }
+            ChannelConfig::FromTarget { ptr } => {
+                ReaderWriterChannel::from_target(io, actor, ptr).await
+            }
+        }
+    }
+}
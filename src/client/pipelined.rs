@@ -0,0 +1,488 @@
+//! Pipelined asynchronous Client - typically used by a Host.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::future::poll_fn;
+use core::ops::{Deref, DerefMut};
+use core::task::{Poll, Waker};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{ChannelActor, Deadline, ReaderWriterChannel, ReaderWriterChannelIo};
+use crate::client::{AsyncDelay, ChannelConfig, PollPolicy, Poller, RpcClientConfig};
+use crate::envelope::Envelope;
+use crate::io::{Reader, Writer};
+use crate::{Error, Result};
+
+/// Async RPC Client supporting multiple outstanding requests over a single
+/// command/response channel pair.
+///
+/// Unlike [`crate::client::AsyncRpcClient`], which strictly alternates one
+/// command with one response, this client sends further commands while
+/// earlier ones are still being processed, up to a configurable window.
+/// This hides the round trip latency of the debug interface.
+///
+/// Requests always use the standard [`Envelope`], and responses are matched
+/// back to their requests using the request ID, so the target may answer
+/// them in any order.  The target must consume commands promptly to allow
+/// further commands to be sent - see [`crate::channel::CommandQueue`].
+///
+/// [`Self::request()`] takes `&self`, so multiple request futures can be
+/// polled concurrently, for example using `join`.  The client is not `Sync`,
+/// so all requests must come from the same task.
+///
+/// [`Self::request()`] also takes a [`crate::channel::Deadline`], as the
+/// Target may never answer a command.  For example, a
+/// [`crate::channel::CommandQueue`] discards a malformed command without a
+/// response, as it has no request ID to answer.
+///
+/// If a request future is dropped after its command was sent, the request
+/// continues to count against the window until its response arrives.  If
+/// the Target never responds, the request is forgotten after the response
+/// channel has been polled [`Self::DEFAULT_ABANDONED_TIMEOUT`] times - see
+/// [`Self::with_abandoned_timeout()`] and [`Self::forget_abandoned()`].
+///
+/// Example usage:
+///
+/// ```rust,ignore
+/// use airfrog_rpc::channel::MaxWaits;
+/// use airfrog_rpc::client::{PipelinedRpcClient, RpcClientConfig};
+///
+/// let config = RpcClientConfig::FromTarget {
+///     cmd_ch_ptr: 0x2000_0000,
+///     rsp_ch_ptr: 0x2000_1000,
+/// };
+/// let client = PipelinedRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, config, 4);
+/// let (a, b) = join(
+///     client.request(1, &[0x01], MaxWaits(100)),
+///     client.request(2, &[0x02], MaxWaits(100)),
+/// )
+/// .await;
+/// ```
+pub struct PipelinedRpcClient<'a, R: Reader, W: Writer, D: AsyncDelay> {
+    // Taken by whichever request is currently accessing the target
+    io: Cell<Option<PipelineIo<'a, R, W>>>,
+    // Requests waiting for access to the target
+    io_waiters: RefCell<Vec<Waker>>,
+    state: RefCell<PipelineState>,
+    window: usize,
+    abandoned_timeout: u32,
+    poll_policy: PollPolicy,
+    _delay: core::marker::PhantomData<D>,
+}
+
+// Everything needed to access the target's channels
+struct PipelineIo<'a, R: Reader, W: Writer> {
+    io: ReaderWriterChannelIo<'a, R, W>,
+    cmd_ch_config: ChannelConfig,
+    rsp_ch_config: ChannelConfig,
+}
+
+impl<'a, R: Reader, W: Writer> PipelineIo<'a, R, W> {
+    async fn cmd_channel<'method>(
+        &'method mut self,
+    ) -> Result<ReaderWriterChannel<'method, 'a, R, W>> {
+        self.cmd_ch_config
+            .channel(&mut self.io, ChannelActor::Producer)
+            .await
+    }
+
+    async fn rsp_channel<'method>(
+        &'method mut self,
+    ) -> Result<ReaderWriterChannel<'method, 'a, R, W>> {
+        self.rsp_ch_config
+            .channel(&mut self.io, ChannelActor::Consumer)
+            .await
+    }
+}
+
+// Bookkeeping shared between requests.  Never borrowed across an await.
+#[derive(Default)]
+struct PipelineState {
+    // Seeded from the Target by the first request
+    next_request_id: Option<u32>,
+    // Requests waiting to be sent, having been allocated space in the window
+    reserved: usize,
+    // Requests sent, with their responses once received
+    pending: BTreeMap<u32, Option<(Envelope, Vec<u8>)>>,
+    // Requests sent, whose futures were dropped before the response arrived,
+    // with the value of `polls` at which they are forgotten
+    abandoned: BTreeMap<u32, u32>,
+    // Number of times the response channel has been polled
+    polls: u32,
+}
+
+impl PipelineState {
+    fn in_flight(&self) -> usize {
+        self.reserved + self.pending.len() + self.abandoned.len()
+    }
+
+    // Records a poll of the response channel, forgetting any abandoned
+    // requests which have timed out
+    fn poll(&mut self) {
+        self.polls = self.polls.wrapping_add(1);
+        let polls = self.polls;
+        self.abandoned.retain(|request_id, expiry| {
+            let keep = *expiry != polls;
+            if !keep {
+                warn!("Forgetting abandoned request {request_id}, with no response");
+            }
+            keep
+        });
+    }
+}
+
+impl<'a, R: Reader, W: Writer, D: AsyncDelay> PipelinedRpcClient<'a, R, W, D> {
+    /// Default number of times the response channel is polled, after a
+    /// request is abandoned, before it is forgotten
+    pub const DEFAULT_ABANDONED_TIMEOUT: u32 = 100;
+
+    /// Create a new PipelinedRpcClient
+    ///
+    /// Arguments:
+    /// - `reader`: Reader object to read from target
+    /// - `writer`: Writer object to write to target
+    /// - `config`: Configuration for creating the client
+    /// - `window`: Maximum number of requests in flight at once.  Values
+    ///   below 1 are treated as 1.
+    pub fn new(
+        reader: &'a mut R,
+        writer: &'a mut W,
+        config: RpcClientConfig,
+        window: usize,
+    ) -> Self {
+        let (cmd_ch_config, rsp_ch_config) = config.channel_configs();
+
+        Self {
+            io: Cell::new(Some(PipelineIo {
+                io: ReaderWriterChannelIo::new(reader, writer),
+                cmd_ch_config,
+                rsp_ch_config,
+            })),
+            io_waiters: RefCell::new(Vec::new()),
+            state: RefCell::new(PipelineState::default()),
+            window: window.max(1),
+            abandoned_timeout: Self::DEFAULT_ABANDONED_TIMEOUT,
+            poll_policy: PollPolicy::DEFAULT,
+            _delay: core::marker::PhantomData,
+        }
+    }
+
+    /// Set the policy used when waiting for the Target.  Defaults to
+    /// [`PollPolicy::DEFAULT`].
+    pub fn with_poll_policy(mut self, policy: PollPolicy) -> Self {
+        self.poll_policy = policy;
+        self
+    }
+
+    /// Set how many times the response channel is polled, after a request
+    /// future is dropped, before the request is forgotten and stops counting
+    /// against the window.  A response arriving after that is discarded.
+    /// Defaults to [`Self::DEFAULT_ABANDONED_TIMEOUT`].  Values below 1 are
+    /// treated as 1.
+    pub fn with_abandoned_timeout(mut self, polls: u32) -> Self {
+        self.abandoned_timeout = polls.max(1);
+        self
+    }
+
+    /// Maximum number of requests in flight at once
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Number of requests currently in flight, including those whose futures
+    /// have been dropped but whose responses have not yet arrived.
+    pub fn in_flight(&self) -> usize {
+        self.state.borrow().in_flight()
+    }
+
+    /// Forget all requests whose futures have been dropped, but whose
+    /// responses have not yet arrived, so they no longer count against the
+    /// window.  For example, after resetting the Target.  Any responses which
+    /// do arrive later are discarded.
+    ///
+    /// Returns the number of requests forgotten.
+    pub fn forget_abandoned(&self) -> usize {
+        let mut state = self.state.borrow_mut();
+        let count = state.abandoned.len();
+        state.abandoned.clear();
+        count
+    }
+
+    /// Perform an RPC request, alongside any other requests in flight.
+    ///
+    /// Waits for space in the window, sends the command, then waits for the
+    /// matching response.  While waiting, responses to other requests are
+    /// collected and passed to their futures.
+    ///
+    /// If the returned future is dropped after the command was sent, the
+    /// response is discarded when it arrives, or the request is forgotten
+    /// after the abandoned timeout - see [`Self::with_abandoned_timeout()`].
+    ///
+    /// Arguments:
+    /// - `msg_type`: Application-defined message type
+    /// - `payload`: Command payload to send to target
+    /// - `deadline`: When to give up waiting for the Target, for example
+    ///   [`crate::channel::MaxWaits`]
+    ///
+    /// Returns:
+    /// - `Ok((envelope, response_payload))`: Response envelope and payload
+    ///   received from target
+    /// - `Err(Error::Timeout)`: Deadline passed first.  If the command was
+    ///   sent, the request is treated as abandoned.
+    /// - `Err(error)`: Error occurred during request
+    pub async fn request(
+        &self,
+        msg_type: u8,
+        payload: &[u8],
+        mut deadline: impl Deadline,
+    ) -> Result<(Envelope, Vec<u8>)> {
+        // Wait for space in the window
+        let reservation = self.reserve(&mut deadline).await?;
+
+        let request_id = self.take_request_id().await?;
+        let envelope = Envelope::request(request_id, msg_type, payload.len())?;
+        let mut command = Vec::with_capacity(Envelope::SIZE + payload.len());
+        command.extend_from_slice(&envelope.encode());
+        command.extend_from_slice(payload);
+
+        // Send the command once the target has consumed the previous one,
+        // collecting responses while waiting so the target isn't blocked
+        // publishing them.
+        let mut poller = Poller::new(self.poll_policy);
+        loop {
+            let mut io = self.lock().await;
+            let mut cmd_ch = io.cmd_channel().await?;
+            if cmd_ch.can_publish().await? {
+                cmd_ch.publish_bytes(&command).await?;
+                break;
+            }
+            self.collect_response(&mut io).await?;
+            drop(io);
+
+            if deadline.expired() {
+                return Err(Error::Timeout);
+            }
+            poller.wait::<D>().await;
+        }
+        debug!("Pipelined command {request_id} sent to target");
+        let _pending = reservation.into_pending(request_id);
+
+        // Wait for the response
+        poller.reset();
+        loop {
+            if let Some(response) = self.take_response(request_id) {
+                return Ok(response);
+            }
+
+            let mut io = self.lock().await;
+            self.collect_response(&mut io).await?;
+            drop(io);
+
+            if let Some(response) = self.take_response(request_id) {
+                return Ok(response);
+            }
+
+            if deadline.expired() {
+                return Err(Error::Timeout);
+            }
+            poller.wait::<D>().await;
+        }
+    }
+
+    // Allocates a request ID.  The first is seeded from the command
+    // channel's producer sequence number, which every command sent
+    // increments, so it is beyond the IDs any earlier client has sent.
+    async fn take_request_id(&self) -> Result<u32> {
+        if self.state.borrow().next_request_id.is_none() {
+            let mut io = self.lock().await;
+            let seed = io.cmd_channel().await?.producer_seq().await?;
+            // Another request may have seeded it while this one waited
+            self.state.borrow_mut().next_request_id.get_or_insert(seed);
+        }
+
+        let mut state = self.state.borrow_mut();
+        let request_id = state.next_request_id.unwrap_or_default();
+        state.next_request_id = Some(request_id.wrapping_add(1));
+        Ok(request_id)
+    }
+
+    // Waits for space in the window, and reserves it.  While waiting,
+    // collects responses if no other request is accessing the target, so
+    // responses to abandoned requests are received, and they time out, even
+    // if this is the only request.
+    async fn reserve(&self, deadline: &mut impl Deadline) -> Result<Reservation<'_, 'a, R, W, D>> {
+        let mut poller = Poller::new(self.poll_policy);
+        loop {
+            {
+                let mut state = self.state.borrow_mut();
+                if state.in_flight() < self.window {
+                    state.reserved += 1;
+                    return Ok(Reservation { client: self });
+                }
+            }
+            if let Some(mut io) = self.try_lock() {
+                self.collect_response(&mut io).await?;
+            }
+            if deadline.expired() {
+                return Err(Error::Timeout);
+            }
+            poller.wait::<D>().await;
+        }
+    }
+
+    // Gets exclusive access to the target, if no other request has it
+    fn try_lock(&self) -> Option<IoGuard<'_, 'a, R, W, D>> {
+        self.io.take().map(|io| IoGuard {
+            client: self,
+            io: Some(io),
+        })
+    }
+
+    // Waits for exclusive access to the target, sleeping until it is
+    // released by another request
+    async fn lock(&self) -> IoGuard<'_, 'a, R, W, D> {
+        poll_fn(|cx| match self.try_lock() {
+            Some(io) => Poll::Ready(io),
+            None => {
+                let mut waiters = self.io_waiters.borrow_mut();
+                if !waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                    waiters.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    // Returns access to the target, and wakes any requests waiting for it
+    fn unlock(&self, io: Option<PipelineIo<'a, R, W>>) {
+        self.io.set(io);
+        let waiters = core::mem::take(&mut *self.io_waiters.borrow_mut());
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+
+    // Consumes a response from the target, if available, and stores it
+    // against its request
+    async fn collect_response(&self, io: &mut PipelineIo<'a, R, W>) -> Result<()> {
+        self.state.borrow_mut().poll();
+        let mut rsp_ch = io.rsp_channel().await?;
+        let Some(size) = rsp_ch.data_available().await? else {
+            return Ok(());
+        };
+        let mut response = vec![0u8; size];
+        let size = rsp_ch.consume_bytes(&mut response).await?;
+        response.truncate(size);
+
+        let envelope = match Envelope::decode(&response) {
+            Ok(envelope)
+                if envelope.is_response() && envelope.length as usize == size - Envelope::SIZE =>
+            {
+                envelope
+            }
+            _ => {
+                warn!("Discarding malformed pipelined response ({size} bytes)");
+                return Ok(());
+            }
+        };
+        response.drain(..Envelope::SIZE);
+
+        let request_id = envelope.request_id;
+        let mut state = self.state.borrow_mut();
+        if let Some(slot) = state.pending.get_mut(&request_id) {
+            debug!("Pipelined response {request_id} received");
+            *slot = Some((envelope, response));
+        } else if state.abandoned.remove(&request_id).is_some() {
+            debug!("Discarding response to abandoned request {request_id}");
+        } else {
+            warn!("Discarding response to unknown request {request_id}");
+        }
+
+        Ok(())
+    }
+
+    fn take_response(&self, request_id: u32) -> Option<(Envelope, Vec<u8>)> {
+        let mut state = self.state.borrow_mut();
+        if let Some(Some(_)) = state.pending.get(&request_id) {
+            state.pending.remove(&request_id).flatten()
+        } else {
+            None
+        }
+    }
+}
+
+// Space in the window, reserved for a request which has not yet been sent.
+// Released if the request future is dropped.
+struct Reservation<'c, 'a, R: Reader, W: Writer, D: AsyncDelay> {
+    client: &'c PipelinedRpcClient<'a, R, W, D>,
+}
+
+impl<'c, 'a, R: Reader, W: Writer, D: AsyncDelay> Reservation<'c, 'a, R, W, D> {
+    // Converts the reservation into a pending request, once sent
+    fn into_pending(self, request_id: u32) -> Pending<'c, 'a, R, W, D> {
+        let client = self.client;
+        {
+            let mut state = client.state.borrow_mut();
+            state.pending.insert(request_id, None);
+        }
+        drop(self);
+        Pending { client, request_id }
+    }
+}
+
+impl<R: Reader, W: Writer, D: AsyncDelay> Drop for Reservation<'_, '_, R, W, D> {
+    fn drop(&mut self) {
+        self.client.state.borrow_mut().reserved -= 1;
+    }
+}
+
+// A request which has been sent.  If the request future is dropped before
+// the response is taken, the response is discarded when it arrives.
+struct Pending<'c, 'a, R: Reader, W: Writer, D: AsyncDelay> {
+    client: &'c PipelinedRpcClient<'a, R, W, D>,
+    request_id: u32,
+}
+
+impl<R: Reader, W: Writer, D: AsyncDelay> Drop for Pending<'_, '_, R, W, D> {
+    fn drop(&mut self) {
+        let mut state = self.client.state.borrow_mut();
+        if let Some(None) = state.pending.remove(&self.request_id) {
+            let expiry = state.polls.wrapping_add(self.client.abandoned_timeout);
+            state.abandoned.insert(self.request_id, expiry);
+        }
+    }
+}
+
+// Exclusive access to the target.  Returned to the client when dropped.
+struct IoGuard<'c, 'a, R: Reader, W: Writer, D: AsyncDelay> {
+    client: &'c PipelinedRpcClient<'a, R, W, D>,
+    io: Option<PipelineIo<'a, R, W>>,
+}
+
+impl<'a, R: Reader, W: Writer, D: AsyncDelay> Deref for IoGuard<'_, 'a, R, W, D> {
+    type Target = PipelineIo<'a, R, W>;
+
+    fn deref(&self) -> &Self::Target {
+        self.io.as_ref().expect("IoGuard holds I/O until dropped")
+    }
+}
+
+impl<R: Reader, W: Writer, D: AsyncDelay> DerefMut for IoGuard<'_, '_, R, W, D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.io.as_mut().expect("IoGuard holds I/O until dropped")
+    }
+}
+
+impl<R: Reader, W: Writer, D: AsyncDelay> Drop for IoGuard<'_, '_, R, W, D> {
+    fn drop(&mut self) {
+        self.client.unlock(self.io.take());
+    }
+}
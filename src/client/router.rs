@@ -0,0 +1,319 @@
+//! Message router - used by a Host to pass messages between Targets.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{ChannelActor, ReaderWriterChannelIo};
+use crate::client::{AsyncDelay, ChannelConfig, PollPolicy, Poller, RpcClientConfig};
+use crate::io::{Reader, Writer};
+use crate::{Error, Result};
+
+/// Which messages a route applies to
+#[derive(Debug, Clone)]
+pub enum RouteMatch {
+    /// All messages
+    Any,
+    /// Messages whose first byte, the stream ID, is this value.  For example
+    /// [`crate::mux`] messages containing frames for a single stream.
+    Stream(u8),
+    /// Messages whose first four bytes, a little-endian address, are within
+    /// this range
+    Address(Range<u32>),
+    /// Messages for which this function returns true
+    Custom(fn(&[u8]) -> bool),
+}
+
+impl RouteMatch {
+    fn matches(&self, msg: &[u8]) -> bool {
+        match self {
+            RouteMatch::Any => true,
+            RouteMatch::Stream(stream) => msg.first() == Some(stream),
+            RouteMatch::Address(range) => msg
+                .get(..4)
+                .is_some_and(|a| range.contains(&u32::from_le_bytes([a[0], a[1], a[2], a[3]]))),
+            RouteMatch::Custom(f) => f(msg),
+        }
+    }
+}
+
+// Routing rule
+#[derive(Debug, Clone)]
+struct Route {
+    from: usize,
+    matches: RouteMatch,
+    to: usize,
+}
+
+/// Statistics for one of a [`Router`]'s ports.  Counters saturate at their
+/// maximum value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PortStats {
+    /// Messages received from the Target
+    pub received: u32,
+
+    /// Bytes received from the Target
+    pub bytes_received: u64,
+
+    /// Messages delivered to the Target
+    pub delivered: u32,
+
+    /// Bytes delivered to the Target
+    pub bytes_delivered: u64,
+
+    /// Messages received from the Target which were dropped, as no route
+    /// matched, or they were too large for the destination's channel
+    pub dropped: u32,
+
+    /// Number of times a message could not be delivered to the Target, as
+    /// its inbound channel was busy
+    pub busy: u32,
+}
+
+// A Target attached to the router
+struct Port<'a, R: Reader, W: Writer> {
+    io: ReaderWriterChannelIo<'a, R, W>,
+    // Host to Target
+    inbound: ChannelConfig,
+    // Target to Host
+    outbound: ChannelConfig,
+    // Message received from this port, waiting for its destination
+    pending: Option<(usize, Vec<u8>)>,
+    stats: PortStats,
+}
+
+/// Routes messages between Targets attached to the same Host.
+///
+/// Each Target is attached to a port, with [`Self::add_port()`], using a
+/// command/response channel pair.  The Target receives messages on the
+/// command channel, its inbound channel, and sends them on the response
+/// channel, its outbound channel.
+///
+/// Routes, added with [`Self::add_route()`], direct messages received from
+/// one port to another.  They are checked in the order they were added, and
+/// the first matching route is used.  Messages no route matches are dropped.
+///
+/// When the destination's inbound channel is busy, the message is held, and
+/// no more messages are consumed from the source until it is delivered.  As
+/// the source's outbound channel then remains full, the backpressure extends
+/// to the source Target.
+///
+/// ```rust,ignore
+/// use airfrog_rpc::client::{RouteMatch, Router};
+///
+/// let mut router = Router::<_, _, Delay>::new();
+/// let a = router.add_port(&mut reader_a, &mut writer_a, config_a);
+/// let b = router.add_port(&mut reader_b, &mut writer_b, config_b);
+/// router.add_route(a, RouteMatch::Any, b)?;
+/// router.add_route(b, RouteMatch::Any, a)?;
+/// router.run().await?;
+/// ```
+pub struct Router<'a, R: Reader, W: Writer, D: AsyncDelay> {
+    ports: Vec<Port<'a, R, W>>,
+    routes: Vec<Route>,
+    poll_policy: PollPolicy,
+    _delay: core::marker::PhantomData<D>,
+}
+
+impl<'a, R: Reader, W: Writer, D: AsyncDelay> Router<'a, R, W, D> {
+    /// Create a new Router, with no ports
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            ports: Vec::new(),
+            routes: Vec::new(),
+            poll_policy: PollPolicy::DEFAULT,
+            _delay: core::marker::PhantomData,
+        }
+    }
+
+    /// Set the policy used when waiting for the Target.  Defaults to
+    /// [`PollPolicy::DEFAULT`].
+    pub fn with_poll_policy(mut self, policy: PollPolicy) -> Self {
+        self.poll_policy = policy;
+        self
+    }
+
+    /// Attach a Target to a new port
+    ///
+    /// Arguments:
+    /// - `reader`: Reader object to read from the Target
+    /// - `writer`: Writer object to write to the Target
+    /// - `config`: Channel configuration.  The command channel is the
+    ///   Target's inbound channel, and the response channel its outbound
+    ///   channel.
+    ///
+    /// Returns the port number, used to add routes.
+    pub fn add_port(
+        &mut self,
+        reader: &'a mut R,
+        writer: &'a mut W,
+        config: RpcClientConfig,
+    ) -> usize {
+        let (inbound, outbound) = config.channel_configs();
+        self.ports.push(Port {
+            io: ReaderWriterChannelIo::new(reader, writer),
+            inbound,
+            outbound,
+            pending: None,
+            stats: PortStats::default(),
+        });
+        self.ports.len() - 1
+    }
+
+    /// Add a route, directing matching messages from port `from` to port
+    /// `to`
+    ///
+    /// Returns:
+    /// - `Ok(())`: Route added
+    /// - `Err(Error::InvalidOperation)`: Either port does not exist
+    pub fn add_route(&mut self, from: usize, matches: RouteMatch, to: usize) -> Result<()> {
+        if from >= self.ports.len() || to >= self.ports.len() {
+            return Err(Error::InvalidOperation);
+        }
+        self.routes.push(Route { from, matches, to });
+        Ok(())
+    }
+
+    /// Remove all routes.  Messages already received are still delivered.
+    pub fn clear_routes(&mut self) {
+        self.routes.clear();
+    }
+
+    /// Get a port's statistics
+    pub fn stats(&self, port: usize) -> Option<&PortStats> {
+        self.ports.get(port).map(|p| &p.stats)
+    }
+
+    /// Route messages until an error occurs, delaying whenever no message
+    /// was delivered, including when a message was received but its
+    /// destination was busy, or it was dropped
+    pub async fn run(&mut self) -> Result<()> {
+        let mut poller = Poller::new(self.poll_policy);
+        loop {
+            if self.poll().await? == 0 {
+                poller.wait::<D>().await;
+            } else {
+                poller.reset();
+            }
+        }
+    }
+
+    /// Make one pass over all ports, delivering any messages waiting for
+    /// their destinations, and receiving and routing a message from each
+    /// port with none waiting.
+    ///
+    /// How a message which cannot be delivered is handled depends on why:
+    /// - The destination's inbound channel is busy: the message is held, and
+    ///   delivery retried by later calls.  Counted in the destination port's
+    ///   [`PortStats::busy`].
+    /// - The message is larger than the destination's inbound channel: it can
+    ///   never be delivered, so is dropped, and counted in the source port's
+    ///   [`PortStats::dropped`].  Later messages from the source are routed
+    ///   as normal.
+    /// - Any other error, for example failing to access the Target: the
+    ///   message is held, and the error returned.  It is delivered by a later
+    ///   call, once the error clears.
+    ///
+    /// Returns the number of messages delivered.
+    pub async fn poll(&mut self) -> Result<usize> {
+        let mut delivered = 0;
+        for port in 0..self.ports.len() {
+            if self.ports[port].pending.is_none() {
+                self.receive(port).await?;
+            }
+            if self.deliver(port).await? {
+                delivered += 1;
+            }
+        }
+        Ok(delivered)
+    }
+
+    // Receives a message from a port, if available, and finds its
+    // destination
+    async fn receive(&mut self, port: usize) -> Result<()> {
+        let source = &mut self.ports[port];
+        let mut channel = source
+            .outbound
+            .channel(&mut source.io, ChannelActor::Consumer)
+            .await?;
+        let Some(size) = channel.data_available().await? else {
+            return Ok(());
+        };
+        let mut msg = vec![0u8; size];
+        let len = channel.consume_bytes(&mut msg).await?;
+        msg.truncate(len);
+
+        source.stats.received = source.stats.received.saturating_add(1);
+        source.stats.bytes_received = source.stats.bytes_received.saturating_add(len as u64);
+
+        match self
+            .routes
+            .iter()
+            .find(|route| route.from == port && route.matches.matches(&msg))
+        {
+            Some(route) => {
+                trace!(
+                    "Routing {len} byte message from port {port} to {}",
+                    route.to
+                );
+                source.pending = Some((route.to, msg));
+            }
+            None => {
+                debug!("Dropping unrouted {len} byte message from port {port}");
+                source.stats.dropped = source.stats.dropped.saturating_add(1);
+            }
+        }
+        Ok(())
+    }
+
+    // Delivers the message waiting from a port, if any, returning whether it
+    // was delivered
+    async fn deliver(&mut self, port: usize) -> Result<bool> {
+        let Some((to, msg)) = self.ports[port].pending.take() else {
+            return Ok(false);
+        };
+
+        let dest = &mut self.ports[to];
+        let result = match dest
+            .inbound
+            .channel(&mut dest.io, ChannelActor::Producer)
+            .await
+        {
+            Ok(mut channel) => channel.publish_bytes(&msg).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => {
+                dest.stats.delivered = dest.stats.delivered.saturating_add(1);
+                dest.stats.bytes_delivered =
+                    dest.stats.bytes_delivered.saturating_add(msg.len() as u64);
+                Ok(true)
+            }
+            Err(Error::Busy) => {
+                dest.stats.busy = dest.stats.busy.saturating_add(1);
+                self.ports[port].pending = Some((to, msg));
+                Ok(false)
+            }
+            Err(Error::PayloadTooLarge) => {
+                warn!(
+                    "Dropping {} byte message from port {port}, too large for port {to}",
+                    msg.len()
+                );
+                let source = &mut self.ports[port].stats;
+                source.dropped = source.dropped.saturating_add(1);
+                Ok(false)
+            }
+            Err(e) => {
+                self.ports[port].pending = Some((to, msg));
+                Err(e)
+            }
+        }
+    }
+}
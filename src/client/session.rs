@@ -0,0 +1,266 @@
+//! Session manager - typically used by a Host driving several Targets.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::future::{Future, poll_fn};
+use core::pin::Pin;
+use core::task::Poll;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::client::{AsyncDelay, AsyncRpcClient, RpcClientConfig};
+use crate::io::{Reader, Writer};
+use crate::{Error, Result};
+
+/// Default number of consecutive failures after which a target is considered
+/// unhealthy
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// Health of a target, as tracked by a [`SessionManager`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TargetHealth {
+    /// Number of requests which succeeded
+    pub successes: u32,
+
+    /// Number of requests which failed
+    pub failures: u32,
+
+    /// Number of requests which failed since the last success
+    pub consecutive_failures: u32,
+
+    /// Error from the most recent failed request
+    pub last_error: Option<Error>,
+}
+
+impl TargetHealth {
+    fn record<T>(&mut self, result: &Result<T>) {
+        match result {
+            Ok(_) => {
+                self.successes = self.successes.saturating_add(1);
+                self.consecutive_failures = 0;
+            }
+            Err(e) => {
+                self.failures = self.failures.saturating_add(1);
+                self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+                self.last_error = Some(*e);
+            }
+        }
+    }
+}
+
+// A target's client and health
+struct Session<'a, R: Reader, W: Writer, D: AsyncDelay> {
+    client: AsyncRpcClient<'a, R, W, D>,
+    health: TargetHealth,
+}
+
+/// Manages RPC sessions with multiple Targets, keyed by a target ID.
+///
+/// Each target has its own [`Reader`]/[`Writer`] pair, for example one per
+/// SWD bus, and [`RpcClientConfig`].  The manager creates a client for each
+/// target when it is added, and keeps it, along with the target's
+/// [`TargetHealth`], until the target is removed.
+///
+/// Where targets share a debug interface, for example multidrop SWD, use
+/// `SharedIo` handles, with the `shared` feature, as the readers and writers.
+///
+/// Requests can be sent to one target, with [`Self::request()`], or fanned
+/// out to all targets concurrently, with [`Self::request_all()`] and
+/// [`Self::fan_out()`].  Every request made through the manager updates the
+/// target's health.
+///
+/// Any `Reader` and `Writer` can be used, so simulated targets, for example
+/// backed by host memory, can stand in for real ones under test.
+///
+/// ```rust,ignore
+/// use airfrog_rpc::client::{RpcClientConfig, SessionManager};
+///
+/// let mut sessions = SessionManager::<_, _, _, Delay>::new();
+/// sessions.add_target(1, &mut reader_a, &mut writer_a, config_a);
+/// sessions.add_target(2, &mut reader_b, &mut writer_b, config_b);
+///
+/// for (id, result) in sessions.request_all(&[GET_STATUS]).await {
+///     // Process response...
+/// }
+/// let unhealthy = sessions.unhealthy().collect::<Vec<_>>();
+/// ```
+pub struct SessionManager<'a, K: Ord + Clone, R: Reader, W: Writer, D: AsyncDelay> {
+    sessions: BTreeMap<K, Session<'a, R, W, D>>,
+    failure_threshold: u32,
+}
+
+impl<'a, K: Ord + Clone, R: Reader, W: Writer, D: AsyncDelay> SessionManager<'a, K, R, W, D> {
+    /// Create a new SessionManager, with no targets
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            sessions: BTreeMap::new(),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+        }
+    }
+
+    /// Set the number of consecutive failures after which a target is
+    /// considered unhealthy.  Values below 1 are treated as 1.
+    pub fn with_failure_threshold(mut self, failures: u32) -> Self {
+        self.failure_threshold = failures.max(1);
+        self
+    }
+
+    /// Add a target, creating its client.  Replaces any existing target with
+    /// the same ID, resetting its health.
+    ///
+    /// Arguments:
+    /// - `id`: Target ID
+    /// - `reader`: Reader object to read from the target
+    /// - `writer`: Writer object to write to the target
+    /// - `config`: Configuration for creating the target's client
+    pub fn add_target(
+        &mut self,
+        id: K,
+        reader: &'a mut R,
+        writer: &'a mut W,
+        config: RpcClientConfig,
+    ) {
+        let session = Session {
+            client: AsyncRpcClient::new(reader, writer, config),
+            health: TargetHealth::default(),
+        };
+        if self.sessions.insert(id, session).is_some() {
+            debug!("Replaced existing target session");
+        }
+    }
+
+    /// Remove a target, returning whether it existed
+    pub fn remove_target(&mut self, id: &K) -> bool {
+        self.sessions.remove(id).is_some()
+    }
+
+    /// IDs of all targets, in order
+    pub fn targets(&self) -> impl Iterator<Item = &K> {
+        self.sessions.keys()
+    }
+
+    /// Number of targets
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Whether there are no targets
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Get a target's client, to use directly.  Requests made this way do
+    /// not update the target's health.
+    pub fn client(&mut self, id: &K) -> Option<&mut AsyncRpcClient<'a, R, W, D>> {
+        self.sessions.get_mut(id).map(|session| &mut session.client)
+    }
+
+    /// Get a target's health
+    pub fn health(&self, id: &K) -> Option<&TargetHealth> {
+        self.sessions.get(id).map(|session| &session.health)
+    }
+
+    /// Whether a target exists and is healthy - that is, has had fewer
+    /// consecutive failures than the failure threshold
+    pub fn is_healthy(&self, id: &K) -> bool {
+        self.health(id)
+            .is_some_and(|health| health.consecutive_failures < self.failure_threshold)
+    }
+
+    /// IDs of targets which are not healthy
+    pub fn unhealthy(&self) -> impl Iterator<Item = &K> {
+        self.sessions
+            .iter()
+            .filter(|(_, session)| session.health.consecutive_failures >= self.failure_threshold)
+            .map(|(id, _)| id)
+    }
+
+    /// Reset a target's health, for example after it has been power cycled
+    pub fn reset_health(&mut self, id: &K) {
+        if let Some(session) = self.sessions.get_mut(id) {
+            session.health = TargetHealth::default();
+        }
+    }
+
+    /// Perform an RPC request on one target, updating its health.
+    ///
+    /// Returns:
+    /// - `Ok(response_data)`: Response data received from the target
+    /// - `Err(Error::InvalidOperation)`: No target with this ID
+    /// - `Err(error)`: Error occurred during request
+    pub async fn request(&mut self, id: &K, command: &[u8]) -> Result<Vec<u8>> {
+        let session = self.sessions.get_mut(id).ok_or(Error::InvalidOperation)?;
+        let result = session.client.request(command).await;
+        session.health.record(&result);
+        result
+    }
+
+    /// Perform the same RPC request on all targets concurrently, updating
+    /// their health.
+    ///
+    /// Returns each target's ID and result, in target ID order.
+    pub async fn request_all(&mut self, command: &[u8]) -> Vec<(K, Result<Vec<u8>>)> {
+        self.fan_out(async |client: &mut AsyncRpcClient<'a, R, W, D>| client.request(command).await)
+            .await
+    }
+
+    /// Run an operation on all targets' clients concurrently, updating their
+    /// health.  For example, to call a command on each target, or use
+    /// the system service.
+    ///
+    /// ```rust,ignore
+    /// let versions = sessions
+    ///     .fan_out(async |client| client.firmware_version().await)
+    ///     .await;
+    /// ```
+    ///
+    /// Returns each target's ID and result, in target ID order.
+    pub async fn fan_out<T, F>(&mut self, f: F) -> Vec<(K, Result<T>)>
+    where
+        F: AsyncFn(&mut AsyncRpcClient<'a, R, W, D>) -> Result<T>,
+    {
+        let f = &f;
+        let mut futures = self
+            .sessions
+            .values_mut()
+            .map(|session| Box::pin(f(&mut session.client)))
+            .collect::<Vec<_>>();
+        let results = join_all(&mut futures).await;
+        drop(futures);
+
+        self.sessions
+            .iter_mut()
+            .zip(results)
+            .map(|((id, session), result)| {
+                session.health.record(&result);
+                (id.clone(), result)
+            })
+            .collect()
+    }
+}
+
+// Polls all the futures concurrently until complete, returning their outputs
+// in order
+async fn join_all<F: Future + Unpin>(futures: &mut [F]) -> Vec<F::Output> {
+    let mut outputs = futures.iter().map(|_| None).collect::<Vec<_>>();
+    poll_fn(|cx| {
+        let mut done = true;
+        for (future, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            if output.is_none() {
+                match Pin::new(future).poll(cx) {
+                    Poll::Ready(value) => *output = Some(value),
+                    Poll::Pending => done = false,
+                }
+            }
+        }
+        if done { Poll::Ready(()) } else { Poll::Pending }
+    })
+    .await;
+    outputs.into_iter().flatten().collect()
+}
@@ -0,0 +1,377 @@
+//! Shared access to a Target's debug interface from multiple async tasks -
+//! typically used by a Host.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use alloc::vec::Vec;
+use core::cell::{RefCell, UnsafeCell};
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+use critical_section::Mutex;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::client::{AsyncDelay, AsyncRpcClient};
+use crate::io::{Reader, Writer};
+
+/// A [`Reader`]/[`Writer`] pair, such as a debug probe, shared between
+/// multiple async tasks.
+///
+/// Each task gets its own [`SharedIoHandle`] from [`Self::handle()`], and
+/// uses the [`SharedReader`] and [`SharedWriter`] it provides wherever a
+/// `Reader` and `Writer` are needed - for example to create an
+/// [`crate::client::AsyncRpcClient`].
+///
+/// Every read and write takes the lock for its own duration.  A whole
+/// request is made atomic by sending it with [`SharedIoHandle::request()`]
+/// or [`SharedIoHandle::call()`], which hold the lock until the response
+/// has been received.  To keep any other sequence atomic, the task holds the
+/// lock using [`SharedIoHandle::lock()`].  Reads and writes from other
+/// handles wait until the lock is released.
+///
+/// Tasks waiting for the lock, or for the reader or writer, sleep until it
+/// is released, when they are woken.
+///
+/// ```rust,ignore
+/// use airfrog_rpc::client::{AsyncRpcClient, SharedIo};
+///
+/// static PROBE: StaticCell<SharedIo<SwdReader, SwdWriter>> = StaticCell::new();
+/// let probe = PROBE.init(SharedIo::new(swd_reader, swd_writer));
+///
+/// // In each task
+/// let handle = probe.handle();
+/// let (mut reader, mut writer) = (handle.reader(), handle.writer());
+/// let mut client = AsyncRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, config);
+/// loop {
+///     let response = handle.request(&mut client, &[GET_TELEMETRY]).await?;
+///     // Process response...
+/// }
+/// ```
+pub struct SharedIo<R: Reader, W: Writer> {
+    state: Mutex<RefCell<LockState>>,
+    reader: UnsafeCell<R>,
+    writer: UnsafeCell<W>,
+}
+
+// SAFETY: The reader and writer are only accessed through an Access, of
+// which there is at most one for each at a time, enforced by the state
+// under a critical section.
+unsafe impl<R: Reader + Send, W: Writer + Send> Sync for SharedIo<R, W> {}
+
+#[derive(Debug)]
+struct LockState {
+    // Handle holding the lock, and the number of times it has taken it
+    owner: Option<(u32, usize)>,
+    next_handle: u32,
+    // Whether the reader or writer is in use, indexed by Side
+    busy: [bool; 2],
+    // Base addresses to apply before next using the reader or writer
+    base: [Option<u32>; 2],
+    // Tasks waiting for the lock, or the reader or writer, to be released
+    waiters: Vec<Waker>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Side {
+    Reader = 0,
+    Writer = 1,
+}
+
+impl<R: Reader, W: Writer> SharedIo<R, W> {
+    /// Create a new SharedIo
+    ///
+    /// Arguments:
+    /// - `reader`: Reader object to read from target
+    /// - `writer`: Writer object to write to target
+    pub const fn new(reader: R, writer: W) -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(LockState {
+                owner: None,
+                next_handle: 0,
+                busy: [false; 2],
+                base: [None; 2],
+                waiters: Vec::new(),
+            })),
+            reader: UnsafeCell::new(reader),
+            writer: UnsafeCell::new(writer),
+        }
+    }
+
+    /// Get a new handle, for use by a single task
+    pub fn handle(&self) -> SharedIoHandle<'_, R, W> {
+        let id = self.update(|state| {
+            let id = state.next_handle;
+            state.next_handle = state.next_handle.wrapping_add(1);
+            id
+        });
+        SharedIoHandle { shared: self, id }
+    }
+
+    /// Whether any handle currently holds the lock
+    pub fn is_locked(&self) -> bool {
+        self.update(|state| state.owner.is_some())
+    }
+
+    /// Consume the SharedIo, returning the reader and writer.  Any base
+    /// address updates not yet applied are lost.
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader.into_inner(), self.writer.into_inner())
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut LockState) -> T) -> T {
+        critical_section::with(|cs| f(&mut self.state.borrow_ref_mut(cs)))
+    }
+
+    // Takes the lock for handle `id`, if free or already held by it
+    fn try_acquire(&self, id: u32) -> bool {
+        self.update(|state| state.acquire(id))
+    }
+
+    fn release(&self, id: u32) {
+        let released = self.update(|state| match state.owner {
+            Some((owner, count)) if owner == id => {
+                state.owner = (count > 1).then_some((owner, count - 1));
+                state.owner.is_none()
+            }
+            _ => {
+                warn!("SharedIo lock released by handle {id} which does not hold it");
+                false
+            }
+        });
+        if released {
+            self.wake_waiters();
+        }
+    }
+
+    // Wakes every waiting task, each of which checks whether it can now
+    // proceed.  The wakers are woken outside the critical section.
+    fn wake_waiters(&self) {
+        let waiters = self.update(|state| core::mem::take(&mut state.waiters));
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+
+    // Waits until `f` returns a value.  `f` is checked, and the task
+    // registered to be woken, in the same critical section, so a release
+    // between the two can't be missed.
+    async fn wait_for<T>(&self, mut f: impl FnMut(&mut LockState) -> Option<T>) -> T {
+        poll_fn(|cx| {
+            self.update(|state| match f(state) {
+                Some(value) => Poll::Ready(value),
+                None => {
+                    if !state.waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                        state.waiters.push(cx.waker().clone());
+                    }
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+
+    // Waits for the lock, and exclusive use of the reader or writer,
+    // returning any base address update to apply first
+    async fn access(&self, id: u32, side: Side) -> (Access<'_, R, W>, Option<u32>) {
+        let base = self
+            .wait_for(|state| {
+                if state.busy[side as usize] || !state.acquire(id) {
+                    return None;
+                }
+                state.busy[side as usize] = true;
+                Some(state.base[side as usize].take())
+            })
+            .await;
+        let access = Access {
+            shared: self,
+            id,
+            side,
+        };
+        (access, base)
+    }
+}
+
+impl LockState {
+    fn acquire(&mut self, id: u32) -> bool {
+        match self.owner {
+            None => self.owner = Some((id, 1)),
+            Some((owner, count)) if owner == id => self.owner = Some((owner, count + 1)),
+            Some(_) => return false,
+        }
+        true
+    }
+}
+
+/// A task's handle to a [`SharedIo`].
+///
+/// The reader and writer it provides share the handle's identity, so they
+/// can use the target while the handle holds the lock.
+pub struct SharedIoHandle<'s, R: Reader, W: Writer> {
+    shared: &'s SharedIo<R, W>,
+    id: u32,
+}
+
+impl<'s, R: Reader, W: Writer> SharedIoHandle<'s, R, W> {
+    /// Get a reader using this handle
+    pub fn reader(&self) -> SharedReader<'s, R, W> {
+        SharedReader {
+            shared: self.shared,
+            id: self.id,
+        }
+    }
+
+    /// Get a writer using this handle
+    pub fn writer(&self) -> SharedWriter<'s, R, W> {
+        SharedWriter {
+            shared: self.shared,
+            id: self.id,
+        }
+    }
+
+    /// Wait for the lock, and hold it until the returned guard is dropped.
+    /// Only this handle's reader and writer can use the target meanwhile.
+    ///
+    /// The lock may be taken again by the same handle, while already held.
+    pub async fn lock(&self) -> SharedIoLock<'s, R, W> {
+        let id = self.id;
+        self.shared
+            .wait_for(|state| state.acquire(id).then_some(()))
+            .await;
+        SharedIoLock {
+            shared: self.shared,
+            id,
+        }
+    }
+
+    /// Take the lock if it is available, without waiting
+    pub fn try_lock(&self) -> Option<SharedIoLock<'s, R, W>> {
+        self.shared.try_acquire(self.id).then_some(SharedIoLock {
+            shared: self.shared,
+            id: self.id,
+        })
+    }
+}
+
+impl<'s, R: Reader + Send, W: Writer + Send> SharedIoHandle<'s, R, W> {
+    /// Perform [`AsyncRpcClient::request()`] atomically, holding the lock
+    /// until the response has been received.
+    ///
+    /// Arguments:
+    /// - `client` - Client using this handle's reader and writer
+    /// - `command` - Command data to send to target
+    pub async fn request<D: AsyncDelay>(
+        &self,
+        client: &mut AsyncRpcClient<'_, SharedReader<'s, R, W>, SharedWriter<'s, R, W>, D>,
+        command: &[u8],
+    ) -> crate::Result<Vec<u8>> {
+        let _lock = self.lock().await;
+        client.request(command).await
+    }
+
+    /// Perform [`AsyncRpcClient::call()`] atomically, holding the lock
+    /// until the response has been received.
+    ///
+    /// Arguments:
+    /// - `client` - Client using this handle's reader and writer
+    /// - `command_id` - ID of the command to call
+    /// - `payload` - Command payload
+    pub async fn call<D: AsyncDelay>(
+        &self,
+        client: &mut AsyncRpcClient<'_, SharedReader<'s, R, W>, SharedWriter<'s, R, W>, D>,
+        command_id: u8,
+        payload: &[u8],
+    ) -> crate::Result<Vec<u8>> {
+        let _lock = self.lock().await;
+        client.call(command_id, payload).await
+    }
+}
+
+/// Lock on a [`SharedIo`], held by a [`SharedIoHandle`].  Released when
+/// dropped.
+pub struct SharedIoLock<'s, R: Reader, W: Writer> {
+    shared: &'s SharedIo<R, W>,
+    id: u32,
+}
+
+impl<R: Reader, W: Writer> Drop for SharedIoLock<'_, R, W> {
+    fn drop(&mut self) {
+        self.shared.release(self.id);
+    }
+}
+
+/// [`Reader`] using a [`SharedIo`], obtained from
+/// [`SharedIoHandle::reader()`]
+pub struct SharedReader<'s, R: Reader, W: Writer> {
+    shared: &'s SharedIo<R, W>,
+    id: u32,
+}
+
+impl<R: Reader + Send, W: Writer + Send> Reader for SharedReader<'_, R, W> {
+    type Error = R::Error;
+
+    async fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), Self::Error> {
+        let (_access, base) = self.shared.access(self.id, Side::Reader).await;
+        // SAFETY: _access gives exclusive use of the reader until dropped
+        let reader = unsafe { &mut *self.shared.reader.get() };
+        if let Some(base) = base {
+            reader.update_base_address(base);
+        }
+        reader.read(addr, buf).await
+    }
+
+    /// Updates the shared reader's base address, for all handles, before it
+    /// is next used
+    fn update_base_address(&mut self, new_base: u32) {
+        self.shared
+            .update(|state| state.base[Side::Reader as usize] = Some(new_base));
+    }
+}
+
+/// [`Writer`] using a [`SharedIo`], obtained from
+/// [`SharedIoHandle::writer()`]
+pub struct SharedWriter<'s, R: Reader, W: Writer> {
+    shared: &'s SharedIo<R, W>,
+    id: u32,
+}
+
+impl<R: Reader + Send, W: Writer + Send> Writer for SharedWriter<'_, R, W> {
+    type Error = W::Error;
+
+    async fn write(&mut self, addr: u32, data: &[u8]) -> Result<(), Self::Error> {
+        let (_access, base) = self.shared.access(self.id, Side::Writer).await;
+        // SAFETY: _access gives exclusive use of the writer until dropped
+        let writer = unsafe { &mut *self.shared.writer.get() };
+        if let Some(base) = base {
+            writer.update_base_address(base);
+        }
+        writer.write(addr, data).await
+    }
+
+    /// Updates the shared writer's base address, for all handles, before it
+    /// is next used
+    fn update_base_address(&mut self, new_base: u32) {
+        self.shared
+            .update(|state| state.base[Side::Writer as usize] = Some(new_base));
+    }
+}
+
+// Exclusive use of the reader or writer, holding the lock.  Released when
+// dropped, including if the read or write future is dropped.
+struct Access<'s, R: Reader, W: Writer> {
+    shared: &'s SharedIo<R, W>,
+    id: u32,
+    side: Side,
+}
+
+impl<R: Reader, W: Writer> Drop for Access<'_, R, W> {
+    fn drop(&mut self) {
+        self.shared
+            .update(|state| state.busy[self.side as usize] = false);
+        self.shared.release(self.id);
+        // Other futures of the same handle may be waiting for this side,
+        // even if the handle still holds the lock
+        self.shared.wake_waiters();
+    }
+}
@@ -0,0 +1,138 @@
+//! System service requests for the asynchronous Client - typically used by a
+//! Host.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::client::{AsyncDelay, AsyncRpcClient};
+use crate::io::{Reader, Writer};
+use crate::server::CommandInfo;
+use crate::system::{self, MemoryInfo, ServerLimits, VersionInfo};
+use crate::{Error, Result};
+
+/// Firmware version and build information, as returned by
+/// [`AsyncRpcClient::firmware_version()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirmwareVersion {
+    /// Firmware version
+    pub version: String,
+    /// Build information
+    pub build: String,
+}
+
+/// Description of one of the Target's commands, as returned by
+/// [`AsyncRpcClient::describe()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandDescription {
+    /// Command ID, for use with [`AsyncRpcClient::call()`]
+    pub id: u8,
+    /// Command version
+    pub version: u8,
+    /// Command name
+    pub name: String,
+    /// Description of the command payload, empty if not provided
+    pub request_schema: String,
+    /// Description of the response payload, empty if not provided
+    pub response_schema: String,
+}
+
+impl From<CommandInfo<'_>> for CommandDescription {
+    fn from(info: CommandInfo<'_>) -> Self {
+        Self {
+            id: info.id,
+            version: info.version,
+            name: info.name.to_string(),
+            request_schema: info.request_schema.to_string(),
+            response_schema: info.response_schema.to_string(),
+        }
+    }
+}
+
+impl<R: Reader, W: Writer, D: AsyncDelay> AsyncRpcClient<'_, R, W, D> {
+    /// Check the Target's system service is responding, by sending `payload`
+    /// and checking it is echoed back.
+    ///
+    /// Returns [`Error::Malformed`] if the echoed payload differs.
+    pub async fn ping(&mut self, payload: &[u8]) -> Result<()> {
+        let response = self.call(system::PING, payload).await?;
+        if response != payload {
+            warn!("Ping response doesn't match");
+            return Err(Error::Malformed);
+        }
+        Ok(())
+    }
+
+    /// Get the Target's firmware version and build information
+    pub async fn firmware_version(&mut self) -> Result<FirmwareVersion> {
+        let response = self.call(system::VERSION, &[]).await?;
+        let info = VersionInfo::decode(&response).ok_or(Error::Malformed)?;
+        Ok(FirmwareVersion {
+            version: info.version.to_string(),
+            build: info.build.to_string(),
+        })
+    }
+
+    /// Get the Target's time since boot, in milliseconds
+    pub async fn uptime_ms(&mut self) -> Result<u64> {
+        let response = self.call(system::UPTIME, &[]).await?;
+        system::decode_u64(&response).ok_or(Error::Malformed)
+    }
+
+    /// Get the Target's free RAM and stack high-water mark
+    pub async fn memory_info(&mut self) -> Result<MemoryInfo> {
+        let response = self.call(system::MEMORY, &[]).await?;
+        MemoryInfo::decode(&response).ok_or(Error::Malformed)
+    }
+
+    /// Ask the Target to reset.  The Target resets after responding, so
+    /// channels must be re-established afterwards.
+    pub async fn request_reset(&mut self) -> Result<()> {
+        self.call(system::RESET, &[]).await.map(|_| ())
+    }
+
+    /// Set the Target's wall-clock time
+    ///
+    /// Arguments:
+    /// - `unix_ms` - Milliseconds since the Unix epoch
+    pub async fn set_time(&mut self, unix_ms: u64) -> Result<()> {
+        self.call(system::SET_TIME, &unix_ms.to_le_bytes())
+            .await
+            .map(|_| ())
+    }
+
+    /// Get the largest command and response payloads the Target's
+    /// [`crate::server::RpcServer`] can handle.  Larger commands are
+    /// rejected with [`crate::server::Status::CommandTooLarge`].
+    pub async fn server_limits(&mut self) -> Result<ServerLimits> {
+        let response = self.call(system::LIMITS, &[]).await?;
+        ServerLimits::decode(&response).ok_or(Error::Malformed)
+    }
+
+    /// Fetch the catalogue of commands the Target's
+    /// [`crate::server::RpcServer`] supports, one command per request.
+    ///
+    /// Returns [`Error::Malformed`] if a description could not be parsed.
+    pub async fn describe(&mut self) -> Result<Vec<CommandDescription>> {
+        let mut commands = Vec::new();
+        let mut total = 1;
+        while commands.len() < total {
+            let index = commands.len() as u16;
+            let response = self.call(system::DESCRIBE, &index.to_le_bytes()).await?;
+            let (count, info) = response.split_at_checked(2).ok_or(Error::Malformed)?;
+            total = u16::from_le_bytes([count[0], count[1]]) as usize;
+            if info.is_empty() {
+                // Index out of range - the Target's commands have changed
+                break;
+            }
+            let info = CommandInfo::decode(info).ok_or(Error::Malformed)?;
+            commands.push(info.into());
+        }
+        Ok(commands)
+    }
+}
@@ -0,0 +1,94 @@
+//! Typed requests for the asynchronous Client - typically used by a Host.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use alloc::vec;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::Result;
+use crate::client::{AsyncDelay, AsyncRpcClient};
+use crate::codec::Codec;
+#[cfg(any(feature = "postcard", feature = "cbor"))]
+use crate::codec::DefaultCodec;
+use crate::io::{Reader, Writer};
+
+impl<R: Reader, W: Writer, D: AsyncDelay> AsyncRpcClient<'_, R, W, D> {
+    /// Perform an RPC request with a typed command and response, encoded
+    /// using [`DefaultCodec`].
+    ///
+    /// The command is encoded without any framing - the Target decodes it
+    /// using, for example, [`crate::channel::Channel::consume_typed()`].
+    ///
+    /// Returns [`crate::Error::BufferTooSmall`] if the encoded command
+    /// doesn't fit in the command channel, or [`crate::Error::Codec`] if the
+    /// command could not be encoded or the response could not be decoded.
+    #[cfg(any(feature = "postcard", feature = "cbor"))]
+    pub async fn request_typed<Req, Rsp>(&mut self, request: &Req) -> Result<Rsp>
+    where
+        Req: Serialize + ?Sized,
+        Rsp: DeserializeOwned,
+    {
+        self.request_typed_with::<DefaultCodec, Req, Rsp>(request)
+            .await
+    }
+
+    /// As [`Self::request_typed()`], using codec `C`.
+    pub async fn request_typed_with<C, Req, Rsp>(&mut self, request: &Req) -> Result<Rsp>
+    where
+        C: Codec,
+        Req: Serialize + ?Sized,
+        Rsp: DeserializeOwned,
+    {
+        let mut command = vec![0u8; self.cmd_capacity().await?];
+        let len = C::encode(request, &mut command)?;
+
+        let response = self.request(&command[..len]).await?;
+        C::decode(&response)
+    }
+
+    /// Call a command on a [`crate::server::RpcServer`] with a typed command
+    /// and response, encoded using [`DefaultCodec`].
+    ///
+    /// The Target's handler decodes the command using
+    /// [`crate::server::Request::decode()`], and encodes the response using
+    /// [`crate::server::encode_response()`].
+    ///
+    /// Returns [`crate::Error::Status`] if the server returned a non-OK
+    /// status, [`crate::Error::BufferTooSmall`] if the encoded command doesn't
+    /// fit in the command channel, and [`crate::Error::Codec`] if encoding or
+    /// decoding failed.
+    #[cfg(any(feature = "postcard", feature = "cbor"))]
+    pub async fn call_typed<Req, Rsp>(&mut self, command_id: u8, request: &Req) -> Result<Rsp>
+    where
+        Req: Serialize + ?Sized,
+        Rsp: DeserializeOwned,
+    {
+        self.call_typed_with::<DefaultCodec, Req, Rsp>(command_id, request)
+            .await
+    }
+
+    /// As [`Self::call_typed()`], using codec `C`.
+    pub async fn call_typed_with<C, Req, Rsp>(
+        &mut self,
+        command_id: u8,
+        request: &Req,
+    ) -> Result<Rsp>
+    where
+        C: Codec,
+        Req: Serialize + ?Sized,
+        Rsp: DeserializeOwned,
+    {
+        // Leave room for the command ID
+        let capacity = self.cmd_capacity().await?;
+        let mut payload = vec![0u8; capacity.saturating_sub(1)];
+        let len = C::encode(request, &mut payload)?;
+
+        let response = self.call(command_id, &payload[..len]).await?;
+        C::decode(&response)
+    }
+}
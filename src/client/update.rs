@@ -0,0 +1,123 @@
+//! Firmware update driver for the asynchronous Client - typically used by a
+//! Host.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use alloc::vec::Vec;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::client::{AsyncDelay, AsyncRpcClient};
+use crate::io::{Reader, Writer};
+use crate::update::{self, UpdateState, UpdateStatus};
+use crate::{Error, Result};
+
+impl<R: Reader, W: Writer, D: AsyncDelay> AsyncRpcClient<'_, R, W, D> {
+    /// Update the Target's firmware, using its
+    /// [firmware update service](crate::update).
+    ///
+    /// Transfers are resumable - if this fails part way through, for example
+    /// due to a communication error, calling it again with the same image
+    /// and digest continues from where the Target got to.  The Target holds
+    /// the transfer's progress in RAM, so if it has reset in the meantime,
+    /// the transfer starts again.
+    ///
+    /// The image is sent in the largest chunks the Target reports it can
+    /// receive.
+    ///
+    /// Arguments:
+    /// - `image` - The new firmware image
+    /// - `digest` - Expected digest or signature of the image, as checked by
+    ///   the Target's [`crate::update::ImageVerifier`]
+    /// - `progress` - Called after each chunk is written
+    ///
+    /// Returns:
+    /// - `Ok(())`: Image verified and marked bootable.  The Target boots it
+    ///   after its next reset.
+    /// - `Err(Error::UpdateFailed)`: Image failed verification
+    /// - `Err(Error::Status(status))`: Target rejected a command
+    /// - `Err(error)`: Other error occurred
+    pub async fn update_firmware(
+        &mut self,
+        image: &[u8],
+        digest: &[u8],
+        mut progress: impl FnMut(&UpdateStatus),
+    ) -> Result<()> {
+        let image_size = u32::try_from(image.len()).map_err(|_| Error::PayloadTooLarge)?;
+
+        let mut payload = Vec::with_capacity(4 + digest.len());
+        payload.extend_from_slice(&image_size.to_le_bytes());
+        payload.extend_from_slice(digest);
+        let response = self.call(update::UPDATE_BEGIN, &payload).await?;
+        let mut status = UpdateStatus::decode(&response).ok_or(Error::Malformed)?;
+        let max_chunk = response
+            .get(UpdateStatus::SIZE..UpdateStatus::SIZE + 4)
+            .ok_or(Error::Malformed)?;
+        let max_chunk = u32::from_le_bytes(max_chunk.try_into().map_err(|_| Error::Malformed)?);
+        if status.written > 0 {
+            info!(
+                "Resuming update at {} of {image_size} bytes",
+                status.written
+            );
+        }
+
+        // Largest chunk the Target can receive, which also fits in the
+        // command channel after the command ID and offset, in whole flash
+        // writes
+        let write_size = (status.write_size as usize).max(1);
+        let capacity = self
+            .cmd_capacity()
+            .await?
+            .saturating_sub(5)
+            .min(max_chunk as usize);
+        let chunk_size = capacity - capacity % write_size;
+        if chunk_size == 0 {
+            return Err(Error::BufferTooSmall);
+        }
+
+        while status.written < image_size {
+            let offset = status.written;
+            let start = offset as usize;
+            let end = (start + chunk_size).min(image.len());
+
+            // Pad the final chunk to a whole flash write
+            payload.clear();
+            payload.extend_from_slice(&offset.to_le_bytes());
+            payload.extend_from_slice(&image[start..end]);
+            payload.resize(4 + (end - start).next_multiple_of(write_size), 0xFF);
+
+            status = self.update_command(update::UPDATE_WRITE, &payload).await?;
+            if status.state != UpdateState::Receiving || status.written <= offset {
+                warn!("Update write at {offset} not accepted: {status:?}");
+                return Err(Error::SequenceMismatch);
+            }
+            progress(&status);
+        }
+
+        let status = self.update_command(update::UPDATE_FINISH, &[]).await?;
+        match status.state {
+            UpdateState::Complete => Ok(()),
+            _ => {
+                warn!("Update failed: {status:?}");
+                Err(Error::UpdateFailed)
+            }
+        }
+    }
+
+    /// Get the progress of the Target's firmware update
+    pub async fn update_status(&mut self) -> Result<UpdateStatus> {
+        self.update_command(update::UPDATE_STATUS, &[]).await
+    }
+
+    /// Abandon the Target's firmware update
+    pub async fn update_abort(&mut self) -> Result<UpdateStatus> {
+        self.update_command(update::UPDATE_ABORT, &[]).await
+    }
+
+    async fn update_command(&mut self, command_id: u8, payload: &[u8]) -> Result<UpdateStatus> {
+        let response = self.call(command_id, payload).await?;
+        UpdateStatus::decode(&response).ok_or(Error::Malformed)
+    }
+}
@@ -0,0 +1,112 @@
+//! Codecs for typed requests and responses.
+//!
+//! The [`Codec`] trait serializes and deserializes `serde` types to and from
+//! byte buffers, so it can be used on both the Host and the Target.
+//!
+//! Implementations, each behind a feature:
+//! - `postcard` - [`PostcardCodec`], compact and the default, which doesn't
+//!   allocate
+//! - `cbor` - [`CborCodec`], self-describing, which allocates when decoding
+//!
+//! [`DefaultCodec`] is [`PostcardCodec`] if the `postcard` feature is
+//! enabled, otherwise [`CborCodec`].
+//!
+//! Typed APIs built on this trait:
+//! - Host: [`crate::client::AsyncRpcClient::request_typed()`] and
+//!   [`crate::client::AsyncRpcClient::call_typed()`]
+//! - Target: [`crate::channel::Channel::consume_typed()`],
+//!   [`crate::channel::Channel::publish_typed()`],
+//!   [`crate::server::Request::decode()`] and
+//!   [`crate::server::encode_response()`]
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use serde::{Deserialize, Serialize};
+
+#[allow(unused_imports)]
+use crate::{Error, Result};
+
+/// Serializes and deserializes values to and from byte buffers.
+pub trait Codec {
+    /// Encode `value` into `buf`.
+    ///
+    /// Returns:
+    /// - `Ok(len)`: Number of bytes written
+    /// - `Err(Error::BufferTooSmall)`: `buf` is too small for the value
+    /// - `Err(Error::Codec)`: Value couldn't be encoded
+    fn encode<T: Serialize + ?Sized>(value: &T, buf: &mut [u8]) -> Result<usize>;
+
+    /// Decode a value from `buf`.
+    ///
+    /// Values should be owned types, as with `serde::de::DeserializeOwned`.
+    /// Only some codecs, such as `PostcardCodec`, can decode values which
+    /// borrow from `buf`, so code generic over the codec must not rely on it.
+    ///
+    /// Returns [`Error::Codec`] if the value couldn't be decoded.
+    fn decode<'de, T: Deserialize<'de>>(buf: &'de [u8]) -> Result<T>;
+}
+
+/// Codec using [postcard](https://docs.rs/postcard).
+#[cfg(feature = "postcard")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostcardCodec;
+
+#[cfg(feature = "postcard")]
+impl Codec for PostcardCodec {
+    fn encode<T: Serialize + ?Sized>(value: &T, buf: &mut [u8]) -> Result<usize> {
+        postcard::to_slice(value, buf)
+            .map(|used| used.len())
+            .map_err(|e| match e {
+                postcard::Error::SerializeBufferFull => Error::BufferTooSmall,
+                _ => Error::Codec,
+            })
+    }
+
+    fn decode<'de, T: Deserialize<'de>>(buf: &'de [u8]) -> Result<T> {
+        postcard::from_bytes(buf).map_err(|_| Error::Codec)
+    }
+}
+
+/// Codec using CBOR, via [ciborium](https://docs.rs/ciborium).
+///
+/// Decoded values can't borrow from the buffer, so must use owned types, for
+/// example `String` rather than `&str`.  Decoding allocates.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborCodec;
+
+#[cfg(feature = "cbor")]
+impl Codec for CborCodec {
+    fn encode<T: Serialize + ?Sized>(value: &T, buf: &mut [u8]) -> Result<usize> {
+        // Writing to a slice only fails when it is full
+        let size = buf.len();
+        let mut remaining = buf;
+        ciborium::into_writer(value, &mut remaining).map_err(|e| match e {
+            ciborium::ser::Error::Io(_) => Error::BufferTooSmall,
+            ciborium::ser::Error::Value(_) => Error::Codec,
+        })?;
+        Ok(size - remaining.len())
+    }
+
+    fn decode<'de, T: Deserialize<'de>>(buf: &'de [u8]) -> Result<T> {
+        // ciborium only deserializes owned types directly, so decode via a
+        // Value.  The scratch buffer only needs to hold short strings - longer
+        // ones are read in chunks.
+        let mut scratch = [0u8; 64];
+        let value: ciborium::Value =
+            ciborium::de::from_reader_with_buffer(buf, &mut scratch).map_err(|_| Error::Codec)?;
+        value.deserialized().map_err(|_| Error::Codec)
+    }
+}
+
+/// The default codec - [`PostcardCodec`] if the `postcard` feature is
+/// enabled.
+#[cfg(feature = "postcard")]
+pub type DefaultCodec = PostcardCodec;
+
+/// The default codec - [`CborCodec`] as the `postcard` feature is not
+/// enabled.
+#[cfg(all(feature = "cbor", not(feature = "postcard")))]
+pub type DefaultCodec = CborCodec;
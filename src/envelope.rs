@@ -0,0 +1,150 @@
+//! Optional standard envelope for command and response payloads.
+//!
+//! By default payloads are raw bytes, and it is up to the application to
+//! associate a response with the command that caused it.  The envelope adds a
+//! small header to each payload, carrying a request ID, message type, flags
+//! and payload length.  The request ID in a response is echoed from the
+//! command, allowing the Host to verify that the response matches.
+//!
+//! - On the Host, use [`crate::client::AsyncRpcClient::request_enveloped()`],
+//!   which generates request IDs and verifies them in responses, or
+//!   [`crate::client::PipelinedRpcClient`].
+//! - On the Target, use [`crate::channel::Channel::consume_envelope()`] and
+//!   [`crate::channel::Channel::publish_response()`], which echoes the IDs,
+//!   or [`crate::channel::CommandQueue`].
+//!
+//! Applications which don't use the envelope continue to use the raw
+//! `request()`/`publish_bytes()`/`consume_bytes()` APIs.
+//!
+//! # Framing
+//!
+//! There are three ways of framing commands and responses on the command
+//! and response channels, and both sides must use the same one:
+//!
+//! - Raw - the application's own format.  The Host uses
+//!   [`crate::client::AsyncRpcClient::request()`], and the Target
+//!   `consume_bytes()` and `publish_bytes()`.
+//! - Envelope - this module's header, using the APIs above.
+//! - Server - a command ID byte, and a status byte in responses.  The Host
+//!   uses [`crate::client::AsyncRpcClient::call()`], or the typed, system or
+//!   update clients, and the Target [`crate::server::RpcServer`].
+//!
+//! [`crate::server::RpcServer`] uses its own command ID and status byte
+//! framing, described in [`crate::server`], and does not understand
+//! envelopes.  Sending it an enveloped command treats the low byte of the
+//! request ID as the command ID.
+//!
+//! Request IDs start from the command channel's producer sequence number
+//! when the client first sends a request, so they don't repeat the IDs
+//! of requests sent by earlier clients, whose responses may still arrive.
+//!
+//! # Wire format
+//!
+//! The header is [`Envelope::SIZE`] bytes, little-endian, and immediately
+//! precedes the payload:
+//!
+//! | Offset | Size | Field        |
+//! |--------|------|--------------|
+//! | 0      | 4    | `request_id` |
+//! | 4      | 1    | `msg_type`   |
+//! | 5      | 1    | `flags`      |
+//! | 6      | 2    | `length`     |
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use crate::{Error, Result};
+
+/// Envelope header, prepended to enveloped payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Envelope {
+    /// Request ID, generated by the requester and echoed in the response
+    pub request_id: u32,
+
+    /// Application-defined message type, echoed in the response
+    pub msg_type: u8,
+
+    /// Flags - see `FLAG_*` constants
+    pub flags: u8,
+
+    /// Length of the payload following the header, in bytes
+    pub length: u16,
+}
+
+impl Envelope {
+    /// Size of the encoded header in bytes.  A multiple of the word size, so
+    /// the payload remains word-aligned.
+    pub const SIZE: usize = 8;
+
+    /// Set on responses
+    pub const FLAG_RESPONSE: u8 = 0x01;
+
+    /// Set on responses indicating the request failed.  The payload is
+    /// application-defined error information.
+    pub const FLAG_ERROR: u8 = 0x02;
+
+    /// Create a new request envelope.
+    ///
+    /// Returns [`Error::PayloadTooLarge`] if `length` doesn't fit in the
+    /// header.
+    pub fn request(request_id: u32, msg_type: u8, length: usize) -> Result<Self> {
+        Ok(Self {
+            request_id,
+            msg_type,
+            flags: 0,
+            length: Self::check_length(length)?,
+        })
+    }
+
+    /// Create the envelope for a response to this request, echoing the
+    /// request ID and message type.  [`Self::FLAG_RESPONSE`] is always set.
+    pub fn response(&self, flags: u8, length: usize) -> Result<Self> {
+        Ok(Self {
+            request_id: self.request_id,
+            msg_type: self.msg_type,
+            flags: flags | Self::FLAG_RESPONSE,
+            length: Self::check_length(length)?,
+        })
+    }
+
+    /// Whether this is a response
+    pub fn is_response(&self) -> bool {
+        self.flags & Self::FLAG_RESPONSE != 0
+    }
+
+    /// Whether this is an error response
+    pub fn is_error(&self) -> bool {
+        self.flags & Self::FLAG_ERROR != 0
+    }
+
+    /// Encode the header
+    pub fn encode(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0u8; Self::SIZE];
+        buf[0..4].copy_from_slice(&self.request_id.to_le_bytes());
+        buf[4] = self.msg_type;
+        buf[5] = self.flags;
+        buf[6..8].copy_from_slice(&self.length.to_le_bytes());
+        buf
+    }
+
+    /// Decode a header from the start of `buf`.
+    ///
+    /// Returns [`Error::Malformed`] if `buf` is too short to contain a
+    /// header.
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        if buf.len() < Self::SIZE {
+            return Err(Error::Malformed);
+        }
+        Ok(Self {
+            request_id: u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]),
+            msg_type: buf[4],
+            flags: buf[5],
+            length: u16::from_le_bytes([buf[6], buf[7]]),
+        })
+    }
+
+    fn check_length(length: usize) -> Result<u16> {
+        u16::try_from(length).map_err(|_| Error::PayloadTooLarge)
+    }
+}
@@ -0,0 +1,174 @@
+//! Unsolicited events, published by the Target on a dedicated event channel,
+//! without the Host asking.  For example button presses, fault conditions and
+//! sensor thresholds.
+//!
+//! - On the Target, use an [`EventPublisher`] with the event channel, or
+//!   [`crate::channel::Channel::publish_event()`] directly.
+//! - On the Host, use [`crate::client::EventSubscriber`], which polls the
+//!   event channel and returns events as they arrive.
+//!
+//! The event channel holds a single event at a time.  If the Host has not
+//! consumed the previous event when the Target publishes another, the new
+//! event is dropped, and the drop reported to the Host with the next event
+//! which is published.
+//!
+//! # Wire format
+//!
+//! Each event is an [`EventHeader`] of [`EventHeader::SIZE`] bytes,
+//! little-endian, immediately followed by the event payload:
+//!
+//! | Offset | Size | Field        |
+//! |--------|------|--------------|
+//! | 0      | 4    | `seq`        |
+//! | 4      | 2    | `event_type` |
+//! | 6      | 2    | `dropped`    |
+//!
+//! `seq` is incremented for every event the Target generates, including
+//! dropped events, so the Host can detect events it did not see.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use core::borrow::BorrowMut;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{ChannelIo, GenericChannel};
+use crate::{Error, Result};
+
+/// Event header, prepended to event payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventHeader {
+    /// Sequence number, incremented for each event generated by the Target
+    pub seq: u32,
+
+    /// Application-defined event type
+    pub event_type: u16,
+
+    /// Number of events dropped by the Target immediately before this one,
+    /// saturating at `u16::MAX`
+    pub dropped: u16,
+}
+
+impl EventHeader {
+    /// Size of the encoded header in bytes.  A multiple of the word size, so
+    /// the payload remains word-aligned.
+    pub const SIZE: usize = 8;
+
+    /// Encode the header
+    pub fn encode(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0u8; Self::SIZE];
+        buf[0..4].copy_from_slice(&self.seq.to_le_bytes());
+        buf[4..6].copy_from_slice(&self.event_type.to_le_bytes());
+        buf[6..8].copy_from_slice(&self.dropped.to_le_bytes());
+        buf
+    }
+
+    /// Decode a header from the start of `buf`.
+    ///
+    /// Returns [`Error::Malformed`] if `buf` is too short to contain a
+    /// header.
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        if buf.len() < Self::SIZE {
+            return Err(Error::Malformed);
+        }
+        Ok(Self {
+            seq: u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]),
+            event_type: u16::from_le_bytes([buf[4], buf[5]]),
+            dropped: u16::from_le_bytes([buf[6], buf[7]]),
+        })
+    }
+}
+
+/// Publishes events on a Target's event channel, numbering them and
+/// counting those dropped because the Host has not yet consumed the previous
+/// event.
+///
+/// Does not allocate, or hold the channel, so can be used from a `static`.
+///
+/// ```rust,ignore
+/// const BUTTON_PRESSED: u16 = 1;
+///
+/// let mut events = EventPublisher::new();
+///
+/// loop {
+///     if button.pressed() {
+///         events.publish(&mut event_ch, BUTTON_PRESSED, &[button.id()])?;
+///     }
+///     // Other superloop work
+/// }
+/// ```
+#[derive(Debug)]
+pub struct EventPublisher {
+    seq: u32,
+    dropped: u16,
+    total_dropped: u32,
+}
+
+impl EventPublisher {
+    /// Create a new EventPublisher
+    // We need a new() rather than a default() as it must be const.
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self {
+            seq: 0,
+            dropped: 0,
+            total_dropped: 0,
+        }
+    }
+
+    /// Publish an event, or drop it if the event channel is busy.  Does not
+    /// block.
+    ///
+    /// Arguments:
+    /// - `event_ch` - Event channel, which must be a Producer
+    /// - `event_type` - Application-defined event type
+    /// - `payload` - Event payload
+    ///
+    /// Returns:
+    /// - `Ok(true)`: Event published
+    /// - `Ok(false)`: Event dropped, as the Host has not consumed the
+    ///   previous event
+    /// - `Err(error)`: Event could not be published, for example
+    ///   [`Error::PayloadTooLarge`].  It is not counted as dropped.
+    pub fn publish<I: ChannelIo, B: BorrowMut<I>>(
+        &mut self,
+        event_ch: &mut GenericChannel<I, B>,
+        event_type: u16,
+        payload: &[u8],
+    ) -> Result<bool> {
+        let header = EventHeader {
+            seq: self.seq,
+            event_type,
+            dropped: self.dropped,
+        };
+
+        let published = match event_ch.publish_event(&header, payload) {
+            Ok(()) => {
+                self.dropped = 0;
+                true
+            }
+            Err(Error::Busy) => {
+                trace!("Dropped event {} type {event_type}", self.seq);
+                self.dropped = self.dropped.saturating_add(1);
+                self.total_dropped = self.total_dropped.saturating_add(1);
+                false
+            }
+            Err(e) => return Err(e),
+        };
+        self.seq = self.seq.wrapping_add(1);
+
+        Ok(published)
+    }
+
+    /// Sequence number of the next event
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    /// Total number of events dropped, saturating at `u32::MAX`
+    pub fn dropped(&self) -> u32 {
+        self.total_dropped
+    }
+}
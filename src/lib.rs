@@ -43,8 +43,15 @@
 //! - [`channel`] - Channel objects for unidirectional communication in either direction
 //! - [`client`] - RPC client for sending commands and receiving responses, typically used
 //!   on the host
+//! - `codec` - Codecs for typed requests and responses, requires the `postcard` or `cbor`
+//!   feature
+//! - [`envelope`] - Optional standard envelope for payloads, carrying request IDs
+//! - [`event`] - Unsolicited events from the target to the host, on a dedicated channel
 //! - [`io`] - Async I/O traits for debug interface access, typically used for host access
 //!   to target RAM/flash peripherals
+//! - `logger` - Target logs shipped to the host over a channel
+//! - [`server`] - RPC server dispatching commands to handlers, typically used on the
+//!   target
 //!
 //! ## Supported Targets
 //!
@@ -59,6 +66,9 @@
 //! [`io::Writer`] for ESP32-C3, and uses [`channel::ReaderWriterChannel`], allowing it
 //! to communicate using these channels with an SWD target.
 //!
+//! The same channels can be used between the cores of a dual-core MCU, such as the
+//! RP2040, RP2350 or ESP32, using [`channel::AtomicChannelIo`].
+//!
 //! ## Getting Started
 //!
 //! The target must reserved dedicated SRAM regions for each channel.
@@ -76,14 +86,23 @@
 //! where the actual command or response payload is stored.
 //!
 //! **Target setup**:
-//! 1. Reserve SRAM region(s) e.g. 1KB each for each channel
+//! 1. Reserve SRAM region(s) e.g. 1KB each for each channel, or declare a
+//!    [`channel::StaticChannelRegion`] for each, sized at compile time, for example
+//!    using [`static_channel!`], which also creates the channel
 //! 2. Create a [`channel::RamChannelIo`] instance for each required channel
-//! 3. Create a [`channel::RamChannel`] instance for each channel
+//! 3. Create a [`channel::RamChannel`] instance for each channel, or a
+//!    [`channel::OwnedRamChannel`], which owns its [`channel::RamChannelIo`], to store it
+//!    in a struct or task without a `static mut`
 //! 4. Poll [`channel::RamChannel::data_available()`] for your consumer channel, in your
 //!    main loop or dedicated task
 //! 5. When data arrives, process it, and optionally send responses on alternate channel
 //! 6. Data format is application-specific and currently either bytes or u32s
 //!
+//! Alternatively, create a [`server::RpcServer`] with the command and response channels,
+//! and a table of command handlers, and call [`server::RpcServer::poll()`] from your main
+//! loop.  Mount the [`system`] service's standard housekeeping commands alongside your
+//! own with [`server::RpcServer::mount()`].
+//!
 //! **Host setup**:
 //! 1. Configure channel locations, or dynamically read from the target using well-known
 //!    locations for pointers to the channel locations/sizes
@@ -94,7 +113,8 @@
 //! 4. Send data with [`channel::ReaderWriterChannel::publish_bytes()`].
 //! 5. Before using a different channel you will likely need to ensure the previous
 //!    channel is dropped, to free up the Io instance to be mutably borrowed by your
-//!    new channel.
+//!    new channel.  Alternatively, give each channel its own reader and writer, and use
+//!    [`channel::OwnedReaderWriterChannel`] to keep it.
 //!
 //! As implied a Channel is intended to be short-lived - create, use, drop. This allows
 //! temporary ownership of the Reader/Writer, which may be a shared hardware resource on
@@ -106,6 +126,19 @@
 //! The RPC layer handles reliable delivery, but your application defines the actual
 //! command/response protocol and data formats.
 //!
+//! Long-running commands can be cancelled by the Host using
+//! [`client::AsyncRpcClient::cancel()`].  The Target should poll
+//! [`channel::Channel::cancel_requested()`] on its command channel while handling such
+//! commands, and acknowledge using [`channel::Channel::publish_cancelled()`] on its
+//! response channel.
+//!
+//! To report events such as button presses or faults without the Host asking, publish them
+//! on a dedicated event channel with an [`event::EventPublisher`], and receive them on the
+//! Host with a [`client::EventSubscriber`].
+//!
+//! To carry several independent streams, such as logs and telemetry, over one channel
+//! pair, use the [`mux`] layer.
+//!
 //! While the above documentation describes the Host controlling the Target, it is
 //! possible to use the channel(s) in the reverse direction.
 //!
@@ -117,6 +150,28 @@
 //! - `async` - Enable async channel implementations and traits (requires `alloc`), which
 //!   is generally required by the Host, but not by the Target.
 //!
+//! Optional features:
+//! - `postcard` - Typed requests and responses, using the postcard codec (the default
+//!   codec)
+//! - `cbor` - Typed requests and responses, using the CBOR codec.  Requires an
+//!   allocator.
+//! - `macros` - The `service` macro, generating Host and Target code from a single
+//!   service definition.  Requires `postcard` or `cbor`.
+//! - `update` - The `update` firmware update service, writing images to flash using
+//!   `embedded-storage`
+//! - `sha2` - SHA-256 image verification for the firmware update service
+//! - `logger` - A `log` backend for the Target, shipping log records to the Host over a
+//!   channel.  The Host collector only requires `async`.
+//! - `stream` - `futures` `Stream` and `Sink` adapters for async channels, see
+//!   `channel::stream`
+//! - `pipe` - `embedded-io` byte-stream pipes over a channel pair, see `pipe`
+//! - `defmt` - A `defmt` global logger for the Target, shipping defmt frames to the Host
+//!   over a channel.  Do not use with another defmt transport, such as `defmt-rtt`.
+//! - `shared` - Share a Host's debug interface between multiple async tasks, see
+//!   `client::shared`.  Requires a `critical-section` implementation.
+//! - `isr` - Share a Target's channel between interrupt and thread contexts, see
+//!   `channel::isr`.  Requires a `critical-section` implementation.
+//!
 //! Compile with `--no-default-features` to disable unnecessary async support for a Target.
 
 // Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
@@ -130,10 +185,34 @@ extern crate alloc;
 
 pub mod channel;
 pub mod client;
+#[cfg(feature = "codec")]
+pub mod codec;
+pub mod envelope;
+pub mod event;
 pub mod io;
+#[cfg(any(feature = "logger", feature = "defmt", feature = "async"))]
+pub mod logger;
+pub mod mux;
+#[cfg(feature = "pipe")]
+pub mod pipe;
+pub mod server;
+pub mod system;
+#[cfg(feature = "update")]
+pub mod update;
+
+#[cfg(feature = "macros")]
+pub use airfrog_rpc_macros::service;
+
+// The code the service macro generates uses the default codec
+#[cfg(all(feature = "macros", not(any(feature = "postcard", feature = "cbor"))))]
+compile_error!("The `macros` feature requires a codec - enable `postcard` or `cbor`");
 
 /// RPC errors
+///
+/// New variants may be added in future versions, so `match`es must include a
+/// wildcard arm.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     /// No data available
     NoData,
@@ -155,6 +234,23 @@ pub enum Error {
     Uninit,
     /// Data area or buffer not aligned
     NotAligned,
+    /// Malformed message, for example an invalid envelope
+    Malformed,
+    /// Request was cancelled, and the cancellation acknowledged by the other
+    /// side
+    Cancelled,
+    /// Value could not be encoded or decoded
+    Codec,
+    /// [`server::RpcServer`] returned a non-OK status
+    Status(server::Status),
+    /// Target's service definition does not match the Host's, or the Target
+    /// does not implement the service
+    ServiceMismatch,
+    /// Firmware update image failed verification on the Target
+    UpdateFailed,
+    /// Multiplexed stream was closed by the other side before data written
+    /// to it was sent
+    StreamClosed,
 }
 
 /// Type to represent the result of an RPC operation
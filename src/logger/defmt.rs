@@ -0,0 +1,317 @@
+//! defmt global logger - used by a Target to ship defmt log frames to the
+//! Host over a dedicated channel.
+//!
+//! Enabling the `defmt` feature makes this crate the application's
+//! `defmt::global_logger`, so it must not be used alongside another defmt
+//! transport such as `defmt-rtt`.
+//!
+//! Encoded frames are buffered in RAM, [`BUFFER_SIZE`] bytes, and published
+//! on the log channel as the channel becomes free.  Call [`init()`] once to
+//! initialize the channel, and `defmt::flush()` periodically, for example
+//! from a superloop, so that the last frames logged reach the Host.  Frames
+//! logged before [`init()`] is called are buffered.
+//!
+//! The buffer size can be changed by setting the
+//! `AIRFROG_RPC_DEFMT_BUFFER_SIZE` environment variable when building, for
+//! example:
+//!
+//! ```text
+//! AIRFROG_RPC_DEFMT_BUFFER_SIZE=4096 cargo build
+//! ```
+//!
+//! When the Host is slow and the buffer fills, new frames are dropped, and
+//! the number dropped is reported to the Host with the next message.  Frames
+//! are never split, so the Host always receives whole frames, in order.
+//!
+//! Logging is done within a critical section, using `critical-section`, so
+//! is safe from interrupts and from within other critical sections.  The
+//! application must provide a `critical-section` implementation.
+//!
+//! # Wire format
+//!
+//! Each channel message starts with a little-endian `u32`, the number of
+//! frames dropped since the previous message, followed by one or more
+//! frames.  Each frame is a little-endian `u16` length, followed by that many
+//! bytes of the frame, as encoded by `defmt::Encoder`.
+//!
+//! ```rust,ignore
+//! airfrog_rpc::logger::defmt::init(LOG_CH_ADDR, LOG_CH_SIZE)?;
+//!
+//! loop {
+//!     defmt::info!("Temperature {}", read_temperature());
+//!     defmt::flush();
+//!     // Other superloop work
+//! }
+//! ```
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use core::cell::RefCell;
+use critical_section::{CriticalSection, Mutex, RestoreState};
+use defmt::Encoder;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::Result;
+use crate::channel::{ChannelActor, OwnedChannel, RamChannelIo};
+use crate::logger::{FRAME_HEADER_SIZE, MESSAGE_HEADER_SIZE};
+
+/// Size of the buffer frames are held in until published, in bytes.  Set by
+/// the `AIRFROG_RPC_DEFMT_BUFFER_SIZE` environment variable at build time,
+/// defaulting to 1024.
+pub const BUFFER_SIZE: usize = match option_env!("AIRFROG_RPC_DEFMT_BUFFER_SIZE") {
+    Some(size) => parse_size(size),
+    None => 1024,
+};
+
+const _: () = assert!(
+    BUFFER_SIZE > MESSAGE_HEADER_SIZE + FRAME_HEADER_SIZE,
+    "AIRFROG_RPC_DEFMT_BUFFER_SIZE is too small"
+);
+
+// Parses a decimal buffer size at compile time
+const fn parse_size(size: &str) -> usize {
+    let bytes = size.as_bytes();
+    assert!(
+        !bytes.is_empty(),
+        "AIRFROG_RPC_DEFMT_BUFFER_SIZE must be a number"
+    );
+    let mut value: usize = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let digit = bytes[i];
+        assert!(
+            digit.is_ascii_digit(),
+            "AIRFROG_RPC_DEFMT_BUFFER_SIZE must be a number"
+        );
+        value = value * 10 + (digit - b'0') as usize;
+        i += 1;
+    }
+    value
+}
+
+static STATE: Mutex<RefCell<State>> = Mutex::new(RefCell::new(State {
+    taken: false,
+    written: false,
+    restore: RestoreState::invalid(),
+    encoder: Encoder::new(),
+    frames: Frames {
+        channel: None,
+        buf: [0; BUFFER_SIZE],
+        len: MESSAGE_HEADER_SIZE,
+        frame_len: 0,
+        overflow: false,
+        dropped: 0,
+        total_dropped: 0,
+    },
+}));
+
+struct State {
+    taken: bool,
+    // Whether anything has been written to the current frame
+    written: bool,
+    restore: RestoreState,
+    encoder: Encoder,
+    frames: Frames,
+}
+
+// Buffer of encoded frames
+struct Frames {
+    // The log channel, once initialized
+    channel: Option<OwnedChannel<RamChannelIo>>,
+    // Message being built - the dropped count, followed by frames
+    buf: [u8; BUFFER_SIZE],
+    // Length of the whole frames in the buffer
+    len: usize,
+    // Length of the frame being written, including its header, or 0 if none
+    // is being written, and whether it has overflowed the buffer
+    frame_len: usize,
+    overflow: bool,
+    // Frames dropped since the last message
+    dropped: u32,
+    total_dropped: u32,
+}
+
+/// Initialize the log channel, as a Producer, and publish any frames logged
+/// so far.  Call once, before or after logging starts.
+///
+/// Arguments:
+/// - `base_addr` - Base address of the log channel
+/// - `size` - Total size of the channel in bytes, including Control Block
+///   and data portions
+pub fn init(base_addr: u32, size: usize) -> Result<()> {
+    critical_section::with(|cs| {
+        let channel =
+            OwnedChannel::new(RamChannelIo::new(), ChannelActor::Producer, base_addr, size)?;
+
+        let mut state = STATE.borrow_ref_mut(cs);
+        state.frames.channel = Some(channel);
+        state.frames.publish()?;
+        Ok(())
+    })
+}
+
+/// Total number of frames dropped, saturating at `u32::MAX`
+pub fn dropped() -> u32 {
+    critical_section::with(|cs| STATE.borrow_ref(cs).frames.total_dropped)
+}
+
+#[defmt::global_logger]
+struct ChannelDefmtLogger;
+
+unsafe impl defmt::Logger for ChannelDefmtLogger {
+    fn acquire() {
+        // Held until release()
+        let restore = unsafe { critical_section::acquire() };
+        let cs = unsafe { CriticalSection::new() };
+
+        let mut state = STATE.borrow_ref_mut(cs);
+        if state.taken {
+            panic!("defmt logger taken reentrantly");
+        }
+        state.taken = true;
+        state.written = false;
+        state.restore = restore;
+
+        let State {
+            encoder, frames, ..
+        } = &mut *state;
+        frames.start();
+        encoder.start_frame(|bytes| frames.append(bytes));
+    }
+
+    unsafe fn flush() {
+        let cs = unsafe { CriticalSection::new() };
+        let _ = STATE.borrow_ref_mut(cs).frames.publish();
+    }
+
+    unsafe fn release() {
+        let cs = unsafe { CriticalSection::new() };
+        let mut state = STATE.borrow_ref_mut(cs);
+
+        // Frames without writes, such as from defmt::flush(), are discarded
+        let State {
+            encoder,
+            frames,
+            written,
+            ..
+        } = &mut *state;
+        encoder.end_frame(|bytes| frames.append(bytes));
+        frames.commit(*written);
+        let _ = frames.publish();
+
+        state.taken = false;
+        let restore = state.restore;
+        drop(state);
+        unsafe { critical_section::release(restore) };
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        let cs = unsafe { CriticalSection::new() };
+        let mut state = STATE.borrow_ref_mut(cs);
+
+        state.written = true;
+        let State {
+            encoder, frames, ..
+        } = &mut *state;
+        encoder.write(bytes, |bytes| frames.append(bytes));
+    }
+}
+
+impl Frames {
+    // Starts a new frame, leaving space for its header
+    fn start(&mut self) {
+        self.frame_len = FRAME_HEADER_SIZE;
+        self.overflow = self.len + FRAME_HEADER_SIZE > BUFFER_SIZE;
+    }
+
+    // Appends encoded bytes to the frame being written
+    fn append(&mut self, bytes: &[u8]) {
+        if self.overflow {
+            return;
+        }
+        let start = self.len + self.frame_len;
+        let end = start + bytes.len();
+        if end > BUFFER_SIZE || end - self.len > FRAME_HEADER_SIZE + u16::MAX as usize {
+            self.overflow = true;
+            return;
+        }
+        self.buf[start..end].copy_from_slice(bytes);
+        self.frame_len += bytes.len();
+    }
+
+    // Adds the frame being written to the buffer if `keep` is set, or drops
+    // it if it overflowed
+    fn commit(&mut self, keep: bool) {
+        let frame_len = core::mem::take(&mut self.frame_len);
+        if !keep {
+            return;
+        }
+        if self.overflow {
+            self.drop_frame();
+            return;
+        }
+        let data_len = (frame_len - FRAME_HEADER_SIZE) as u16;
+        self.buf[self.len..self.len + FRAME_HEADER_SIZE].copy_from_slice(&data_len.to_le_bytes());
+        self.len += frame_len;
+    }
+
+    // Publishes as many whole frames as fit in the channel, if it has been
+    // initialized and is free.  Returns whether a message was published.
+    fn publish(&mut self) -> Result<bool> {
+        if self.len == MESSAGE_HEADER_SIZE {
+            return Ok(false);
+        }
+        let Some(channel) = self.channel.as_mut() else {
+            return Ok(false);
+        };
+        if !channel.can_publish()? {
+            return Ok(false);
+        }
+        let capacity = channel.data_capacity()?;
+
+        let mut end = MESSAGE_HEADER_SIZE;
+        while end < self.len {
+            let frame_len = frame_at(&self.buf, end);
+            if end + frame_len > capacity {
+                break;
+            }
+            end += frame_len;
+        }
+
+        if end == MESSAGE_HEADER_SIZE {
+            // The first frame will never fit in the channel
+            let frame_len = frame_at(&self.buf, end);
+            self.remove(end + frame_len);
+            self.drop_frame();
+            return Ok(false);
+        }
+
+        self.buf[..MESSAGE_HEADER_SIZE].copy_from_slice(&self.dropped.to_le_bytes());
+        channel.publish_bytes(&self.buf[..end])?;
+        self.dropped = 0;
+        self.remove(end);
+
+        Ok(true)
+    }
+
+    // Removes frames from the start of the buffer, up to `end`, keeping any
+    // frame being written
+    fn remove(&mut self, end: usize) {
+        self.buf
+            .copy_within(end..self.len + self.frame_len, MESSAGE_HEADER_SIZE);
+        self.len -= end - MESSAGE_HEADER_SIZE;
+    }
+
+    fn drop_frame(&mut self) {
+        self.dropped = self.dropped.saturating_add(1);
+        self.total_dropped = self.total_dropped.saturating_add(1);
+    }
+}
+
+// Length of the frame at `pos` in `buf`, including its header
+fn frame_at(buf: &[u8], pos: usize) -> usize {
+    FRAME_HEADER_SIZE + u16::from_le_bytes([buf[pos], buf[pos + 1]]) as usize
+}
@@ -0,0 +1,294 @@
+//! Log collector - used by a Host to receive a Target's logs.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{ChannelActor, ReaderWriterChannelIo};
+use crate::client::{AsyncDelay, ChannelConfig, PollPolicy, Poller};
+use crate::io::{Reader, Writer};
+use crate::logger::{
+    FRAME_HEADER_SIZE, MESSAGE_HEADER_SIZE, RECORD_HEADER_SIZE, RecordHeader, decode_level,
+};
+use crate::{Error, Result};
+
+/// Log record received from the Target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// Timestamp, in Target-defined units
+    pub timestamp: u64,
+
+    /// Level
+    pub level: log::Level,
+
+    /// Target of the record, typically the module path which logged it
+    pub target: String,
+
+    /// Formatted message
+    pub message: String,
+}
+
+/// Collects the log records a Target publishes on its log channel, using
+/// `ChannelLogger`, with the `logger` feature.
+///
+/// ```rust,ignore
+/// use airfrog_rpc::logger::LogCollector;
+///
+/// let mut logs = LogCollector::<_, _, Delay>::new(&mut reader, &mut writer, 0x2000_3000);
+/// loop {
+///     let record = logs.next().await?;
+///     println!("{} {} {}: {}", record.timestamp, record.level, record.target, record.message);
+/// }
+/// ```
+pub struct LogCollector<'a, R: Reader, W: Writer, D: AsyncDelay> {
+    io: ReaderWriterChannelIo<'a, R, W>,
+    ch_config: ChannelConfig,
+    records: VecDeque<LogRecord>,
+    dropped: u64,
+    poll_policy: PollPolicy,
+    _delay: core::marker::PhantomData<D>,
+}
+
+impl<'a, R: Reader, W: Writer, D: AsyncDelay> LogCollector<'a, R, W, D> {
+    /// Create a new LogCollector
+    ///
+    /// Arguments:
+    /// - `reader`: Reader object to read from target
+    /// - `writer`: Writer object to write to target
+    /// - `log_ch_ptr`: Pointer to the log channel in target memory.  The
+    ///   channel size is read from the target.
+    pub fn new(reader: &'a mut R, writer: &'a mut W, log_ch_ptr: u32) -> Self {
+        Self {
+            io: ReaderWriterChannelIo::new(reader, writer),
+            ch_config: ChannelConfig::FromTarget { ptr: log_ch_ptr },
+            records: VecDeque::new(),
+            dropped: 0,
+            poll_policy: PollPolicy::DEFAULT,
+            _delay: core::marker::PhantomData,
+        }
+    }
+
+    /// Set the policy used when waiting for the Target.  Defaults to
+    /// [`PollPolicy::DEFAULT`].
+    pub fn with_poll_policy(mut self, policy: PollPolicy) -> Self {
+        self.poll_policy = policy;
+        self
+    }
+
+    /// Wait for the next log record.
+    ///
+    /// Returns:
+    /// - `Ok(record)`: Record received
+    /// - `Err(Error::Malformed)`: Message from the Target was invalid.  It is
+    ///   discarded, and collection can continue.
+    /// - `Err(error)`: Other error occurred
+    pub async fn next(&mut self) -> Result<LogRecord> {
+        let mut poller = Poller::new(self.poll_policy);
+        loop {
+            if let Some(record) = self.try_next().await? {
+                return Ok(record);
+            }
+            poller.wait::<D>().await;
+        }
+    }
+
+    /// Get the next log record, if one is available, without waiting.
+    pub async fn try_next(&mut self) -> Result<Option<LogRecord>> {
+        if self.records.is_empty() {
+            self.receive().await?;
+        }
+        Ok(self.records.pop_front())
+    }
+
+    /// Total number of records the Target reported dropping
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    // Consumes a message from the channel, if one is available, and queues
+    // its records
+    async fn receive(&mut self) -> Result<()> {
+        let Some(msg) =
+            consume_message(&mut self.io, &mut self.ch_config, &mut self.dropped).await?
+        else {
+            return Ok(());
+        };
+
+        let mut msg = &msg[..];
+        while !msg.is_empty() {
+            let Some((record, record_len)) = decode_record(msg) else {
+                warn!("Discarding malformed log record");
+                return Err(Error::Malformed);
+            };
+            self.records.push_back(record);
+            msg = &msg[record_len..];
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects the defmt frames a Target publishes on its log channel, using
+/// the `logger::defmt` global logger, with the `defmt` feature.
+///
+/// Frames are returned whole, in order, as encoded by the Target, ready to
+/// pass to a defmt decoder along with the firmware ELF.  For example, feed
+/// them to a `defmt_decoder::StreamDecoder`.
+///
+/// ```rust,ignore
+/// use airfrog_rpc::logger::DefmtCollector;
+///
+/// let table = defmt_decoder::Table::parse(&elf)?.unwrap();
+/// let mut decoder = table.new_stream_decoder();
+/// let mut frames = DefmtCollector::<_, _, Delay>::new(&mut reader, &mut writer, 0x2000_3000);
+/// loop {
+///     decoder.received(&frames.next().await?);
+///     while let Ok(frame) = decoder.decode() {
+///         println!("{}", frame.display(true));
+///     }
+/// }
+/// ```
+pub struct DefmtCollector<'a, R: Reader, W: Writer, D: AsyncDelay> {
+    io: ReaderWriterChannelIo<'a, R, W>,
+    ch_config: ChannelConfig,
+    frames: VecDeque<Vec<u8>>,
+    dropped: u64,
+    poll_policy: PollPolicy,
+    _delay: core::marker::PhantomData<D>,
+}
+
+impl<'a, R: Reader, W: Writer, D: AsyncDelay> DefmtCollector<'a, R, W, D> {
+    /// Create a new DefmtCollector
+    ///
+    /// Arguments:
+    /// - `reader`: Reader object to read from target
+    /// - `writer`: Writer object to write to target
+    /// - `log_ch_ptr`: Pointer to the log channel in target memory.  The
+    ///   channel size is read from the target.
+    pub fn new(reader: &'a mut R, writer: &'a mut W, log_ch_ptr: u32) -> Self {
+        Self {
+            io: ReaderWriterChannelIo::new(reader, writer),
+            ch_config: ChannelConfig::FromTarget { ptr: log_ch_ptr },
+            frames: VecDeque::new(),
+            dropped: 0,
+            poll_policy: PollPolicy::DEFAULT,
+            _delay: core::marker::PhantomData,
+        }
+    }
+
+    /// Set the policy used when waiting for the Target.  Defaults to
+    /// [`PollPolicy::DEFAULT`].
+    pub fn with_poll_policy(mut self, policy: PollPolicy) -> Self {
+        self.poll_policy = policy;
+        self
+    }
+
+    /// Wait for the next encoded frame.
+    ///
+    /// Returns:
+    /// - `Ok(frame)`: Frame received
+    /// - `Err(Error::Malformed)`: Message from the Target was invalid.  It is
+    ///   discarded, and collection can continue.
+    /// - `Err(error)`: Other error occurred
+    pub async fn next(&mut self) -> Result<Vec<u8>> {
+        let mut poller = Poller::new(self.poll_policy);
+        loop {
+            if let Some(frame) = self.try_next().await? {
+                return Ok(frame);
+            }
+            poller.wait::<D>().await;
+        }
+    }
+
+    /// Get the next encoded frame, if one is available, without waiting.
+    pub async fn try_next(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.frames.is_empty() {
+            self.receive().await?;
+        }
+        Ok(self.frames.pop_front())
+    }
+
+    /// Total number of frames the Target reported dropping
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    // Consumes a message from the channel, if one is available, and queues
+    // its frames
+    async fn receive(&mut self) -> Result<()> {
+        let Some(msg) =
+            consume_message(&mut self.io, &mut self.ch_config, &mut self.dropped).await?
+        else {
+            return Ok(());
+        };
+
+        let mut msg = &msg[..];
+        while !msg.is_empty() {
+            let frame = msg.get(..FRAME_HEADER_SIZE).and_then(|header| {
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                msg.get(FRAME_HEADER_SIZE..FRAME_HEADER_SIZE + len)
+            });
+            let Some(frame) = frame else {
+                warn!("Discarding malformed defmt frame");
+                return Err(Error::Malformed);
+            };
+            self.frames.push_back(frame.to_vec());
+            msg = &msg[FRAME_HEADER_SIZE + frame.len()..];
+        }
+
+        Ok(())
+    }
+}
+
+// Consumes a message from a log channel, if one is available, adding the
+// number of entries the Target dropped to `dropped`.  Returns the entries.
+async fn consume_message<R: Reader, W: Writer>(
+    io: &mut ReaderWriterChannelIo<'_, R, W>,
+    ch_config: &mut ChannelConfig,
+    dropped: &mut u64,
+) -> Result<Option<Vec<u8>>> {
+    let mut log_ch = ch_config.channel(io, ChannelActor::Consumer).await?;
+    let Some(size) = log_ch.data_available().await? else {
+        return Ok(None);
+    };
+    let mut buf = vec![0u8; size];
+    let len = log_ch.consume_bytes(&mut buf).await?;
+    buf.truncate(len);
+
+    let Some(header) = buf.get(..MESSAGE_HEADER_SIZE) else {
+        warn!("Discarding malformed log message ({len} bytes)");
+        return Err(Error::Malformed);
+    };
+    let count = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    if count > 0 {
+        debug!("Target dropped {count} log entries");
+        *dropped += count as u64;
+    }
+
+    buf.drain(..MESSAGE_HEADER_SIZE);
+    Ok(Some(buf))
+}
+
+// Decodes the record at the start of `buf`, returning it and its length
+fn decode_record(buf: &[u8]) -> Option<(LogRecord, usize)> {
+    let header = RecordHeader::decode(buf).ok()?;
+    let level = decode_level(header.level)?;
+    let target_end = RECORD_HEADER_SIZE + header.target_len as usize;
+    let target = core::str::from_utf8(buf.get(RECORD_HEADER_SIZE..target_end)?).ok()?;
+    let message = core::str::from_utf8(buf.get(target_end..header.record_len())?).ok()?;
+
+    let record = LogRecord {
+        timestamp: header.timestamp,
+        level,
+        target: target.into(),
+        message: message.into(),
+    };
+    Some((record, header.record_len()))
+}
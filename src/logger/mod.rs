@@ -0,0 +1,113 @@
+//! Target log records, shipped to the Host over a dedicated channel, so that
+//! `log::info!()` and friends reach the Host without a UART.
+//!
+//! - Target: `ChannelLogger`, a [`log::Log`] implementation, requiring the
+//!   `logger` feature
+//! - Host: [`LogCollector`], which returns the records as they arrive
+//!
+//! Targets using `defmt` can instead enable the `defmt` feature, making the
+//! `defmt` module the `defmt::global_logger`, and collect the frames on the
+//! Host with [`DefmtCollector`].
+//!
+//! The Target formats records into a bounded buffer, and publishes as many
+//! as fit in each channel message.  When the Host is slow to collect them
+//! and the buffer fills, new records are dropped, and the number dropped is
+//! reported to the Host with the next message.
+//!
+//! # Wire format
+//!
+//! Each channel message starts with a little-endian `u32`, the number of
+//! records dropped since the previous message, followed by one or more
+//! records.  Each record is a header of [`RECORD_HEADER_SIZE`] bytes,
+//! little-endian, followed by the target and message strings, UTF-8:
+//!
+//! | Offset | Size | Field         |
+//! |--------|------|---------------|
+//! | 0      | 8    | `timestamp`   |
+//! | 8      | 1    | `level`       |
+//! | 9      | 1    | `target_len`  |
+//! | 10     | 2    | `message_len` |
+//!
+//! `level` is 1 (error) to 5 (trace), as [`log::Level`], and `timestamp` is
+//! in Target-defined units, typically milliseconds since boot.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#[cfg(feature = "defmt")]
+pub mod defmt;
+#[cfg(feature = "async")]
+pub mod futures;
+#[cfg(feature = "logger")]
+pub mod sync;
+
+#[cfg(feature = "async")]
+pub use futures::{DefmtCollector, LogCollector, LogRecord};
+#[cfg(feature = "logger")]
+pub use sync::{ChannelLogger, DEFAULT_RECORD_SIZE};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+/// Size of the dropped record count at the start of each message, in bytes
+pub const MESSAGE_HEADER_SIZE: usize = 4;
+
+/// Size of the length preceding each defmt frame, in bytes
+pub const FRAME_HEADER_SIZE: usize = 2;
+
+/// Size of a record header, in bytes
+pub const RECORD_HEADER_SIZE: usize = 12;
+
+// Header of a record within a message
+#[cfg(any(feature = "logger", feature = "async"))]
+pub(crate) struct RecordHeader {
+    timestamp: u64,
+    level: u8,
+    target_len: u8,
+    message_len: u16,
+}
+
+#[cfg(any(feature = "logger", feature = "async"))]
+impl RecordHeader {
+    #[cfg(feature = "logger")]
+    fn encode(&self) -> [u8; RECORD_HEADER_SIZE] {
+        let mut buf = [0u8; RECORD_HEADER_SIZE];
+        buf[0..8].copy_from_slice(&self.timestamp.to_le_bytes());
+        buf[8] = self.level;
+        buf[9] = self.target_len;
+        buf[10..12].copy_from_slice(&self.message_len.to_le_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> crate::Result<Self> {
+        let buf = buf
+            .get(..RECORD_HEADER_SIZE)
+            .ok_or(crate::Error::Malformed)?;
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&buf[0..8]);
+        Ok(Self {
+            timestamp: u64::from_le_bytes(timestamp),
+            level: buf[8],
+            target_len: buf[9],
+            message_len: u16::from_le_bytes([buf[10], buf[11]]),
+        })
+    }
+
+    // Total length of the record, including this header
+    fn record_len(&self) -> usize {
+        RECORD_HEADER_SIZE + self.target_len as usize + self.message_len as usize
+    }
+}
+
+#[cfg(feature = "async")]
+fn decode_level(level: u8) -> Option<log::Level> {
+    match level {
+        1 => Some(log::Level::Error),
+        2 => Some(log::Level::Warn),
+        3 => Some(log::Level::Info),
+        4 => Some(log::Level::Debug),
+        5 => Some(log::Level::Trace),
+        _ => None,
+    }
+}
@@ -0,0 +1,289 @@
+//! Channel logger - used by a Target to ship its logs to the Host.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use core::cell::RefCell;
+use core::fmt::Write;
+use critical_section::Mutex;
+use log::{Log, Metadata, Record};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{ChannelActor, ChannelIo, OwnedChannel};
+use crate::logger::{MESSAGE_HEADER_SIZE, RECORD_HEADER_SIZE, RecordHeader};
+use crate::{Error, Result};
+
+/// Default maximum size of a formatted record, in bytes, including its
+/// header and target
+pub const DEFAULT_RECORD_SIZE: usize = 256;
+
+/// [`log::Log`] implementation which formats records into a buffer of `BUF`
+/// bytes, and publishes them on a dedicated channel, for collection by a
+/// Host's [`crate::logger::LogCollector`].
+///
+/// Records are published as they are logged, if the channel is free, and
+/// otherwise when the next record is logged or [`Log::flush()`] is called.
+/// Call `flush()` periodically, for example from a superloop, so that the
+/// last records logged reach the Host.  Records which do not fit in the
+/// buffer, or in the channel, are dropped and counted.
+///
+/// Each record is formatted on the stack, into a buffer of `REC` bytes,
+/// before being added to the buffer, so records larger than `REC` are also
+/// dropped.  `REC` defaults to [`DEFAULT_RECORD_SIZE`].
+///
+/// Uses `critical-section` so it can be used from interrupts.  The
+/// application must provide a `critical-section` implementation.  Records
+/// are formatted outside the critical section, which is only held to add
+/// them to the buffer and publish it.  Records logged while the logger is
+/// itself busy are discarded.
+///
+/// ```rust,ignore
+/// use airfrog_rpc::channel::RamChannelIo;
+/// use airfrog_rpc::logger::ChannelLogger;
+///
+/// static LOGGER: ChannelLogger<RamChannelIo, 1024> =
+///     ChannelLogger::new(RamChannelIo::new(), LOG_CH_ADDR, uptime_ms);
+///
+/// LOGGER.init_channel(LOG_CH_SIZE)?;
+/// log::set_logger(&LOGGER).unwrap();
+/// log::set_max_level(log::LevelFilter::Info);
+///
+/// loop {
+///     log::logger().flush();
+///     // Other superloop work
+/// }
+/// ```
+pub struct ChannelLogger<I: ChannelIo, const BUF: usize, const REC: usize = DEFAULT_RECORD_SIZE> {
+    inner: Mutex<RefCell<Inner<I, BUF>>>,
+    base_addr: u32,
+    timestamp: fn() -> u64,
+}
+
+struct Inner<I: ChannelIo, const BUF: usize> {
+    // The I/O, until the channel is initialized or connected to, and then
+    // the channel, created once and kept
+    io: Option<I>,
+    channel: Option<OwnedChannel<I>>,
+    // Message being built - the dropped count, followed by records
+    buf: [u8; BUF],
+    len: usize,
+    // Records dropped since the last message
+    dropped: u32,
+    total_dropped: u32,
+}
+
+impl<I: ChannelIo, const BUF: usize, const REC: usize> ChannelLogger<I, BUF, REC> {
+    /// Create a new ChannelLogger
+    ///
+    /// Arguments:
+    /// - `io` - Object implementing [`ChannelIo`] to access the log channel
+    /// - `base_addr` - Base address of the log channel
+    /// - `timestamp` - Returns the timestamp to include in each record, for
+    ///   example milliseconds since boot
+    pub const fn new(io: I, base_addr: u32, timestamp: fn() -> u64) -> Self {
+        assert!(BUF > MESSAGE_HEADER_SIZE + RECORD_HEADER_SIZE);
+        assert!(REC > RECORD_HEADER_SIZE);
+        Self {
+            inner: Mutex::new(RefCell::new(Inner {
+                io: Some(io),
+                channel: None,
+                buf: [0; BUF],
+                len: MESSAGE_HEADER_SIZE,
+                dropped: 0,
+                total_dropped: 0,
+            })),
+            base_addr,
+            timestamp,
+        }
+    }
+
+    /// Initialize the log channel, as a Producer.  Call once, before
+    /// logging, or use [`Self::connect_channel()`] if the channel has been
+    /// initialized separately.  Records logged beforehand are buffered.
+    ///
+    /// Arguments:
+    /// - `size` - Total size of the channel in bytes, including Control Block
+    ///   and data portions
+    ///
+    /// Returns [`Error::InvalidOperation`] if the channel is already
+    /// initialized, or a previous attempt failed, in which case the
+    /// [`ChannelIo`] has been consumed.
+    pub fn init_channel(&self, size: usize) -> Result<()> {
+        critical_section::with(|cs| {
+            let mut inner = self.inner.borrow_ref_mut(cs);
+            let io = inner.io.take().ok_or(Error::InvalidOperation)?;
+            let channel = OwnedChannel::new(io, ChannelActor::Producer, self.base_addr, size)?;
+            inner.channel = Some(channel);
+            Ok(())
+        })
+    }
+
+    /// Connect to a log channel which has already been initialized, as a
+    /// Producer.  Call once, instead of [`Self::init_channel()`].
+    ///
+    /// Returns [`Error::InvalidOperation`] if the channel is already
+    /// initialized or connected to, or a previous attempt failed, in which
+    /// case the [`ChannelIo`] has been consumed.
+    pub fn connect_channel(&self) -> Result<()> {
+        critical_section::with(|cs| {
+            let mut inner = self.inner.borrow_ref_mut(cs);
+            let io = inner.io.take().ok_or(Error::InvalidOperation)?;
+            let channel = OwnedChannel::from_target(io, ChannelActor::Producer, self.base_addr)?;
+            inner.channel = Some(channel);
+            Ok(())
+        })
+    }
+
+    /// Total number of records dropped, saturating at `u32::MAX`
+    pub fn dropped(&self) -> u32 {
+        critical_section::with(|cs| self.inner.borrow_ref(cs).total_dropped)
+    }
+}
+
+impl<I: ChannelIo + Send, const BUF: usize, const REC: usize> Log for ChannelLogger<I, BUF, REC> {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let mut buf = [0u8; REC];
+        let encoded = encode(&mut buf, (self.timestamp)(), record);
+        critical_section::with(|cs| {
+            let Ok(mut inner) = self.inner.borrow(cs).try_borrow_mut() else {
+                return;
+            };
+            match encoded {
+                Some(len) => inner.push(&buf[..len]),
+                None => inner.drop_record(),
+            }
+            let _ = inner.publish();
+        });
+    }
+
+    fn flush(&self) {
+        critical_section::with(|cs| {
+            if let Ok(mut inner) = self.inner.borrow(cs).try_borrow_mut() {
+                let _ = inner.publish();
+            }
+        });
+    }
+}
+
+// Encodes a record, header first, into `buf`.  Returns the record's length,
+// or `None` if it doesn't fit.
+fn encode(buf: &mut [u8], timestamp: u64, record: &Record) -> Option<usize> {
+    let target = truncate(record.target(), u8::MAX as usize);
+    let message_start = RECORD_HEADER_SIZE + target.len();
+    buf.get_mut(RECORD_HEADER_SIZE..message_start)?
+        .copy_from_slice(target.as_bytes());
+
+    let message_end = buf.len().min(message_start + u16::MAX as usize);
+    let mut writer = SliceWriter {
+        buf: &mut buf[message_start..message_end],
+        len: 0,
+    };
+    write!(writer, "{}", record.args()).ok()?;
+    let message_len = writer.len;
+
+    let header = RecordHeader {
+        timestamp,
+        level: record.level() as u8,
+        target_len: target.len() as u8,
+        message_len: message_len as u16,
+    };
+    buf[..RECORD_HEADER_SIZE].copy_from_slice(&header.encode());
+    Some(message_start + message_len)
+}
+
+impl<I: ChannelIo, const BUF: usize> Inner<I, BUF> {
+    // Appends an encoded record to the buffer, or drops it if it doesn't fit
+    fn push(&mut self, record: &[u8]) {
+        let end = self.len + record.len();
+        if end > BUF {
+            self.drop_record();
+            return;
+        }
+        self.buf[self.len..end].copy_from_slice(record);
+        self.len = end;
+    }
+
+    // Publishes as many whole records as fit in the channel, if it has been
+    // initialized and is free.  Returns whether a message was published.
+    fn publish(&mut self) -> Result<bool> {
+        if self.len == MESSAGE_HEADER_SIZE {
+            return Ok(false);
+        }
+
+        let Some(channel) = self.channel.as_mut() else {
+            return Ok(false);
+        };
+        if !channel.can_publish()? {
+            return Ok(false);
+        }
+        let capacity = channel.data_capacity()?;
+
+        let mut end = MESSAGE_HEADER_SIZE;
+        while end < self.len {
+            let record_len = RecordHeader::decode(&self.buf[end..self.len])?.record_len();
+            if end + record_len > capacity {
+                break;
+            }
+            end += record_len;
+        }
+
+        if end == MESSAGE_HEADER_SIZE {
+            // The first record will never fit in the channel
+            let record_len = RecordHeader::decode(&self.buf[end..self.len])?.record_len();
+            self.remove(end + record_len);
+            self.drop_record();
+            return Ok(false);
+        }
+
+        self.buf[..MESSAGE_HEADER_SIZE].copy_from_slice(&self.dropped.to_le_bytes());
+        channel.publish_bytes(&self.buf[..end])?;
+        self.dropped = 0;
+        self.remove(end);
+
+        Ok(true)
+    }
+
+    // Removes records from the start of the buffer, up to `end`
+    fn remove(&mut self, end: usize) {
+        self.buf.copy_within(end..self.len, MESSAGE_HEADER_SIZE);
+        self.len -= end - MESSAGE_HEADER_SIZE;
+    }
+
+    fn drop_record(&mut self) {
+        self.dropped = self.dropped.saturating_add(1);
+        self.total_dropped = self.total_dropped.saturating_add(1);
+    }
+}
+
+// Truncates a string to at most `max` bytes, on a character boundary
+fn truncate(value: &str, max: usize) -> &str {
+    let mut len = value.len().min(max);
+    while !value.is_char_boundary(len) {
+        len -= 1;
+    }
+    &value[..len]
+}
+
+// Formats into a byte slice, failing if the output doesn't fit
+struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.len + s.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(core::fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+//! Asynchronous multiplexer - typically used by a Host.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{ChannelActor, Deadline, ReaderWriterChannelIo};
+use crate::client::{AsyncDelay, ChannelConfig, PollPolicy, Poller, RpcClientConfig};
+use crate::io::{Reader, Writer};
+use crate::mux::{self, Stream, StreamBuf};
+use crate::{Error, Result};
+
+/// Asynchronous multiplexer, carrying logical streams over a
+/// command/response channel pair.
+///
+/// The Host transmits on the command channel, and receives on the response
+/// channel.  Both sides must be configured with the same number of streams.
+///
+/// [`Self::write()`] and [`Self::read()`] drive the channels themselves.  When
+/// only waiting for data on one stream, call [`Self::poll()`] periodically to
+/// keep other streams moving.
+///
+/// Methods which wait for data to be sent take a
+/// [`crate::channel::Deadline`], as the Target may not grant the stream
+/// credit, or may stop polling altogether.
+///
+/// ```rust,ignore
+/// use airfrog_rpc::channel::MaxWaits;
+/// use airfrog_rpc::mux::AsyncMux;
+///
+/// let mut mux = AsyncMux::<_, _, Delay>::new(&mut reader, &mut writer, config, 4, 4096);
+/// mux.open(TELEMETRY, MaxWaits(100)).await?;
+/// mux.write(TELEMETRY, b"start", MaxWaits(100)).await?;
+/// let data = mux.read(LOG).await?;
+/// ```
+pub struct AsyncMux<'a, R: Reader, W: Writer, D: AsyncDelay> {
+    io: ReaderWriterChannelIo<'a, R, W>,
+    tx_ch_config: ChannelConfig,
+    rx_ch_config: ChannelConfig,
+    streams: Vec<Stream<HostBuf>>,
+    // Stream to schedule first in the next message
+    next: usize,
+    poll_policy: PollPolicy,
+    _delay: core::marker::PhantomData<D>,
+}
+
+impl<'a, R: Reader, W: Writer, D: AsyncDelay> AsyncMux<'a, R, W, D> {
+    /// Create a new AsyncMux
+    ///
+    /// Arguments:
+    /// - `reader`: Reader object to read from target
+    /// - `writer`: Writer object to write to target
+    /// - `config`: Channel configuration
+    /// - `streams`: Number of streams, at most 256
+    /// - `buffer`: Maximum bytes buffered per stream, in each direction
+    pub fn new(
+        reader: &'a mut R,
+        writer: &'a mut W,
+        config: RpcClientConfig,
+        streams: usize,
+        buffer: usize,
+    ) -> Self {
+        let (tx_ch_config, rx_ch_config) = config.channel_configs();
+        let streams = streams.min(u8::MAX as usize + 1);
+
+        Self {
+            io: ReaderWriterChannelIo::new(reader, writer),
+            tx_ch_config,
+            rx_ch_config,
+            streams: (0..streams)
+                .map(|_| Stream::new(HostBuf::new(buffer), HostBuf::new(buffer)))
+                .collect(),
+            next: 0,
+            poll_policy: PollPolicy::DEFAULT,
+            _delay: core::marker::PhantomData,
+        }
+    }
+
+    /// Set the policy used when waiting for the Target.  Defaults to
+    /// [`PollPolicy::DEFAULT`].
+    pub fn with_poll_policy(mut self, policy: PollPolicy) -> Self {
+        self.poll_policy = policy;
+        self
+    }
+
+    /// Open a stream, and tell the Target.  If the stream was closed, and
+    /// the Target has not yet been told, the close is sent first.
+    ///
+    /// Arguments:
+    /// - `stream` - Stream ID
+    /// - `deadline` - When to give up waiting for the Target, for example
+    ///   [`crate::channel::MaxWaits`]
+    ///
+    /// Returns:
+    /// - `Ok(())`: Stream opened, and the Target told
+    /// - `Err(Error::Timeout)`: Deadline passed first.  The stream may still
+    ///   be opened by a later [`Self::poll()`].
+    /// - `Err(Error::InvalidOperation)`: Stream ID is out of range
+    pub async fn open(&mut self, stream: u8, mut deadline: impl Deadline) -> Result<()> {
+        self.poll_until(stream, &mut deadline, |s| match s.open() {
+            Ok(()) => Ok(true),
+            Err(Error::Busy) => Ok(false),
+            Err(e) => Err(e),
+        })
+        .await?;
+        self.flush(stream, &mut deadline).await
+    }
+
+    /// Close a stream, once data already written has been sent.
+    ///
+    /// Arguments:
+    /// - `stream` - Stream ID
+    /// - `deadline` - When to give up waiting for the Target
+    ///
+    /// Returns:
+    /// - `Ok(())`: Data sent, and the Target told the stream is closed
+    /// - `Err(Error::Timeout)`: Deadline passed first.  The data and close
+    ///   may still be sent by a later [`Self::poll()`].
+    /// - `Err(Error::StreamClosed)`: Target closed the stream first, and data
+    ///   written to it was discarded unsent
+    /// - `Err(Error::InvalidOperation)`: Stream ID is out of range
+    pub async fn close(&mut self, stream: u8, mut deadline: impl Deadline) -> Result<()> {
+        self.stream_mut(stream)?.close();
+        self.flush(stream, &mut deadline).await
+    }
+
+    /// Whether a stream is open, having been opened by either side
+    pub fn is_open(&self, stream: u8) -> bool {
+        self.streams
+            .get(stream as usize)
+            .is_some_and(|s| s.is_open())
+    }
+
+    /// Write data to a stream, waiting until it has all been sent.
+    ///
+    /// Arguments:
+    /// - `stream` - Stream ID
+    /// - `data` - Data to write
+    /// - `deadline` - When to give up waiting for the Target
+    ///
+    /// Returns:
+    /// - `Ok(())`: Data sent
+    /// - `Err(Error::Timeout)`: Deadline passed first.  Data already buffered
+    ///   may still be sent by a later [`Self::poll()`].
+    /// - `Err(Error::StreamClosed)`: Target closed the stream, and data
+    ///   written to it was discarded unsent
+    /// - `Err(Error::InvalidOperation)`: Stream isn't open
+    pub async fn write(
+        &mut self,
+        stream: u8,
+        data: &[u8],
+        mut deadline: impl Deadline,
+    ) -> Result<()> {
+        let mut data = data;
+        self.poll_until(stream, &mut deadline, |s| {
+            let count = s.write(data)?;
+            data = &data[count..];
+            Ok(data.is_empty())
+        })
+        .await?;
+        self.flush(stream, &mut deadline).await
+    }
+
+    /// Read data from a stream, waiting until some is available.
+    ///
+    /// Returns an empty buffer if the stream is closed and no data remains.
+    /// Returns [`Error::InvalidOperation`] if the stream ID is out of range.
+    pub async fn read(&mut self, stream: u8) -> Result<Vec<u8>> {
+        let mut poller = Poller::new(self.poll_policy);
+        loop {
+            let s = self.stream_mut(stream)?;
+            if s.available() > 0 || !s.is_open() {
+                return Ok(s.rx.data.drain(..).collect());
+            }
+            if !self.poll().await? {
+                poller.wait::<D>().await;
+            } else {
+                poller.reset();
+            }
+        }
+    }
+
+    /// Read data already received on a stream, without waiting
+    pub fn try_read(&mut self, stream: u8) -> Result<Vec<u8>> {
+        Ok(self.stream_mut(stream)?.rx.data.drain(..).collect())
+    }
+
+    /// Move data between the stream buffers and the channels, without
+    /// waiting.
+    ///
+    /// Returns `Ok(true)` if any data was received or transmitted.
+    pub async fn poll(&mut self) -> Result<bool> {
+        let received = self.receive().await?;
+        let transmitted = self.transmit().await?;
+        Ok(received || transmitted)
+    }
+
+    // Polls until the stream has nothing left to send
+    async fn flush(&mut self, stream: u8, deadline: &mut impl Deadline) -> Result<()> {
+        self.poll_until(stream, deadline, |s| {
+            if s.take_discarded() {
+                return Err(Error::StreamClosed);
+            }
+            Ok(s.flushed())
+        })
+        .await
+    }
+
+    // Polls until `done` returns true for the stream, waiting whenever a
+    // poll makes no progress, until the deadline passes
+    async fn poll_until(
+        &mut self,
+        stream: u8,
+        deadline: &mut impl Deadline,
+        mut done: impl FnMut(&mut Stream<HostBuf>) -> Result<bool>,
+    ) -> Result<()> {
+        let mut poller = Poller::new(self.poll_policy);
+        loop {
+            if done(self.stream_mut(stream)?)? {
+                return Ok(());
+            }
+            if self.poll().await? {
+                poller.reset();
+            } else if deadline.expired() {
+                return Err(Error::Timeout);
+            } else {
+                poller.wait::<D>().await;
+            }
+        }
+    }
+
+    async fn receive(&mut self) -> Result<bool> {
+        let mut rx_ch = self
+            .rx_ch_config
+            .channel(&mut self.io, ChannelActor::Consumer)
+            .await?;
+        let Some(size) = rx_ch.data_available().await? else {
+            return Ok(false);
+        };
+        let mut msg = vec![0u8; size];
+        let len = rx_ch.consume_bytes(&mut msg).await?;
+        mux::receive(&mut self.streams, &msg[..len]);
+        Ok(true)
+    }
+
+    async fn transmit(&mut self) -> Result<bool> {
+        let mut tx_ch = self
+            .tx_ch_config
+            .channel(&mut self.io, ChannelActor::Producer)
+            .await?;
+        if !tx_ch.can_publish().await? {
+            return Ok(false);
+        }
+
+        let mut msg = vec![0u8; tx_ch.data_capacity().await?];
+        let streams = self.streams.len();
+        let len = mux::transmit(
+            &mut self.streams,
+            &mut vec![0; streams],
+            &mut vec![0; streams],
+            &mut self.next,
+            &mut msg,
+        );
+        if len == 0 {
+            return Ok(false);
+        }
+        tx_ch.publish_bytes(&msg[..len]).await?;
+        Ok(true)
+    }
+
+    fn stream_mut(&mut self, stream: u8) -> Result<&mut Stream<HostBuf>> {
+        self.streams
+            .get_mut(stream as usize)
+            .ok_or(Error::InvalidOperation)
+    }
+}
+
+// Byte queue with a maximum length
+struct HostBuf {
+    data: VecDeque<u8>,
+    capacity: usize,
+}
+
+impl HostBuf {
+    fn new(capacity: usize) -> Self {
+        Self {
+            data: VecDeque::new(),
+            capacity,
+        }
+    }
+}
+
+impl StreamBuf for HostBuf {
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    fn push(&mut self, data: &[u8]) -> usize {
+        let count = data.len().min(self.free());
+        self.data.extend(&data[..count]);
+        count
+    }
+
+    fn pop(&mut self, out: &mut [u8]) -> usize {
+        let count = out.len().min(self.data.len());
+        for (byte, value) in out.iter_mut().zip(self.data.drain(..count)) {
+            *byte = value;
+        }
+        count
+    }
+}
@@ -0,0 +1,410 @@
+//! Multiplexed virtual channels, carrying many logical streams over a single
+//! command/response [`crate::channel::Channel`] pair.
+//!
+//! Each stream is identified by a stream ID, from 0 to one less than the
+//! number of streams both sides are configured with.  A stream is opened by
+//! either side, after which both sides can write to it and read from it.
+//! Closing a stream sends any data already written, and then tells the other
+//! side the stream is closed.  A closed stream can only be opened again once
+//! the other side has been told it was closed.
+//!
+//! When a side is told a stream is closed, it discards any data it has not
+//! yet sent on the stream, and the next write to the stream, or
+//! [`AsyncMux`] flush, returns [`crate::Error::StreamClosed`].
+//!
+//! Each side buffers data per stream, in both directions.  Flow control is
+//! credit-based: a side only sends as much data on a stream as the other side
+//! has said it has room for, so a slow reader stalls only its own stream.
+//!
+//! Outgoing data from all streams with data pending is shared fairly between
+//! channel messages, round-robin, so a busy stream cannot starve the others.
+//!
+//! - Target: [`Mux`], synchronous and without allocation
+//! - Host: [`AsyncMux`], asynchronous
+//!
+//! # Wire format
+//!
+//! Each channel message contains one or more frames:
+//!
+//! | Offset | Size | Field       |
+//! |--------|------|-------------|
+//! | 0      | 1    | `stream_id` |
+//! | 1      | 1    | `kind`      |
+//! | 2      | 2    | `length`    |
+//! | 4      | n    | `data`      |
+//!
+//! `kind` is a [`FrameKind`], and `length` the length of `data` in bytes,
+//! little-endian.  [`FrameKind::Data`] frames carry stream data, and
+//! [`FrameKind::Credit`] frames a little-endian `u32`, the number of further
+//! bytes the sender can accept on the stream.  Other frames carry no data.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#[cfg(feature = "async")]
+pub mod futures;
+pub mod sync;
+
+#[cfg(feature = "async")]
+pub use futures::AsyncMux;
+pub use sync::Mux;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+/// Size of a frame header, in bytes
+pub const HEADER_SIZE: usize = 4;
+
+// Size of a credit frame, in bytes
+const CREDIT_SIZE: usize = HEADER_SIZE + 4;
+
+/// Type of a multiplexed frame
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    /// Stream data
+    Data = 0,
+    /// Sender has opened the stream
+    Open = 1,
+    /// Sender has closed the stream
+    Close = 2,
+    /// Sender can accept more data on the stream
+    Credit = 3,
+}
+
+impl TryFrom<u8> for FrameKind {
+    type Error = crate::Error;
+
+    fn try_from(value: u8) -> crate::Result<Self> {
+        match value {
+            0 => Ok(FrameKind::Data),
+            1 => Ok(FrameKind::Open),
+            2 => Ok(FrameKind::Close),
+            3 => Ok(FrameKind::Credit),
+            _ => Err(crate::Error::Malformed),
+        }
+    }
+}
+
+// A frame within a channel message
+struct Frame<'a> {
+    stream: u8,
+    kind: FrameKind,
+    data: &'a [u8],
+}
+
+impl<'a> Frame<'a> {
+    // Decodes the frame at the start of `buf`, returning it and its total
+    // length
+    fn decode(buf: &'a [u8]) -> crate::Result<(Self, usize)> {
+        let header = buf.get(..HEADER_SIZE).ok_or(crate::Error::Malformed)?;
+        let kind = FrameKind::try_from(header[1])?;
+        let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+        let data = buf
+            .get(HEADER_SIZE..HEADER_SIZE + len)
+            .ok_or(crate::Error::Malformed)?;
+        let frame = Self {
+            stream: header[0],
+            kind,
+            data,
+        };
+        Ok((frame, HEADER_SIZE + len))
+    }
+}
+
+// Writes a frame header into `buf`, which must be at least HEADER_SIZE bytes
+fn encode_header(buf: &mut [u8], stream: u8, kind: FrameKind, len: usize) {
+    buf[0] = stream;
+    buf[1] = kind as u8;
+    buf[2..HEADER_SIZE].copy_from_slice(&(len as u16).to_le_bytes());
+}
+
+// Byte buffer used for a stream's received and transmitted data, allowing
+// the Target and Host to share the protocol implementation
+pub(crate) trait StreamBuf {
+    fn len(&self) -> usize;
+    fn capacity(&self) -> usize;
+    // Appends as much of `data` as fits, returning how much
+    fn push(&mut self, data: &[u8]) -> usize;
+    // Removes as much as fits in `out`, returning how much
+    fn pop(&mut self, out: &mut [u8]) -> usize;
+    fn clear(&mut self);
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn free(&self) -> usize {
+        self.capacity() - self.len()
+    }
+}
+
+// State of one stream
+pub(crate) struct Stream<B: StreamBuf> {
+    open: bool,
+    send_open: bool,
+    send_close: bool,
+    // Unsent data was discarded when the other side closed the stream, and
+    // this has not yet been reported
+    discarded: bool,
+    // Bytes the other side has said it can accept
+    credit: usize,
+    // Bytes we have said we can accept, and not yet received
+    advertised: usize,
+    rx: B,
+    tx: B,
+}
+
+impl<B: StreamBuf> Stream<B> {
+    pub(crate) const fn new(rx: B, tx: B) -> Self {
+        Self {
+            open: false,
+            send_open: false,
+            send_close: false,
+            discarded: false,
+            credit: 0,
+            advertised: 0,
+            rx,
+            tx,
+        }
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // Returns Busy if the stream is still being closed, as the other side
+    // must be told about the close before the open
+    pub(crate) fn open(&mut self) -> crate::Result<()> {
+        if self.send_close {
+            return Err(crate::Error::Busy);
+        }
+        if !self.open {
+            self.open = true;
+            self.send_open = true;
+            self.reset_credit();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn close(&mut self) {
+        if self.open {
+            self.open = false;
+            self.send_close = true;
+        }
+    }
+
+    // Buffers data to send, returning how much was buffered
+    pub(crate) fn write(&mut self, data: &[u8]) -> crate::Result<usize> {
+        if self.take_discarded() {
+            return Err(crate::Error::StreamClosed);
+        }
+        if !self.open {
+            return Err(crate::Error::InvalidOperation);
+        }
+        Ok(self.tx.push(data))
+    }
+
+    // Whether unsent data was discarded when the other side closed the
+    // stream, since this was last called
+    pub(crate) fn take_discarded(&mut self) -> bool {
+        core::mem::take(&mut self.discarded)
+    }
+
+    pub(crate) fn read(&mut self, buf: &mut [u8]) -> usize {
+        self.rx.pop(buf)
+    }
+
+    pub(crate) fn available(&self) -> usize {
+        self.rx.len()
+    }
+
+    // Whether there is nothing left to send
+    #[cfg(feature = "async")]
+    pub(crate) fn flushed(&self) -> bool {
+        self.tx.is_empty() && !self.send_open && !self.send_close
+    }
+
+    // Credit which should be granted to the other side.  Small grants are
+    // held back, to avoid sending many tiny frames.
+    fn grant(&self) -> usize {
+        if !self.open {
+            return 0;
+        }
+        let grant = self.rx.free().saturating_sub(self.advertised);
+        if self.advertised == 0 || grant >= self.rx.capacity() / 2 {
+            grant.min(u32::MAX as usize)
+        } else {
+            0
+        }
+    }
+
+    fn reset_credit(&mut self) {
+        self.credit = 0;
+        self.advertised = 0;
+    }
+}
+
+// Delivers the frames in a received message to their streams.
+pub(crate) fn receive<B: StreamBuf>(streams: &mut [Stream<B>], mut msg: &[u8]) {
+    while !msg.is_empty() {
+        let Ok((frame, len)) = Frame::decode(msg) else {
+            warn!("Discarding malformed mux message");
+            return;
+        };
+        msg = &msg[len..];
+
+        let Some(s) = streams.get_mut(frame.stream as usize) else {
+            warn!("Discarding frame for unknown stream {}", frame.stream);
+            continue;
+        };
+
+        match frame.kind {
+            FrameKind::Data if !s.open => {
+                warn!("Discarding data for closed stream {}", frame.stream);
+            }
+            FrameKind::Data => {
+                let count = s.rx.push(frame.data);
+                if count < frame.data.len() {
+                    warn!("Stream {} overflowed its credit", frame.stream);
+                }
+                s.advertised = s.advertised.saturating_sub(frame.data.len());
+            }
+            FrameKind::Open => {
+                debug!("Stream {} opened", frame.stream);
+                s.open = true;
+            }
+            FrameKind::Close => {
+                debug!("Stream {} closed", frame.stream);
+                if !s.tx.is_empty() {
+                    warn!(
+                        "Stream {} closed with {} bytes unsent",
+                        frame.stream,
+                        s.tx.len()
+                    );
+                    s.discarded = true;
+                }
+                s.open = false;
+                s.send_open = false;
+                s.tx.clear();
+                s.reset_credit();
+            }
+            // Granted before the other side learnt of a close
+            FrameKind::Credit if !s.open => {
+                debug!("Discarding credit for closed stream {}", frame.stream);
+            }
+            FrameKind::Credit => {
+                let credit = frame
+                    .data
+                    .get(..4)
+                    .map_or(0, |c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]));
+                s.credit = s.credit.saturating_add(credit as usize);
+            }
+        }
+    }
+}
+
+// Builds a message into `out` containing pending control frames, and a fair
+// share of each stream's pending data, within the credit the other side has
+// granted.  `pending` and `alloc` are scratch space, one entry per stream.
+// `next` is the stream to schedule first, and is advanced.
+//
+// Returns the length of the message, which is 0 if there is nothing to send.
+pub(crate) fn transmit<B: StreamBuf>(
+    streams: &mut [Stream<B>],
+    pending: &mut [usize],
+    alloc: &mut [usize],
+    next: &mut usize,
+    out: &mut [u8],
+) -> usize {
+    let mut pos = 0;
+
+    // Opens and credits first, so they precede any data
+    for (id, s) in streams.iter_mut().enumerate() {
+        if s.send_open && out.len() - pos >= HEADER_SIZE {
+            encode_header(&mut out[pos..], id as u8, FrameKind::Open, 0);
+            pos += HEADER_SIZE;
+            s.send_open = false;
+        }
+
+        let grant = s.grant();
+        if grant > 0 && out.len() - pos >= CREDIT_SIZE {
+            encode_header(&mut out[pos..], id as u8, FrameKind::Credit, 4);
+            out[pos + HEADER_SIZE..pos + CREDIT_SIZE]
+                .copy_from_slice(&(grant as u32).to_le_bytes());
+            pos += CREDIT_SIZE;
+            s.advertised += grant;
+        }
+    }
+
+    for (pending, s) in pending.iter_mut().zip(streams.iter()) {
+        *pending = s.tx.len().min(s.credit);
+    }
+    schedule(pending, alloc, *next, out.len() - pos);
+    if !streams.is_empty() {
+        *next = (*next + 1) % streams.len();
+    }
+
+    for (id, s) in streams.iter_mut().enumerate() {
+        let len = alloc[id];
+        if len > 0 {
+            encode_header(&mut out[pos..], id as u8, FrameKind::Data, len);
+            pos += HEADER_SIZE;
+            s.tx.pop(&mut out[pos..pos + len]);
+            pos += len;
+            s.credit -= len;
+        }
+
+        // Closes once all the stream's data has been sent
+        if s.send_close && s.tx.is_empty() && out.len() - pos >= HEADER_SIZE {
+            encode_header(&mut out[pos..], id as u8, FrameKind::Close, 0);
+            pos += HEADER_SIZE;
+            s.send_close = false;
+            s.reset_credit();
+        }
+    }
+
+    pos
+}
+
+// Shares `space` bytes of a message fairly between streams, round-robin from
+// stream `start`, allowing for each stream's frame header.
+//
+// On entry `pending[i]` is the number of bytes stream `i` has waiting.  On
+// return `alloc[i]` is the number of bytes stream `i` may send.
+fn schedule(pending: &[usize], alloc: &mut [usize], start: usize, mut space: usize) {
+    let streams = pending.len();
+    alloc.fill(0);
+
+    loop {
+        let active = (0..streams).filter(|&i| pending[i] > alloc[i]).count();
+        if active == 0 {
+            break;
+        }
+        let quantum = (space / active).max(1);
+
+        let mut progress = false;
+        for i in (0..streams).map(|n| (start + n) % streams) {
+            let wanted = pending[i] - alloc[i];
+            let header = if alloc[i] == 0 { HEADER_SIZE } else { 0 };
+            if wanted == 0 || space <= header {
+                continue;
+            }
+            let granted = wanted
+                .min(quantum.saturating_sub(header).max(1))
+                .min(space - header)
+                .min(u16::MAX as usize - alloc[i]);
+            if granted == 0 {
+                continue;
+            }
+            alloc[i] += granted;
+            space -= granted + header;
+            progress = true;
+        }
+
+        if !progress {
+            break;
+        }
+    }
+}
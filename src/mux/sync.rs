@@ -0,0 +1,244 @@
+//! Synchronous multiplexer - typically used by a Target.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use core::borrow::BorrowMut;
+use core::marker::PhantomData;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{ChannelIo, GenericChannel};
+use crate::mux::{self, Stream, StreamBuf};
+use crate::{Error, Result};
+
+/// Synchronous multiplexer, carrying `STREAMS` logical streams over a
+/// command/response channel pair.  Does not allocate.
+///
+/// Arguments:
+/// - `I` - Channel I/O type, typically [`crate::channel::RamChannelIo`]
+/// - `STREAMS` - Number of streams
+/// - `BUF` - Size of each stream's receive and transmit buffers, in bytes
+/// - `MSG` - Size of the message buffers, in bytes.  Should be at least the
+///   capacity of the channels, as larger received messages are discarded.
+/// - `B` - How the channels hold their I/O object - borrowed by default, or
+///   owned, for example using [`crate::channel::OwnedRamChannel`], so the
+///   mux can be stored in a struct or task
+///
+/// Call [`Self::poll()`] regularly, for example from a superloop, to move
+/// data between the stream buffers and the channels.
+///
+/// ```rust,ignore
+/// const LOG: u8 = 0;
+/// const TELEMETRY: u8 = 1;
+///
+/// let mut mux: Mux<_, 4, 256, 512> = Mux::new(cmd_ch, rsp_ch);
+/// mux.open(LOG)?;
+/// loop {
+///     mux.poll()?;
+///     mux.write(LOG, b"hello")?;
+///     let len = mux.read(TELEMETRY, &mut buf)?;
+///     // Other superloop work
+/// }
+/// ```
+pub struct Mux<
+    'a,
+    I: ChannelIo,
+    const STREAMS: usize,
+    const BUF: usize,
+    const MSG: usize,
+    B: BorrowMut<I> = &'a mut I,
+> {
+    rx_ch: GenericChannel<I, B>,
+    tx_ch: GenericChannel<I, B>,
+    streams: [Stream<Ring<BUF>>; STREAMS],
+    rx_msg: [u8; MSG],
+    tx_msg: [u8; MSG],
+    // Stream to schedule first in the next message
+    next: usize,
+    _lifetime: PhantomData<&'a ()>,
+}
+
+impl<I: ChannelIo, const STREAMS: usize, const BUF: usize, const MSG: usize, B: BorrowMut<I>>
+    Mux<'_, I, STREAMS, BUF, MSG, B>
+{
+    /// Create a new Mux
+    ///
+    /// Arguments:
+    /// - `rx_ch` - Channel to receive on, which must be a Consumer.
+    ///   Typically the command channel.
+    /// - `tx_ch` - Channel to transmit on, which must be a Producer.
+    ///   Typically the response channel.
+    pub fn new(rx_ch: GenericChannel<I, B>, tx_ch: GenericChannel<I, B>) -> Self {
+        Self {
+            rx_ch,
+            tx_ch,
+            streams: [const { Stream::new(Ring::new(), Ring::new()) }; STREAMS],
+            rx_msg: [0; MSG],
+            tx_msg: [0; MSG],
+            next: 0,
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Open a stream, and tell the other side.
+    ///
+    /// Returns:
+    /// - `Ok(())`: Stream opened, or already open
+    /// - `Err(Error::Busy)`: Stream was closed, and the other side has not
+    ///   yet been told.  Call [`Self::poll()`] and try again.
+    /// - `Err(Error::InvalidOperation)`: Stream ID is out of range
+    pub fn open(&mut self, stream: u8) -> Result<()> {
+        self.stream_mut(stream)?.open()
+    }
+
+    /// Close a stream.  Data already written is sent before the other side
+    /// is told.
+    ///
+    /// Returns [`Error::InvalidOperation`] if the stream ID is out of range.
+    pub fn close(&mut self, stream: u8) -> Result<()> {
+        self.stream_mut(stream)?.close();
+        Ok(())
+    }
+
+    /// Whether a stream is open, having been opened by either side
+    pub fn is_open(&self, stream: u8) -> bool {
+        self.streams
+            .get(stream as usize)
+            .is_some_and(|s| s.is_open())
+    }
+
+    /// Buffer data to be sent on a stream.
+    ///
+    /// Returns:
+    /// - `Ok(count)`: Number of bytes buffered, which is less than
+    ///   `data.len()` if the stream's transmit buffer is full
+    /// - `Err(Error::StreamClosed)`: The other side closed the stream, and
+    ///   data previously written to it was discarded unsent.  Reported once.
+    /// - `Err(Error::InvalidOperation)`: Stream isn't open
+    pub fn write(&mut self, stream: u8, data: &[u8]) -> Result<usize> {
+        self.stream_mut(stream)?.write(data)
+    }
+
+    /// Read data received on a stream.  Data received before the stream was
+    /// closed can still be read after it is closed.
+    ///
+    /// Returns the number of bytes read, which is 0 if none is available.
+    pub fn read(&mut self, stream: u8, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.stream_mut(stream)?.read(buf))
+    }
+
+    /// Number of bytes waiting to be read from a stream
+    pub fn available(&self, stream: u8) -> usize {
+        self.streams
+            .get(stream as usize)
+            .map_or(0, |s| s.available())
+    }
+
+    /// Move data between the stream buffers and the channels.  Does not
+    /// block.
+    ///
+    /// Returns `Ok(true)` if any data was received or transmitted, or a
+    /// message larger than `MSG` was received and discarded.
+    pub fn poll(&mut self) -> Result<bool> {
+        let received = self.receive()?;
+        let transmitted = self.transmit()?;
+        Ok(received || transmitted)
+    }
+
+    fn receive(&mut self) -> Result<bool> {
+        let len = match self.rx_ch.consume_bytes(&mut self.rx_msg) {
+            Ok(len) => len,
+            Err(Error::NoData) => return Ok(false),
+            Err(Error::BufferTooSmall) => {
+                // Leaving it in the channel would block every later message
+                warn!("Discarding message larger than {MSG} bytes");
+                self.rx_ch.discard()?;
+                return Ok(true);
+            }
+            Err(e) => return Err(e),
+        };
+        mux::receive(&mut self.streams, &self.rx_msg[..len]);
+        Ok(true)
+    }
+
+    fn transmit(&mut self) -> Result<bool> {
+        if !self.tx_ch.can_publish()? {
+            return Ok(false);
+        }
+
+        let space = MSG.min(self.tx_ch.data_capacity()?);
+        let len = mux::transmit(
+            &mut self.streams,
+            &mut [0; STREAMS],
+            &mut [0; STREAMS],
+            &mut self.next,
+            &mut self.tx_msg[..space],
+        );
+        if len == 0 {
+            return Ok(false);
+        }
+        self.tx_ch.publish_bytes(&self.tx_msg[..len])?;
+        Ok(true)
+    }
+
+    fn stream_mut(&mut self, stream: u8) -> Result<&mut Stream<Ring<BUF>>> {
+        self.streams
+            .get_mut(stream as usize)
+            .ok_or(Error::InvalidOperation)
+    }
+}
+
+// Fixed-size byte ring buffer
+struct Ring<const N: usize> {
+    buf: [u8; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> Ring<N> {
+    const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            head: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<const N: usize> StreamBuf for Ring<N> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        N
+    }
+
+    fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    fn push(&mut self, data: &[u8]) -> usize {
+        let count = data.len().min(self.free());
+        for (i, byte) in data[..count].iter().enumerate() {
+            self.buf[(self.head + self.len + i) % N] = *byte;
+        }
+        self.len += count;
+        count
+    }
+
+    fn pop(&mut self, out: &mut [u8]) -> usize {
+        let count = out.len().min(self.len);
+        for (i, byte) in out[..count].iter_mut().enumerate() {
+            *byte = self.buf[(self.head + i) % N];
+        }
+        if count > 0 {
+            self.head = (self.head + count) % N;
+            self.len -= count;
+        }
+        count
+    }
+}
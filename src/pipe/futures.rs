@@ -0,0 +1,155 @@
+//! Asynchronous pipe - typically used by a Host.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use alloc::collections::VecDeque;
+use alloc::vec;
+use embedded_io_async::{ErrorType, Read, Write};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{ChannelActor, ReaderWriterChannelIo};
+use crate::client::{AsyncDelay, ChannelConfig, PollPolicy, Poller, RpcClientConfig};
+use crate::io::{Reader, Writer};
+use crate::{Error, Result};
+
+/// Asynchronous byte-stream pipe over a command/response channel pair,
+/// implementing `embedded-io-async` traits.
+///
+/// The Host transmits on the command channel, and receives on the response
+/// channel.
+///
+/// Reads wait until data is available.  Writes wait until the command
+/// channel is free, and publish as much as fits in one message.  While
+/// waiting, data received from the Target is buffered for later reads.
+/// [`Write::flush()`] waits until the Target has consumed the last message.
+///
+/// ```rust,ignore
+/// use airfrog_rpc::pipe::AsyncPipe;
+/// use embedded_io_async::{Read, Write};
+///
+/// let mut pipe = AsyncPipe::<_, _, Delay>::new(&mut reader, &mut writer, config);
+/// pipe.write_all(b"AT\r\n").await?;
+/// let mut buf = [0u8; 64];
+/// let len = pipe.read(&mut buf).await?;
+/// ```
+pub struct AsyncPipe<'a, R: Reader, W: Writer, D: AsyncDelay> {
+    io: ReaderWriterChannelIo<'a, R, W>,
+    tx_ch_config: ChannelConfig,
+    rx_ch_config: ChannelConfig,
+    rx_buf: VecDeque<u8>,
+    poll_policy: PollPolicy,
+    _delay: core::marker::PhantomData<D>,
+}
+
+impl<'a, R: Reader, W: Writer, D: AsyncDelay> AsyncPipe<'a, R, W, D> {
+    /// Create a new AsyncPipe
+    ///
+    /// Arguments:
+    /// - `reader`: Reader object to read from target
+    /// - `writer`: Writer object to write to target
+    /// - `config`: Channel configuration
+    pub fn new(reader: &'a mut R, writer: &'a mut W, config: RpcClientConfig) -> Self {
+        let (tx_ch_config, rx_ch_config) = config.channel_configs();
+
+        Self {
+            io: ReaderWriterChannelIo::new(reader, writer),
+            tx_ch_config,
+            rx_ch_config,
+            rx_buf: VecDeque::new(),
+            poll_policy: PollPolicy::DEFAULT,
+            _delay: core::marker::PhantomData,
+        }
+    }
+
+    /// Set the policy used when waiting for the Target.  Defaults to
+    /// [`PollPolicy::DEFAULT`].
+    pub fn with_poll_policy(mut self, policy: PollPolicy) -> Self {
+        self.poll_policy = policy;
+        self
+    }
+
+    // Receives a message into the receive buffer, if one is available
+    async fn receive(&mut self) -> Result<bool> {
+        let mut rx_ch = self
+            .rx_ch_config
+            .channel(&mut self.io, ChannelActor::Consumer)
+            .await?;
+        let Some(size) = rx_ch.data_available().await? else {
+            return Ok(false);
+        };
+        let mut msg = vec![0u8; size];
+        let len = rx_ch.consume_bytes(&mut msg).await?;
+        self.rx_buf.extend(&msg[..len]);
+        Ok(len > 0)
+    }
+
+    // Waits until the command channel is free, receiving meanwhile, so the
+    // Target is never left waiting for the Host to read
+    async fn wait_free(&mut self) -> Result<()> {
+        let mut poller = Poller::new(self.poll_policy);
+        loop {
+            let mut tx_ch = self
+                .tx_ch_config
+                .channel(&mut self.io, ChannelActor::Producer)
+                .await?;
+            if tx_ch.can_publish().await? {
+                return Ok(());
+            }
+            if !self.receive().await? {
+                poller.wait::<D>().await;
+            } else {
+                poller.reset();
+            }
+        }
+    }
+}
+
+impl<R: Reader, W: Writer, D: AsyncDelay> ErrorType for AsyncPipe<'_, R, W, D> {
+    type Error = Error;
+}
+
+impl<R: Reader, W: Writer, D: AsyncDelay> Read for AsyncPipe<'_, R, W, D> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut poller = Poller::new(self.poll_policy);
+        while self.rx_buf.is_empty() {
+            if !self.receive().await? {
+                poller.wait::<D>().await;
+            } else {
+                poller.reset();
+            }
+        }
+
+        let len = buf.len().min(self.rx_buf.len());
+        for (byte, value) in buf.iter_mut().zip(self.rx_buf.drain(..len)) {
+            *byte = value;
+        }
+        Ok(len)
+    }
+}
+
+impl<R: Reader, W: Writer, D: AsyncDelay> Write for AsyncPipe<'_, R, W, D> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.wait_free().await?;
+
+        let mut tx_ch = self
+            .tx_ch_config
+            .channel(&mut self.io, ChannelActor::Producer)
+            .await?;
+        let len = buf.len().min(tx_ch.data_capacity().await?);
+        tx_ch.publish_bytes(&buf[..len]).await?;
+        Ok(len)
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.wait_free().await
+    }
+}
@@ -0,0 +1,45 @@
+//! Byte-stream pipes over a command/response [`crate::channel::Channel`]
+//! pair, so that protocols written against a byte stream such as a UART can
+//! run over the channels, like a virtual serial link.
+//!
+//! - Target: [`Pipe`], implementing `embedded-io` [`embedded_io::Read`] and
+//!   [`embedded_io::Write`]
+//! - Host: [`AsyncPipe`], implementing `embedded-io-async`
+//!   [`embedded_io_async::Read`] and [`embedded_io_async::Write`]
+//!
+//! Message boundaries are not preserved.  Each message received is buffered
+//! and returned by as many reads as it takes, and writes are split into as
+//! many messages as required.
+//!
+//! Requires the `pipe` feature.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#[cfg(feature = "async")]
+pub mod futures;
+pub mod sync;
+
+#[cfg(feature = "async")]
+pub use futures::AsyncPipe;
+pub use sync::Pipe;
+
+use embedded_io::ErrorKind;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::Error;
+
+impl embedded_io::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Timeout => ErrorKind::TimedOut,
+            Error::InvalidOperation => ErrorKind::InvalidInput,
+            Error::PayloadTooLarge | Error::BufferTooSmall => ErrorKind::OutOfMemory,
+            Error::Malformed | Error::Codec => ErrorKind::InvalidData,
+            Error::Cancelled => ErrorKind::Interrupted,
+            _ => ErrorKind::Other,
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Synchronous pipe - typically used by a Target.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use core::borrow::BorrowMut;
+use core::marker::PhantomData;
+use embedded_io::{ErrorType, Read, ReadReady, Write, WriteReady};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{ChannelIo, GenericChannel};
+use crate::{Error, Result};
+
+/// Synchronous byte-stream pipe over a command/response channel pair,
+/// implementing `embedded-io` traits.  Does not allocate.
+///
+/// Arguments:
+/// - `I` - Channel I/O type, typically [`crate::channel::RamChannelIo`]
+/// - `BUF` - Size of the receive and transmit buffers, in bytes.  Must be at
+///   least the data capacity of the receive channel, so any message fits.
+/// - `B` - How the channels hold their I/O object - borrowed by default, or
+///   owned, for example using [`crate::channel::OwnedRamChannel`], so the
+///   pipe can be stored in a struct or task
+///
+/// Reads block until data is available, publishing buffered data while they
+/// wait.  Writes are buffered, and published when the transmit channel is
+/// free.  Call [`Write::flush()`] to wait until everything written has been
+/// published, or [`Self::poll()`] regularly to publish buffered data without
+/// blocking.
+///
+/// ```rust,ignore
+/// use embedded_io::{Read, Write};
+///
+/// let mut pipe: Pipe<_, 256> = Pipe::new(cmd_ch, rsp_ch)?;
+/// let mut buf = [0u8; 64];
+/// let len = pipe.read(&mut buf)?;
+/// pipe.write_all(&buf[..len])?;
+/// pipe.flush()?;
+/// ```
+pub struct Pipe<'a, I: ChannelIo, const BUF: usize, B: BorrowMut<I> = &'a mut I> {
+    rx_ch: GenericChannel<I, B>,
+    tx_ch: GenericChannel<I, B>,
+    // Received message, and how much of it has been read
+    rx_buf: [u8; BUF],
+    rx_len: usize,
+    rx_pos: usize,
+    // Data written but not yet published
+    tx_buf: [u8; BUF],
+    tx_len: usize,
+    _lifetime: PhantomData<&'a ()>,
+}
+
+impl<I: ChannelIo, const BUF: usize, B: BorrowMut<I>> Pipe<'_, I, BUF, B> {
+    /// Create a new Pipe
+    ///
+    /// Arguments:
+    /// - `rx_ch` - Channel to receive on, which must be a Consumer.
+    ///   Typically the command channel.
+    /// - `tx_ch` - Channel to transmit on, which must be a Producer.
+    ///   Typically the response channel.
+    ///
+    /// Returns [`Error::BufferTooSmall`] if `BUF` is less than the data
+    /// capacity of `rx_ch`, as a larger message could never be received.
+    pub fn new(mut rx_ch: GenericChannel<I, B>, tx_ch: GenericChannel<I, B>) -> Result<Self> {
+        if BUF < rx_ch.data_capacity()? {
+            return Err(Error::BufferTooSmall);
+        }
+        Ok(Self {
+            rx_ch,
+            tx_ch,
+            rx_buf: [0; BUF],
+            rx_len: 0,
+            rx_pos: 0,
+            tx_buf: [0; BUF],
+            tx_len: 0,
+            _lifetime: PhantomData,
+        })
+    }
+
+    /// Publish buffered data, if the transmit channel is free, and receive
+    /// a message, if none is buffered.  Does not block.
+    ///
+    /// Returns `Ok(true)` if any data was received or published.
+    pub fn poll(&mut self) -> Result<bool> {
+        let received = self.receive()?;
+        let published = self.publish()?;
+        Ok(received || published)
+    }
+
+    // Receives a message into the receive buffer, if it is empty
+    fn receive(&mut self) -> Result<bool> {
+        if self.rx_pos < self.rx_len {
+            return Ok(false);
+        }
+        match self.rx_ch.consume_bytes(&mut self.rx_buf) {
+            Ok(len) => {
+                self.rx_len = len;
+                self.rx_pos = 0;
+                Ok(len > 0)
+            }
+            Err(Error::NoData) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Publishes as much buffered data as fits in a message, if the transmit
+    // channel is free
+    fn publish(&mut self) -> Result<bool> {
+        if self.tx_len == 0 || !self.tx_ch.can_publish()? {
+            return Ok(false);
+        }
+        let len = self.tx_len.min(self.tx_ch.data_capacity()?);
+        self.tx_ch.publish_bytes(&self.tx_buf[..len])?;
+        self.tx_buf.copy_within(len..self.tx_len, 0);
+        self.tx_len -= len;
+        Ok(true)
+    }
+}
+
+impl<I: ChannelIo, const BUF: usize, B: BorrowMut<I>> ErrorType for Pipe<'_, I, BUF, B> {
+    type Error = Error;
+}
+
+impl<I: ChannelIo, const BUF: usize, B: BorrowMut<I>> Read for Pipe<'_, I, BUF, B> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // Publish while waiting, as the Host may be waiting for data already
+        // written before it sends more
+        while self.rx_pos >= self.rx_len {
+            self.poll()?;
+        }
+
+        let len = buf.len().min(self.rx_len - self.rx_pos);
+        buf[..len].copy_from_slice(&self.rx_buf[self.rx_pos..self.rx_pos + len]);
+        self.rx_pos += len;
+        Ok(len)
+    }
+}
+
+impl<I: ChannelIo, const BUF: usize, B: BorrowMut<I>> ReadReady for Pipe<'_, I, BUF, B> {
+    fn read_ready(&mut self) -> Result<bool> {
+        self.receive()?;
+        Ok(self.rx_pos < self.rx_len)
+    }
+}
+
+impl<I: ChannelIo, const BUF: usize, B: BorrowMut<I>> Write for Pipe<'_, I, BUF, B> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.tx_len == BUF {
+            self.publish()?;
+        }
+
+        let len = buf.len().min(BUF - self.tx_len);
+        self.tx_buf[self.tx_len..self.tx_len + len].copy_from_slice(&buf[..len]);
+        self.tx_len += len;
+        self.publish()?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        while self.tx_len > 0 {
+            self.publish()?;
+        }
+        Ok(())
+    }
+}
+
+impl<I: ChannelIo, const BUF: usize, B: BorrowMut<I>> WriteReady for Pipe<'_, I, BUF, B> {
+    fn write_ready(&mut self) -> Result<bool> {
+        self.publish()?;
+        Ok(self.tx_len < BUF)
+    }
+}
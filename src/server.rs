@@ -0,0 +1,592 @@
+//! RPC server, typically used by a Target.
+//!
+//! [`RpcServer`] implements the Target side of [`crate::client::AsyncRpcClient`]
+//! requests.  It owns the command and response channels, and dispatches each
+//! command to a handler function from a static table.  It is `no_std` and
+//! does not allocate.
+//!
+//! # Wire format
+//!
+//! Commands consist of a single command ID byte, followed by an optional
+//! payload:
+//!
+//! | Offset | Size | Field        |
+//! |--------|------|--------------|
+//! | 0      | 1    | `command_id` |
+//! | 1      | n    | `payload`    |
+//!
+//! Responses consist of a single [`Status`] byte, followed by an optional
+//! payload:
+//!
+//! | Offset | Size | Field     |
+//! |--------|------|-----------|
+//! | 0      | 1    | `status`  |
+//! | 1      | n    | `payload` |
+//!
+//! Unknown commands receive a [`Status::UnknownCommand`] response with no
+//! payload.
+//!
+//! The largest command a server accepts is limited by both its buffer size
+//! `N` and its command channel, and similarly for responses.  Commands too
+//! large to accept receive a [`Status::CommandTooLarge`] response.  The Host
+//! can find the limits using the [`crate::system::LIMITS`] command, which the
+//! server handles itself, via
+//! [`crate::client::AsyncRpcClient::server_limits()`].
+//!
+//! This framing is separate from [`crate::envelope::Envelope`] - commands
+//! are not wrapped in envelopes, and responses are matched to commands by
+//! order rather than by request ID.  Use
+//! [`crate::client::AsyncRpcClient::call()`] and friends to talk to an
+//! `RpcServer`.  Envelopes are for Targets which implement their own
+//! protocol, and are not understood by `RpcServer`.
+//!
+//! Command IDs from [`crate::system::RESERVED_BASE`] upwards are reserved for
+//! the [system service](crate::system).  Applications can mount its command
+//! table alongside their own using [`RpcServer::mount()`].
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use airfrog_rpc::server::{Command, Request, RpcServer, Status};
+//!
+//! struct App { led: bool }
+//!
+//! fn set_led(app: &mut App, req: &mut Request<'_>, _rsp: &mut [u8]) -> Result<usize, Status> {
+//!     app.led = *req.payload().first().ok_or(Status::InvalidRequest)? != 0;
+//!     Ok(0)
+//! }
+//!
+//! static COMMANDS: &[Command<App>] = &[Command::new(0x01, "set_led", set_led)];
+//!
+//! let mut server: RpcServer<_, _, 256> = RpcServer::new(cmd_ch, rsp_ch, COMMANDS);
+//! let mut app = App { led: false };
+//! loop {
+//!     server.poll(&mut app)?;
+//!     // Other superloop work
+//! }
+//! ```
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use core::borrow::BorrowMut;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::channel::{ChannelIo, GenericChannel};
+#[cfg(feature = "codec")]
+use crate::codec::Codec;
+use crate::system::{self, ServerLimits};
+use crate::{Error, Result};
+#[cfg(feature = "codec")]
+use serde::{Deserialize, Serialize};
+
+/// Response status, sent as the first byte of every response.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Command succeeded
+    Ok = 0,
+    /// No handler for the command ID
+    UnknownCommand = 1,
+    /// Command was empty, or its payload was invalid
+    InvalidRequest = 2,
+    /// Handler failed
+    HandlerError = 3,
+    /// Response would not fit in the response channel
+    ResponseTooLarge = 4,
+    /// Handler abandoned the command following a cancel request.  Not sent
+    /// as a status byte - the server acknowledges the cancellation using
+    /// [`crate::channel::Channel::publish_cancelled()`] instead.
+    Cancelled = 5,
+    /// Command was larger than the server can receive.  See
+    /// [`crate::system::LIMITS`].
+    CommandTooLarge = 6,
+}
+
+impl From<u8> for Status {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Status::Ok,
+            1 => Status::UnknownCommand,
+            2 => Status::InvalidRequest,
+            3 => Status::HandlerError,
+            4 => Status::ResponseTooLarge,
+            5 => Status::Cancelled,
+            6 => Status::CommandTooLarge,
+            _ => Status::HandlerError,
+        }
+    }
+}
+
+/// Command handler function.
+///
+/// Arguments:
+/// - `ctx` - Application context, as passed to [`RpcServer::poll()`]
+/// - `request` - The command being handled
+/// - `response` - Buffer for the response payload.  Its length is the
+///   maximum response payload size.
+///
+/// Returns the number of response payload bytes written, or the status to
+/// respond with on failure.
+pub type Handler<C> = fn(
+    ctx: &mut C,
+    request: &mut Request<'_>,
+    response: &mut [u8],
+) -> core::result::Result<usize, Status>;
+
+/// An entry in the server's command table.
+///
+/// Everything except the handler is served to the Host by the
+/// [`crate::system::DESCRIBE`] command, so generic Host tools can discover
+/// the Target's commands.
+pub struct Command<C> {
+    /// Command ID, the first byte of the command
+    pub id: u8,
+    /// Human readable name, for logging and introspection
+    pub name: &'static str,
+    /// Handler function
+    pub handler: Handler<C>,
+    /// Command version, incremented when the command's payloads change
+    pub version: u8,
+    /// Description of the command payload, empty if not provided
+    pub request_schema: &'static str,
+    /// Description of the response payload, empty if not provided
+    pub response_schema: &'static str,
+}
+
+impl<C> Command<C> {
+    /// Create a new command table entry, with version 1 and no schema
+    pub const fn new(id: u8, name: &'static str, handler: Handler<C>) -> Self {
+        Self {
+            id,
+            name,
+            handler,
+            version: 1,
+            request_schema: "",
+            response_schema: "",
+        }
+    }
+
+    /// Set the command's version
+    pub const fn with_version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Set descriptions of the command's payloads, for example
+    /// `"(index: u8, on: bool)"` and `"Option<bool>"`.  The format is
+    /// application-defined.
+    pub const fn with_schema(
+        mut self,
+        request_schema: &'static str,
+        response_schema: &'static str,
+    ) -> Self {
+        self.request_schema = request_schema;
+        self.response_schema = response_schema;
+        self
+    }
+
+    /// This command's [`CommandInfo`]
+    pub fn info(&self) -> CommandInfo<'static> {
+        CommandInfo {
+            id: self.id,
+            version: self.version,
+            name: self.name,
+            request_schema: self.request_schema,
+            response_schema: self.response_schema,
+        }
+    }
+}
+
+/// Description of a command, as served by the [`crate::system::DESCRIBE`]
+/// command.
+///
+/// Encoded as the ID and version bytes, followed by the name, request schema
+/// and response schema, each a `u8` length followed by UTF-8 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandInfo<'a> {
+    /// Command ID
+    pub id: u8,
+    /// Command version
+    pub version: u8,
+    /// Command name
+    pub name: &'a str,
+    /// Description of the command payload
+    pub request_schema: &'a str,
+    /// Description of the response payload
+    pub response_schema: &'a str,
+}
+
+impl<'a> CommandInfo<'a> {
+    /// Encode into `buf`, truncating each string to 255 bytes.
+    ///
+    /// Returns the number of bytes written, or `None` if `buf` is too small.
+    pub fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        buf.get_mut(..2)?.copy_from_slice(&[self.id, self.version]);
+        let mut len = 2;
+        for value in [self.name, self.request_schema, self.response_schema] {
+            len += system::encode_str(value, &mut buf[len..])?;
+        }
+        Some(len)
+    }
+
+    /// Decode from `buf`.
+    ///
+    /// Returns `None` if `buf` is malformed.
+    pub fn decode(buf: &'a [u8]) -> Option<Self> {
+        let (id, version) = (*buf.first()?, *buf.get(1)?);
+        let (name, rest) = system::decode_str(&buf[2..])?;
+        let (request_schema, rest) = system::decode_str(rest)?;
+        let (response_schema, _) = system::decode_str(rest)?;
+        Some(Self {
+            id,
+            version,
+            name,
+            request_schema,
+            response_schema,
+        })
+    }
+}
+
+/// A command passed to a [`Handler`].
+pub struct Request<'r> {
+    id: u8,
+    payload: &'r [u8],
+    max_payload: usize,
+    cancel: &'r mut dyn CancelSource,
+}
+
+impl Request<'_> {
+    /// Command ID
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Command payload, excluding the command ID
+    pub fn payload(&self) -> &[u8] {
+        self.payload
+    }
+
+    /// Largest command payload, excluding the command ID, the server can
+    /// receive.  Limited by both the server's buffer and the command channel.
+    /// Useful for telling the Host how much data it may send per command.
+    pub fn max_payload(&self) -> usize {
+        self.max_payload
+    }
+
+    /// Whether the Host has asked for this command to be cancelled.
+    /// Long-running handlers should poll this, and return
+    /// [`Status::Cancelled`] if set.
+    pub fn cancel_requested(&mut self) -> bool {
+        self.cancel.cancel_requested()
+    }
+}
+
+#[cfg(feature = "codec")]
+impl<'r> Request<'r> {
+    /// Decode the command payload as a value using codec `C`.
+    ///
+    /// Returns [`Status::InvalidRequest`] if the payload could not be
+    /// decoded, so handlers can use `?`.
+    pub fn decode<C: Codec, T: Deserialize<'r>>(&self) -> core::result::Result<T, Status> {
+        C::decode(self.payload).map_err(|_| Status::InvalidRequest)
+    }
+}
+
+/// Encode a value as a handler's response payload using codec `C`.
+///
+/// Returns the number of bytes written, for returning from the handler,
+/// [`Status::ResponseTooLarge`] if the value doesn't fit in `response`, or
+/// [`Status::HandlerError`] if the value could not be encoded.
+///
+/// ```rust,ignore
+/// fn read_sensor(app: &mut App, req: &mut Request<'_>, rsp: &mut [u8]) -> Result<usize, Status> {
+///     let channel: u8 = req.decode::<DefaultCodec, _>()?;
+///     encode_response::<DefaultCodec, _>(&app.sensor(channel), rsp)
+/// }
+/// ```
+#[cfg(feature = "codec")]
+pub fn encode_response<C: Codec, T: Serialize + ?Sized>(
+    value: &T,
+    response: &mut [u8],
+) -> core::result::Result<usize, Status> {
+    C::encode(value, response).map_err(|e| match e {
+        Error::BufferTooSmall => Status::ResponseTooLarge,
+        _ => {
+            warn!("Failed to encode response: {e:?}");
+            Status::HandlerError
+        }
+    })
+}
+
+// Allows Request to check for cancellation without being generic over the
+// channel's I/O type
+trait CancelSource {
+    fn cancel_requested(&mut self) -> bool;
+}
+
+impl<I: ChannelIo, B: BorrowMut<I>> CancelSource for GenericChannel<I, B> {
+    fn cancel_requested(&mut self) -> bool {
+        GenericChannel::cancel_requested(self).unwrap_or(false)
+    }
+}
+
+/// RPC server, dispatching commands to handlers from a static table.
+///
+/// Arguments:
+/// - `I` - Channel I/O type, typically [`crate::channel::RamChannelIo`]
+/// - `C` - Application context type, passed to handlers
+/// - `N` - Size of the command and response buffers, in bytes, including the
+///   command ID and status bytes.  Must be at least 1.
+/// - `B` - How the channels access `I`.  By default they borrow it, as
+///   [`crate::channel::Channel`], or they can own it, as
+///   [`crate::channel::OwnedChannel`].
+///
+/// See the [module documentation](self) for an example.
+pub struct RpcServer<'a, I: ChannelIo, C, const N: usize, B: BorrowMut<I> = &'a mut I> {
+    cmd_ch: GenericChannel<I, B>,
+    rsp_ch: GenericChannel<I, B>,
+    tables: [&'a [Command<C>]; MAX_TABLES],
+    num_tables: usize,
+    cmd_buf: [u8; N],
+    rsp_buf: [u8; N],
+    // Length of a response which is waiting for the response channel
+    pending_rsp: Option<usize>,
+}
+
+/// Maximum number of command tables an [`RpcServer`] can have mounted,
+/// including the one passed to [`RpcServer::new()`]
+pub const MAX_TABLES: usize = 4;
+
+impl<'a, I: ChannelIo, C, const N: usize, B: BorrowMut<I>> RpcServer<'a, I, C, N, B> {
+    // Evaluated at compile time when referenced from new().  The buffers
+    // must at least hold a status byte.
+    const VALID: () = assert!(N >= 1, "RpcServer buffer size N must be at least 1");
+
+    /// Create a new RpcServer
+    ///
+    /// Arguments:
+    /// - `cmd_ch` - Command channel, which must be a Consumer
+    /// - `rsp_ch` - Response channel, which must be a Producer
+    /// - `commands` - Command table
+    pub fn new(
+        cmd_ch: GenericChannel<I, B>,
+        rsp_ch: GenericChannel<I, B>,
+        commands: &'a [Command<C>],
+    ) -> Self {
+        let () = Self::VALID;
+        Self {
+            cmd_ch,
+            rsp_ch,
+            tables: [commands; MAX_TABLES],
+            num_tables: 1,
+            cmd_buf: [0; N],
+            rsp_buf: [0; N],
+            pending_rsp: None,
+        }
+    }
+
+    /// Mount an additional command table, for example
+    /// [`crate::system::commands()`].  Tables are searched in the order they
+    /// were mounted, so if IDs clash the earliest mounted command is used.
+    ///
+    /// Returns [`Error::BufferTooSmall`] if [`MAX_TABLES`] tables are
+    /// already mounted.
+    pub fn mount(&mut self, commands: &'a [Command<C>]) -> Result<()> {
+        let table = self
+            .tables
+            .get_mut(self.num_tables)
+            .ok_or(Error::BufferTooSmall)?;
+        *table = commands;
+        self.num_tables += 1;
+        Ok(())
+    }
+
+    /// Handle at most one command, if available.  Does not block, so is
+    /// suitable for calling from a superloop.
+    ///
+    /// If the previous response has not yet been consumed by the Host, no
+    /// new command is handled until it has.
+    ///
+    /// Returns `Ok(true)` if a command was handled.
+    pub fn poll(&mut self, ctx: &mut C) -> Result<bool> {
+        // Deliver any response still waiting for the Host to consume the
+        // previous one
+        if !self.send_pending()? {
+            return Ok(false);
+        }
+
+        let Some(size) = self.cmd_ch.data_available()? else {
+            return Ok(false);
+        };
+
+        // Commands must fit in both our buffer and the command channel, and
+        // responses in both our buffer and the response channel
+        let cmd_limit = N.min(self.cmd_ch.data_capacity()?);
+        let rsp_limit = N.min(self.rsp_ch.data_capacity()?);
+        if rsp_limit == 0 {
+            return Err(Error::BufferTooSmall);
+        }
+
+        if size == 0 || size > cmd_limit {
+            warn!("Discarding invalid command ({size} bytes)");
+            self.cmd_ch.discard()?;
+            self.queue_status(if size == 0 {
+                Status::InvalidRequest
+            } else {
+                Status::CommandTooLarge
+            });
+            self.send_pending()?;
+            return Ok(true);
+        }
+
+        let size = self.cmd_ch.consume_bytes(&mut self.cmd_buf)?;
+        let id = self.cmd_buf[0];
+
+        // Commands handled by the server itself
+        let tables = self.tables;
+        let tables = &tables[..self.num_tables];
+        let result = match id {
+            system::DESCRIBE => Some(describe(
+                tables,
+                &self.cmd_buf[1..size],
+                &mut self.rsp_buf[1..rsp_limit],
+            )),
+            system::LIMITS => Some(
+                ServerLimits {
+                    max_command: cmd_limit.saturating_sub(1) as u32,
+                    max_response: (rsp_limit - 1) as u32,
+                }
+                .encode(&mut self.rsp_buf[1..rsp_limit])
+                .ok_or(Status::ResponseTooLarge),
+            ),
+            _ => None,
+        };
+        if let Some(result) = result {
+            match result {
+                Ok(len) => {
+                    self.rsp_buf[0] = Status::Ok as u8;
+                    self.pending_rsp = Some(len + 1);
+                }
+                Err(status) => self.queue_status(status),
+            }
+            self.send_pending()?;
+            return Ok(true);
+        }
+
+        let Some(command) = tables
+            .iter()
+            .flat_map(|table| table.iter())
+            .find(|command| command.id == id)
+        else {
+            debug!("Unknown command {id:#04X}");
+            self.queue_status(Status::UnknownCommand);
+            self.send_pending()?;
+            return Ok(true);
+        };
+
+        trace!("Handling command {} ({id:#04X})", command.name);
+        let mut request = Request {
+            id,
+            payload: &self.cmd_buf[1..size],
+            max_payload: cmd_limit.saturating_sub(1),
+            cancel: &mut self.cmd_ch,
+        };
+        let result = (command.handler)(ctx, &mut request, &mut self.rsp_buf[1..rsp_limit]);
+
+        match result {
+            Ok(len) if len < rsp_limit => {
+                self.rsp_buf[0] = Status::Ok as u8;
+                self.pending_rsp = Some(len + 1);
+            }
+            Ok(len) => {
+                warn!("Command {} response too large ({len} bytes)", command.name);
+                self.queue_status(Status::ResponseTooLarge);
+            }
+            Err(Status::Cancelled) => {
+                debug!("Command {} cancelled", command.name);
+                self.pending_rsp = Some(0);
+            }
+            Err(status) => {
+                debug!("Command {} failed {status:?}", command.name);
+                self.queue_status(status);
+            }
+        }
+        self.send_pending()?;
+
+        Ok(true)
+    }
+
+    /// Access the command channel
+    pub fn cmd_channel(&mut self) -> &mut GenericChannel<I, B> {
+        &mut self.cmd_ch
+    }
+
+    /// Access the response channel
+    pub fn rsp_channel(&mut self) -> &mut GenericChannel<I, B> {
+        &mut self.rsp_ch
+    }
+
+    fn queue_status(&mut self, status: Status) {
+        self.rsp_buf[0] = status as u8;
+        self.pending_rsp = Some(1);
+    }
+
+    // Attempts to publish the pending response.  A length of 0 indicates a
+    // cancellation acknowledgement.  Returns whether there is now no pending
+    // response.
+    fn send_pending(&mut self) -> Result<bool> {
+        let Some(len) = self.pending_rsp else {
+            return Ok(true);
+        };
+
+        let result = if len == 0 {
+            self.rsp_ch.publish_cancelled()
+        } else {
+            self.rsp_ch.publish_bytes(&self.rsp_buf[..len])
+        };
+
+        match result {
+            Ok(()) => {
+                self.pending_rsp = None;
+                Ok(true)
+            }
+            Err(Error::Busy) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// Handles the DESCRIBE command.  The command payload is the index of the
+// command to describe, as a little-endian u16, and the response is the total
+// number of commands, as a little-endian u16, followed by the command's
+// CommandInfo, if the index is in range.
+fn describe<C>(
+    tables: &[&[Command<C>]],
+    payload: &[u8],
+    response: &mut [u8],
+) -> core::result::Result<usize, Status> {
+    let index = payload
+        .get(..2)
+        .map(|index| u16::from_le_bytes([index[0], index[1]]) as usize)
+        .ok_or(Status::InvalidRequest)?;
+
+    let mut commands = tables.iter().flat_map(|table| table.iter());
+    let total = commands.clone().count().min(u16::MAX as usize);
+    response
+        .get_mut(..2)
+        .ok_or(Status::ResponseTooLarge)?
+        .copy_from_slice(&(total as u16).to_le_bytes());
+
+    // Out of range indexes just get the count
+    let Some(command) = commands.nth(index) else {
+        return Ok(2);
+    };
+    let len = command
+        .info()
+        .encode(&mut response[2..])
+        .ok_or(Status::ResponseTooLarge)?;
+    Ok(2 + len)
+}
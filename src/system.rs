@@ -0,0 +1,293 @@
+//! System service - standard housekeeping commands for every Target.
+//!
+//! The system service occupies a reserved range of [`RpcServer`](crate::server::RpcServer) command
+//! IDs, from [`RESERVED_BASE`] upwards, so it can be mounted alongside an
+//! application's own commands using [`RpcServer::mount()`](crate::server::RpcServer::mount).
+//!
+//! | ID     | Command      | Command payload      | Response payload      |
+//! |--------|--------------|----------------------|-----------------------|
+//! | `0xF0` | [`PING`]     | any                  | command payload       |
+//! | `0xF1` | [`VERSION`]  | none                 | [`VersionInfo`]       |
+//! | `0xF2` | [`UPTIME`]   | none                 | `u64` milliseconds    |
+//! | `0xF3` | [`MEMORY`]   | none                 | [`MemoryInfo`]        |
+//! | `0xF4` | [`RESET`]    | none                 | none                  |
+//! | `0xF5` | [`SET_TIME`] | `u64` Unix time (ms) | none                  |
+//! | `0xFE` | [`LIMITS`]   | none                 | [`ServerLimits`]      |
+//! | `0xFF` | [`DESCRIBE`] | `u16` index          | `u16` count, [`crate::server::CommandInfo`] |
+//!
+//! All integers are little-endian.  [`VersionInfo`] is encoded as two
+//! strings, each a `u8` length followed by UTF-8 bytes.  [`MemoryInfo`] is
+//! encoded as two `u32`s, `free_ram` then `stack_high_water`.  [`ServerLimits`]
+//! is encoded as two `u32`s, `max_command` then `max_response`.
+//!
+//! IDs `0xF6` to `0xFA` are used by the firmware update service, in
+//! `crate::update`, behind the `update` feature.
+//!
+//! [`DESCRIBE`] and [`LIMITS`] are handled by
+//! [`RpcServer`](crate::server::RpcServer) itself, describing every command
+//! in its mounted tables and the payload sizes it can handle, so are
+//! available even if the system service isn't mounted.
+//!
+//! The encoding does not need the `codec` feature, so the service is
+//! available to every Target.
+//!
+//! The Target implements [`System`] on its [`RpcServer`](crate::server::RpcServer) context, and mounts
+//! [`commands()`]:
+//!
+//! ```rust,ignore
+//! use airfrog_rpc::system::{self, MemoryInfo, System, VersionInfo};
+//!
+//! impl System for App {
+//!     fn version(&self) -> VersionInfo<'_> {
+//!         VersionInfo { version: env!("CARGO_PKG_VERSION"), build: BUILD_ID }
+//!     }
+//!     fn uptime_ms(&mut self) -> u64 { self.timer.now_ms() }
+//!     fn reset(&mut self) -> Result<(), Status> { self.reset_pending = true; Ok(()) }
+//!     fn set_time(&mut self, unix_ms: u64) { self.clock.set(unix_ms) }
+//! }
+//!
+//! let mut server: RpcServer<_, App, 256> = RpcServer::new(cmd_ch, rsp_ch, APP_COMMANDS);
+//! server.mount(system::commands())?;
+//! ```
+//!
+//! The Host uses [`crate::client::AsyncRpcClient::ping()`] and friends.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::server::{Command, Request, Status};
+
+/// First command ID reserved for the system service.  Applications must not
+/// use IDs from here upwards.
+pub const RESERVED_BASE: u8 = 0xF0;
+
+/// Echo the command payload
+pub const PING: u8 = RESERVED_BASE;
+/// Get firmware version and build information
+pub const VERSION: u8 = RESERVED_BASE + 1;
+/// Get time since boot
+pub const UPTIME: u8 = RESERVED_BASE + 2;
+/// Get free RAM and stack high-water mark
+pub const MEMORY: u8 = RESERVED_BASE + 3;
+/// Request a reset
+pub const RESET: u8 = RESERVED_BASE + 4;
+/// Set the Target's wall-clock time
+pub const SET_TIME: u8 = RESERVED_BASE + 5;
+/// Get the largest command and response payloads the Target can handle
+pub const LIMITS: u8 = 0xFE;
+/// Describe one of the Target's commands
+pub const DESCRIBE: u8 = 0xFF;
+
+/// Firmware version and build information
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionInfo<'a> {
+    /// Firmware version, for example `"1.2.3"`
+    pub version: &'a str,
+    /// Build information, for example a git hash or build date
+    pub build: &'a str,
+}
+
+impl<'a> VersionInfo<'a> {
+    /// Encode into `buf`, truncating each string to 255 bytes.
+    ///
+    /// Returns the number of bytes written, or `None` if `buf` is too small.
+    pub fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        let mut len = encode_str(self.version, buf)?;
+        len += encode_str(self.build, &mut buf[len..])?;
+        Some(len)
+    }
+
+    /// Decode from `buf`.
+    ///
+    /// Returns `None` if `buf` is malformed.
+    pub fn decode(buf: &'a [u8]) -> Option<Self> {
+        let (version, rest) = decode_str(buf)?;
+        let (build, _) = decode_str(rest)?;
+        Some(Self { version, build })
+    }
+}
+
+/// Target memory usage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryInfo {
+    /// Free RAM, in bytes
+    pub free_ram: u32,
+    /// Maximum stack used since boot, in bytes
+    pub stack_high_water: u32,
+}
+
+impl MemoryInfo {
+    /// Size of the encoded value, in bytes
+    pub const SIZE: usize = 8;
+
+    /// Encode into `buf`.
+    ///
+    /// Returns the number of bytes written, or `None` if `buf` is too small.
+    pub fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        let buf = buf.get_mut(..Self::SIZE)?;
+        buf[..4].copy_from_slice(&self.free_ram.to_le_bytes());
+        buf[4..].copy_from_slice(&self.stack_high_water.to_le_bytes());
+        Some(Self::SIZE)
+    }
+
+    /// Decode from `buf`.
+    ///
+    /// Returns `None` if `buf` is too short.
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        let buf = buf.get(..Self::SIZE)?;
+        Some(Self {
+            free_ram: u32::from_le_bytes(buf[..4].try_into().ok()?),
+            stack_high_water: u32::from_le_bytes(buf[4..].try_into().ok()?),
+        })
+    }
+}
+
+/// Largest payloads an [`RpcServer`](crate::server::RpcServer) can handle,
+/// limited by both its buffers and its channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerLimits {
+    /// Largest command payload, in bytes, excluding the command ID
+    pub max_command: u32,
+    /// Largest response payload, in bytes, excluding the status
+    pub max_response: u32,
+}
+
+impl ServerLimits {
+    /// Size of the encoded value, in bytes
+    pub const SIZE: usize = 8;
+
+    /// Encode into `buf`.
+    ///
+    /// Returns the number of bytes written, or `None` if `buf` is too small.
+    pub fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        let buf = buf.get_mut(..Self::SIZE)?;
+        buf[..4].copy_from_slice(&self.max_command.to_le_bytes());
+        buf[4..].copy_from_slice(&self.max_response.to_le_bytes());
+        Some(Self::SIZE)
+    }
+
+    /// Decode from `buf`.
+    ///
+    /// Returns `None` if `buf` is too short.
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        let buf = buf.get(..Self::SIZE)?;
+        Some(Self {
+            max_command: u32::from_le_bytes(buf[..4].try_into().ok()?),
+            max_response: u32::from_le_bytes(buf[4..].try_into().ok()?),
+        })
+    }
+}
+
+/// System service implementation, provided by the Target's [`RpcServer`](crate::server::RpcServer)
+/// context.
+pub trait System {
+    /// Firmware version and build information
+    fn version(&self) -> VersionInfo<'_>;
+
+    /// Milliseconds since boot
+    fn uptime_ms(&mut self) -> u64;
+
+    /// Memory usage.  The default implementation reports zeros, for Targets
+    /// which don't track it.
+    fn memory(&mut self) -> MemoryInfo {
+        MemoryInfo::default()
+    }
+
+    /// Request a reset.  The response is sent after this returns, so the
+    /// Target should record the request and reset once the Host has consumed
+    /// the response, rather than resetting immediately.
+    ///
+    /// The default implementation returns [`Status::HandlerError`], for
+    /// Targets which don't support being reset.
+    fn reset(&mut self) -> Result<(), Status> {
+        Err(Status::HandlerError)
+    }
+
+    /// Set the wall-clock time, as milliseconds since the Unix epoch
+    fn set_time(&mut self, unix_ms: u64);
+}
+
+/// Command table for the system service, to be mounted on an
+/// [`RpcServer`](crate::server::RpcServer) whose context implements [`System`].
+pub fn commands<C: System + 'static>() -> &'static [Command<C>] {
+    Commands::<C>::LIST
+}
+
+// Allows a static command table generic over the context type
+struct Commands<C>(core::marker::PhantomData<C>);
+
+impl<C: System + 'static> Commands<C> {
+    const LIST: &'static [Command<C>] = &[
+        Command::new(PING, "ping", ping::<C>).with_schema("[u8]", "[u8]"),
+        Command::new(VERSION, "version", version::<C>).with_schema("()", "VersionInfo"),
+        Command::new(UPTIME, "uptime", uptime::<C>).with_schema("()", "u64"),
+        Command::new(MEMORY, "memory", memory::<C>).with_schema("()", "MemoryInfo"),
+        Command::new(RESET, "reset", reset::<C>).with_schema("()", "()"),
+        Command::new(SET_TIME, "set_time", set_time::<C>).with_schema("u64", "()"),
+    ];
+}
+
+fn ping<C: System>(_: &mut C, req: &mut Request<'_>, rsp: &mut [u8]) -> Result<usize, Status> {
+    let payload = req.payload();
+    rsp.get_mut(..payload.len())
+        .ok_or(Status::ResponseTooLarge)?
+        .copy_from_slice(payload);
+    Ok(payload.len())
+}
+
+fn version<C: System>(ctx: &mut C, _: &mut Request<'_>, rsp: &mut [u8]) -> Result<usize, Status> {
+    ctx.version().encode(rsp).ok_or(Status::ResponseTooLarge)
+}
+
+fn uptime<C: System>(ctx: &mut C, _: &mut Request<'_>, rsp: &mut [u8]) -> Result<usize, Status> {
+    encode_u64(ctx.uptime_ms(), rsp)
+}
+
+fn memory<C: System>(ctx: &mut C, _: &mut Request<'_>, rsp: &mut [u8]) -> Result<usize, Status> {
+    ctx.memory().encode(rsp).ok_or(Status::ResponseTooLarge)
+}
+
+fn reset<C: System>(ctx: &mut C, _: &mut Request<'_>, _: &mut [u8]) -> Result<usize, Status> {
+    info!("Reset requested");
+    ctx.reset().map(|_| 0)
+}
+
+fn set_time<C: System>(ctx: &mut C, req: &mut Request<'_>, _: &mut [u8]) -> Result<usize, Status> {
+    let unix_ms = decode_u64(req.payload()).ok_or(Status::InvalidRequest)?;
+    ctx.set_time(unix_ms);
+    Ok(0)
+}
+
+fn encode_u64(value: u64, buf: &mut [u8]) -> Result<usize, Status> {
+    buf.get_mut(..8)
+        .ok_or(Status::ResponseTooLarge)?
+        .copy_from_slice(&value.to_le_bytes());
+    Ok(8)
+}
+
+pub(crate) fn decode_u64(buf: &[u8]) -> Option<u64> {
+    Some(u64::from_le_bytes(buf.get(..8)?.try_into().ok()?))
+}
+
+pub(crate) fn encode_str(value: &str, buf: &mut [u8]) -> Option<usize> {
+    // Truncate on a character boundary, so the result is still valid UTF-8
+    let mut len = value.len().min(u8::MAX as usize);
+    while !value.is_char_boundary(len) {
+        len -= 1;
+    }
+    let bytes = &value.as_bytes()[..len];
+    *buf.first_mut()? = bytes.len() as u8;
+    buf.get_mut(1..1 + bytes.len())?.copy_from_slice(bytes);
+    Some(1 + bytes.len())
+}
+
+pub(crate) fn decode_str(buf: &[u8]) -> Option<(&str, &[u8])> {
+    let len = *buf.first()? as usize;
+    let bytes = buf.get(1..1 + len)?;
+    let value = core::str::from_utf8(bytes).ok()?;
+    Some((value, &buf[1 + len..]))
+}
@@ -0,0 +1,512 @@
+//! Firmware update service - updates a running Target's firmware over the
+//! RPC channels, without halting it.
+//!
+//! The Target receives the new image in chunks, writing it to an inactive
+//! flash bank through an `embedded-storage` [`NorFlash`] backend.  Once the
+//! whole image has been received, the Target reads it back, checks it with
+//! an [`ImageVerifier`] (for example a SHA-256 hash, or a signature), and
+//! marks the bank bootable.
+//!
+//! Like the [system service](crate::system), the update commands use
+//! reserved command IDs, so can be mounted alongside an application's own
+//! commands using [`RpcServer::mount()`](crate::server::RpcServer::mount).
+//!
+//! | ID     | Command           | Command payload                   | Response payload                       |
+//! |--------|-------------------|-----------------------------------|----------------------------------------|
+//! | `0xF6` | [`UPDATE_BEGIN`]  | `u32` image size, expected digest | [`UpdateStatus`], `u32` max chunk size |
+//! | `0xF7` | [`UPDATE_WRITE`]  | `u32` offset, data                | [`UpdateStatus`]                       |
+//! | `0xF8` | [`UPDATE_STATUS`] | none                              | [`UpdateStatus`]                       |
+//! | `0xF9` | [`UPDATE_FINISH`] | none                              | [`UpdateStatus`]                       |
+//! | `0xFA` | [`UPDATE_ABORT`]  | none                              | [`UpdateStatus`]                       |
+//!
+//! Transfers are resumable.  [`UPDATE_BEGIN`] with the same image size and
+//! digest as the transfer in progress continues it from
+//! [`UpdateStatus::written`], rather than starting again.  Writes must start
+//! at [`UpdateStatus::written`], and be a multiple of
+//! [`UpdateStatus::write_size`] bytes, with the final chunk padded with
+//! `0xFF`.  Repeated writes of data already written are acknowledged, so a
+//! write whose response was lost can be retried.
+//!
+//! Each write's data must be no larger than the maximum chunk size returned
+//! by [`UPDATE_BEGIN`], which is the largest multiple of
+//! [`UpdateStatus::write_size`] that fits in a command the Target's
+//! [`RpcServer`](crate::server::RpcServer) can receive.
+//!
+//! The transfer's progress is held in the [`Updater`], in RAM, so a transfer
+//! can only be resumed while the Target keeps running.  If the Target resets
+//! part way through, the next [`UPDATE_BEGIN`] starts the transfer again from
+//! the beginning.
+//!
+//! The Target implements [`FirmwareUpdate`] on its
+//! [`RpcServer`](crate::server::RpcServer) context, and mounts
+//! [`commands()`]:
+//!
+//! ```rust,ignore
+//! use airfrog_rpc::update::{self, FirmwareUpdate, Sha256Verifier, Updater};
+//!
+//! struct App {
+//!     updater: Updater<Flash, Sha256Verifier>,
+//! }
+//!
+//! impl FirmwareUpdate for App {
+//!     type Flash = Flash;
+//!     type Verifier = Sha256Verifier;
+//!     fn updater(&mut self) -> &mut Updater<Flash, Sha256Verifier> { &mut self.updater }
+//!     fn mark_bootable(&mut self, image_size: u32) -> Result<(), Status> {
+//!         self.boot_config.swap_banks(image_size).map_err(|_| Status::HandlerError)
+//!     }
+//! }
+//!
+//! let updater = Updater::new(flash, Sha256Verifier::new(), INACTIVE_BANK, BANK_SIZE)?;
+//! server.mount(update::commands())?;
+//! ```
+//!
+//! The Host uses [`crate::client::AsyncRpcClient::update_firmware()`].
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use embedded_storage::nor_flash::NorFlash;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::server::{Command, Request, Status};
+use crate::system::RESERVED_BASE;
+use crate::{Error, Result};
+
+/// Start or resume an update
+pub const UPDATE_BEGIN: u8 = RESERVED_BASE + 6;
+/// Write a chunk of the image
+pub const UPDATE_WRITE: u8 = RESERVED_BASE + 7;
+/// Get the update's progress
+pub const UPDATE_STATUS: u8 = RESERVED_BASE + 8;
+/// Verify the image and mark it bootable
+pub const UPDATE_FINISH: u8 = RESERVED_BASE + 9;
+/// Abandon the update
+pub const UPDATE_ABORT: u8 = RESERVED_BASE + 10;
+
+/// Maximum size of an expected digest or signature, in bytes
+pub const MAX_DIGEST: usize = 64;
+
+// Size of the buffer used to read back the image for verification
+const VERIFY_CHUNK: usize = 64;
+
+/// State of an update
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateState {
+    /// No update in progress
+    Idle = 0,
+    /// Receiving the image
+    Receiving = 1,
+    /// Image verified, but not yet marked bootable
+    Verified = 2,
+    /// Image verified and marked bootable
+    Complete = 3,
+    /// Image failed verification
+    Failed = 4,
+}
+
+impl From<u8> for UpdateState {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => UpdateState::Idle,
+            1 => UpdateState::Receiving,
+            2 => UpdateState::Verified,
+            3 => UpdateState::Complete,
+            _ => UpdateState::Failed,
+        }
+    }
+}
+
+/// Progress of an update, returned by every update command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateStatus {
+    /// Update state
+    pub state: UpdateState,
+    /// Writes must be a multiple of this size, in bytes
+    pub write_size: u16,
+    /// Size of the image being received, in bytes
+    pub image_size: u32,
+    /// Bytes of the image written so far, and the offset of the next write
+    pub written: u32,
+}
+
+impl UpdateStatus {
+    /// Size of the encoded value, in bytes
+    pub const SIZE: usize = 11;
+
+    /// Encode into `buf`.
+    ///
+    /// Returns the number of bytes written, or `None` if `buf` is too small.
+    pub fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        let buf = buf.get_mut(..Self::SIZE)?;
+        buf[0] = self.state as u8;
+        buf[1..3].copy_from_slice(&self.write_size.to_le_bytes());
+        buf[3..7].copy_from_slice(&self.image_size.to_le_bytes());
+        buf[7..11].copy_from_slice(&self.written.to_le_bytes());
+        Some(Self::SIZE)
+    }
+
+    /// Decode from `buf`.
+    ///
+    /// Returns `None` if `buf` is too short.
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        let buf = buf.get(..Self::SIZE)?;
+        Some(Self {
+            state: UpdateState::from(buf[0]),
+            write_size: u16::from_le_bytes(buf[1..3].try_into().ok()?),
+            image_size: u32::from_le_bytes(buf[3..7].try_into().ok()?),
+            written: u32::from_le_bytes(buf[7..11].try_into().ok()?),
+        })
+    }
+}
+
+/// Checks a received image against the digest or signature supplied by the
+/// Host.
+pub trait ImageVerifier {
+    /// Prepare to verify a new image
+    fn reset(&mut self);
+
+    /// Add the next part of the image
+    fn update(&mut self, data: &[u8]);
+
+    /// Whether the image matches `expected`, as supplied to
+    /// [`UPDATE_BEGIN`]
+    fn verify(&mut self, expected: &[u8]) -> bool;
+}
+
+/// Verifies images using their SHA-256 hash
+#[cfg(feature = "sha2")]
+#[derive(Clone, Default)]
+pub struct Sha256Verifier {
+    hasher: sha2::Sha256,
+}
+
+#[cfg(feature = "sha2")]
+impl Sha256Verifier {
+    /// Create a new Sha256Verifier
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(feature = "sha2")]
+impl ImageVerifier for Sha256Verifier {
+    fn reset(&mut self) {
+        sha2::Digest::reset(&mut self.hasher);
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(&mut self.hasher, data);
+    }
+
+    fn verify(&mut self, expected: &[u8]) -> bool {
+        sha2::Digest::finalize_reset(&mut self.hasher).as_slice() == expected
+    }
+}
+
+/// Receives an image into an inactive flash bank.
+///
+/// Arguments:
+/// - `F` - Flash backend
+/// - `V` - Image verifier
+pub struct Updater<F: NorFlash, V: ImageVerifier> {
+    flash: F,
+    verifier: V,
+    bank_offset: u32,
+    bank_size: u32,
+    state: UpdateState,
+    image_size: u32,
+    written: u32,
+    // Bank offset up to which the bank has been erased
+    erased_to: u32,
+    digest: [u8; MAX_DIGEST],
+    digest_len: usize,
+}
+
+impl<F: NorFlash, V: ImageVerifier> Updater<F, V> {
+    /// Create a new Updater
+    ///
+    /// Arguments:
+    /// - `flash` - Flash backend
+    /// - `verifier` - Image verifier
+    /// - `bank_offset` - Offset of the inactive bank within `flash`
+    /// - `bank_size` - Size of the inactive bank, in bytes
+    ///
+    /// Returns [`Error::NotAligned`] if the bank is not aligned to the
+    /// flash's erase size, [`Error::InvalidOperation`] if the bank extends
+    /// beyond the end of the flash, or [`Error::BufferTooSmall`] if the
+    /// flash's read size is larger than supported.
+    pub fn new(flash: F, verifier: V, bank_offset: u32, bank_size: u32) -> Result<Self> {
+        let erase_size = F::ERASE_SIZE as u32;
+        if !bank_offset.is_multiple_of(erase_size) || !bank_size.is_multiple_of(erase_size) {
+            return Err(Error::NotAligned);
+        }
+        let bank_end = bank_offset.checked_add(bank_size);
+        if bank_end.is_none_or(|end| end as usize > flash.capacity()) {
+            warn!(
+                "Update bank {bank_offset:#010X} ({bank_size} bytes) exceeds flash capacity {}",
+                flash.capacity()
+            );
+            return Err(Error::InvalidOperation);
+        }
+        if F::READ_SIZE > VERIFY_CHUNK || !VERIFY_CHUNK.is_multiple_of(F::READ_SIZE) {
+            return Err(Error::BufferTooSmall);
+        }
+
+        Ok(Self {
+            flash,
+            verifier,
+            bank_offset,
+            bank_size,
+            state: UpdateState::Idle,
+            image_size: 0,
+            written: 0,
+            erased_to: 0,
+            digest: [0; MAX_DIGEST],
+            digest_len: 0,
+        })
+    }
+
+    /// Progress of the current update
+    pub fn status(&self) -> UpdateStatus {
+        UpdateStatus {
+            state: self.state,
+            write_size: F::WRITE_SIZE as u16,
+            image_size: self.image_size,
+            written: self.written.min(self.image_size),
+        }
+    }
+
+    /// Access the flash backend
+    pub fn flash(&mut self) -> &mut F {
+        &mut self.flash
+    }
+
+    fn begin(&mut self, image_size: u32, digest: &[u8]) -> core::result::Result<(), Status> {
+        if image_size == 0 || image_size > self.bank_size || digest.len() > MAX_DIGEST {
+            return Err(Status::InvalidRequest);
+        }
+
+        if self.state == UpdateState::Receiving
+            && self.image_size == image_size
+            && self.digest[..self.digest_len] == *digest
+        {
+            info!("Resuming update at {} of {image_size} bytes", self.written);
+            return Ok(());
+        }
+
+        info!("Starting update of {image_size} bytes");
+        self.state = UpdateState::Receiving;
+        self.image_size = image_size;
+        self.written = 0;
+        self.erased_to = 0;
+        self.digest[..digest.len()].copy_from_slice(digest);
+        self.digest_len = digest.len();
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> core::result::Result<(), Status> {
+        if self.state != UpdateState::Receiving {
+            return Err(Status::InvalidRequest);
+        }
+
+        let end = offset
+            .checked_add(data.len() as u32)
+            .ok_or(Status::InvalidRequest)?;
+        if end <= self.written {
+            debug!("Ignoring repeated write at {offset}");
+            return Ok(());
+        }
+        let padded_size = self.image_size.next_multiple_of(F::WRITE_SIZE as u32);
+        if offset != self.written || !data.len().is_multiple_of(F::WRITE_SIZE) || end > padded_size
+        {
+            warn!("Invalid update write at {offset} ({} bytes)", data.len());
+            return Err(Status::InvalidRequest);
+        }
+
+        while self.erased_to < end {
+            let from = self.bank_offset + self.erased_to;
+            let to = from + F::ERASE_SIZE as u32;
+            self.flash.erase(from, to).map_err(|_| {
+                error!("Failed to erase flash at {from:#010X}");
+                Status::HandlerError
+            })?;
+            self.erased_to += F::ERASE_SIZE as u32;
+        }
+
+        self.flash
+            .write(self.bank_offset + offset, data)
+            .map_err(|_| {
+                error!("Failed to write flash at {offset:#010X}");
+                Status::HandlerError
+            })?;
+        self.written = end;
+        Ok(())
+    }
+
+    // Reads back the received image and checks it against the digest
+    fn verify(&mut self) -> core::result::Result<(), Status> {
+        match self.state {
+            UpdateState::Receiving if self.written >= self.image_size => (),
+            UpdateState::Verified | UpdateState::Complete => return Ok(()),
+            _ => return Err(Status::InvalidRequest),
+        }
+
+        self.verifier.reset();
+        let mut buf = [0u8; VERIFY_CHUNK];
+        let mut offset = 0;
+        while offset < self.image_size {
+            let len = (self.image_size - offset).min(VERIFY_CHUNK as u32) as usize;
+            let read_len = len.next_multiple_of(F::READ_SIZE);
+            self.flash
+                .read(self.bank_offset + offset, &mut buf[..read_len])
+                .map_err(|_| {
+                    error!("Failed to read flash at {offset:#010X}");
+                    Status::HandlerError
+                })?;
+            self.verifier.update(&buf[..len]);
+            offset += len as u32;
+        }
+
+        if self.verifier.verify(&self.digest[..self.digest_len]) {
+            info!("Update verified");
+            self.state = UpdateState::Verified;
+        } else {
+            warn!("Update failed verification");
+            self.state = UpdateState::Failed;
+        }
+        Ok(())
+    }
+
+    fn abort(&mut self) {
+        if self.state != UpdateState::Idle {
+            info!("Update aborted");
+        }
+        self.state = UpdateState::Idle;
+        self.image_size = 0;
+        self.written = 0;
+    }
+}
+
+/// Firmware update service implementation, provided by the Target's
+/// [`RpcServer`](crate::server::RpcServer) context.
+pub trait FirmwareUpdate {
+    /// Flash backend
+    type Flash: NorFlash;
+    /// Image verifier
+    type Verifier: ImageVerifier;
+
+    /// The Updater receiving the image
+    fn updater(&mut self) -> &mut Updater<Self::Flash, Self::Verifier>;
+
+    /// Mark the inactive bank, now containing a verified image of
+    /// `image_size` bytes, bootable.  Typically this updates the
+    /// bootloader's configuration, so it boots from the bank after the next
+    /// reset.
+    fn mark_bootable(&mut self, image_size: u32) -> core::result::Result<(), Status>;
+}
+
+/// Command table for the firmware update service, to be mounted on an
+/// [`RpcServer`](crate::server::RpcServer) whose context implements
+/// [`FirmwareUpdate`].
+pub fn commands<C: FirmwareUpdate + 'static>() -> &'static [Command<C>] {
+    Commands::<C>::LIST
+}
+
+// Allows a static command table generic over the context type
+struct Commands<C>(core::marker::PhantomData<C>);
+
+impl<C: FirmwareUpdate + 'static> Commands<C> {
+    const LIST: &'static [Command<C>] = &[
+        Command::new(UPDATE_BEGIN, "update_begin", begin::<C>)
+            .with_schema("(u32, [u8])", "(UpdateStatus, u32)"),
+        Command::new(UPDATE_WRITE, "update_write", write::<C>)
+            .with_schema("(u32, [u8])", "UpdateStatus"),
+        Command::new(UPDATE_STATUS, "update_status", status::<C>).with_schema("()", "UpdateStatus"),
+        Command::new(UPDATE_FINISH, "update_finish", finish::<C>).with_schema("()", "UpdateStatus"),
+        Command::new(UPDATE_ABORT, "update_abort", abort::<C>).with_schema("()", "UpdateStatus"),
+    ];
+}
+
+fn begin<C: FirmwareUpdate>(
+    ctx: &mut C,
+    req: &mut Request<'_>,
+    rsp: &mut [u8],
+) -> core::result::Result<usize, Status> {
+    let (image_size, digest) = split_u32(req.payload())?;
+    ctx.updater().begin(image_size, digest)?;
+    let len = encode_status(ctx, rsp)?;
+
+    // Largest write the server can receive, after the offset, in whole
+    // flash writes
+    let write_size = <C::Flash as NorFlash>::WRITE_SIZE;
+    let capacity = req.max_payload().saturating_sub(4);
+    let max_chunk = (capacity - capacity % write_size) as u32;
+    let rsp = rsp.get_mut(len..len + 4).ok_or(Status::ResponseTooLarge)?;
+    rsp.copy_from_slice(&max_chunk.to_le_bytes());
+    Ok(len + 4)
+}
+
+fn write<C: FirmwareUpdate>(
+    ctx: &mut C,
+    req: &mut Request<'_>,
+    rsp: &mut [u8],
+) -> core::result::Result<usize, Status> {
+    let (offset, data) = split_u32(req.payload())?;
+    ctx.updater().write(offset, data)?;
+    encode_status(ctx, rsp)
+}
+
+fn status<C: FirmwareUpdate>(
+    ctx: &mut C,
+    _: &mut Request<'_>,
+    rsp: &mut [u8],
+) -> core::result::Result<usize, Status> {
+    encode_status(ctx, rsp)
+}
+
+fn finish<C: FirmwareUpdate>(
+    ctx: &mut C,
+    _: &mut Request<'_>,
+    rsp: &mut [u8],
+) -> core::result::Result<usize, Status> {
+    ctx.updater().verify()?;
+
+    // If marking the bank bootable fails the image remains verified, so the
+    // Host can retry
+    let status = ctx.updater().status();
+    if status.state == UpdateState::Verified {
+        ctx.mark_bootable(status.image_size)?;
+        info!("Update complete");
+        ctx.updater().state = UpdateState::Complete;
+    }
+    encode_status(ctx, rsp)
+}
+
+fn abort<C: FirmwareUpdate>(
+    ctx: &mut C,
+    _: &mut Request<'_>,
+    rsp: &mut [u8],
+) -> core::result::Result<usize, Status> {
+    ctx.updater().abort();
+    encode_status(ctx, rsp)
+}
+
+fn encode_status<C: FirmwareUpdate>(
+    ctx: &mut C,
+    rsp: &mut [u8],
+) -> core::result::Result<usize, Status> {
+    ctx.updater()
+        .status()
+        .encode(rsp)
+        .ok_or(Status::ResponseTooLarge)
+}
+
+fn split_u32(payload: &[u8]) -> core::result::Result<(u32, &[u8]), Status> {
+    let (value, rest) = payload.split_at_checked(4).ok_or(Status::InvalidRequest)?;
+    let value = u32::from_le_bytes(value.try_into().map_err(|_| Status::InvalidRequest)?);
+    Ok((value, rest))
+}
@@ -0,0 +1,145 @@
+//! Tests for AtomicChannelIo, running each end of the channels on its own
+//! thread, as they would be on each core of a dual-core MCU.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use std::sync::atomic::AtomicU32;
+use std::thread;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::{AtomicChannelIo, ChannelActor, NoDeadline, OwnedChannel};
+
+const CH_SIZE: usize = 256;
+const CH_A: u32 = 0;
+const CH_B: u32 = CH_SIZE as u32;
+const MESSAGES: u32 = 10_000;
+
+fn memory() -> Vec<AtomicU32> {
+    (0..CH_SIZE / 2).map(|_| AtomicU32::new(0)).collect()
+}
+
+// Message n has a length and contents depending on n, so reordered, torn or
+// repeated messages are detected
+fn message(n: u32) -> Vec<u8> {
+    let len = 4 + (n as usize % 200);
+    (0..len)
+        .map(|i| (n as usize).wrapping_mul(31).wrapping_add(i) as u8)
+        .collect()
+}
+
+// Connects to a channel, waiting for the other thread to create it
+fn connect(
+    io: AtomicChannelIo<'_>,
+    actor: ChannelActor,
+    addr: u32,
+) -> OwnedChannel<AtomicChannelIo<'_>> {
+    loop {
+        match OwnedChannel::from_target(io, actor, addr) {
+            Ok(channel) => break channel,
+            Err(Error::Uninit) => thread::yield_now(),
+            Err(e) => panic!("Failed to connect to channel: {e:?}"),
+        }
+    }
+}
+
+#[test]
+fn messages_cross_threads_in_order() {
+    let mem = memory();
+    let io = AtomicChannelIo::new(&mem, 0);
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            let mut tx = OwnedChannel::new(io, ChannelActor::Producer, CH_A, CH_SIZE).unwrap();
+            for n in 0..MESSAGES {
+                tx.publish_bytes_when_ready(&message(n), thread::yield_now, NoDeadline)
+                    .unwrap();
+            }
+        });
+
+        s.spawn(|| {
+            let mut rx = connect(io, ChannelActor::Consumer, CH_A);
+            let mut buf = [0u8; CH_SIZE];
+            let mut n = 0;
+            while n < MESSAGES {
+                match rx.consume_bytes(&mut buf) {
+                    Ok(len) => {
+                        assert_eq!(&buf[..len], message(n), "message {n}");
+                        n += 1;
+                    }
+                    Err(Error::NoData) => thread::yield_now(),
+                    Err(e) => panic!("Failed to consume message {n}: {e:?}"),
+                }
+            }
+        });
+    });
+}
+
+#[test]
+fn request_response_across_threads() {
+    let mem = memory();
+    let io = AtomicChannelIo::new(&mem, 0);
+
+    thread::scope(|s| {
+        // Server, echoing each request with its words incremented
+        s.spawn(|| {
+            let mut rx = OwnedChannel::new(io, ChannelActor::Consumer, CH_A, CH_SIZE).unwrap();
+            let mut tx = OwnedChannel::new(io, ChannelActor::Producer, CH_B, CH_SIZE).unwrap();
+            let mut buf = [0u32; CH_SIZE / 4];
+            let mut handled = 0;
+            while handled < MESSAGES {
+                match rx.consume_data(&mut buf) {
+                    Ok(len) => {
+                        let response = buf[..len].iter().map(|w| w + 1).collect::<Vec<_>>();
+                        tx.publish_data_when_ready(&response, thread::yield_now, NoDeadline)
+                            .unwrap();
+                        handled += 1;
+                    }
+                    Err(Error::NoData) => thread::yield_now(),
+                    Err(e) => panic!("Failed to consume request {handled}: {e:?}"),
+                }
+            }
+        });
+
+        // Client
+        s.spawn(|| {
+            let mut tx = connect(io, ChannelActor::Producer, CH_A);
+            let mut rx = connect(io, ChannelActor::Consumer, CH_B);
+            let mut buf = [0u32; CH_SIZE / 4];
+            for n in 0..MESSAGES {
+                let request = (0..=(n % 16)).map(|i| n * 100 + i).collect::<Vec<_>>();
+                tx.publish_data_when_ready(&request, thread::yield_now, NoDeadline)
+                    .unwrap();
+                let len = loop {
+                    match rx.consume_data(&mut buf) {
+                        Ok(len) => break len,
+                        Err(Error::NoData) => thread::yield_now(),
+                        Err(e) => panic!("Failed to consume response {n}: {e:?}"),
+                    }
+                };
+                let expected = request.iter().map(|w| w + 1).collect::<Vec<_>>();
+                assert_eq!(&buf[..len], expected, "response {n}");
+            }
+        });
+    });
+}
+
+#[test]
+fn addresses_outside_memory_fail() {
+    let mem = memory();
+    let io = AtomicChannelIo::new(&mem, 0x2000_0000);
+
+    assert_eq!(
+        OwnedChannel::new(io, ChannelActor::Producer, 0x1000_0000, CH_SIZE).err(),
+        Some(Error::Io)
+    );
+    assert_eq!(
+        OwnedChannel::new(io, ChannelActor::Producer, 0x2000_0002, CH_SIZE).err(),
+        Some(Error::NotAligned)
+    );
+
+    // Channel claiming to extend past the end of the memory
+    let mut tx = OwnedChannel::new(io, ChannelActor::Producer, 0x2000_0100, CH_SIZE * 2).unwrap();
+    assert_eq!(tx.publish_bytes(&[0; CH_SIZE]), Err(Error::Io));
+}
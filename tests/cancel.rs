@@ -0,0 +1,241 @@
+//! Tests for AsyncRpcClient request cancellation, dropping request() futures
+//! part way through, as a timeout would.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+mod common;
+
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::{ChannelActor, OwnedChannel};
+use airfrog_rpc::client::{AsyncRpcClient, RpcClientConfig};
+
+use common::{
+    Action, CH_SIZE, CMD_CH, Delay, RSP_CH, SimTarget, StopOnDrop, TargetChannel, block_on,
+    poll_times, serve, wait_until,
+};
+
+// Waits until the Host cancels the command, or a few seconds pass
+fn wait_for_cancel(cmd: &mut TargetChannel<'_>) -> bool {
+    let end = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < end {
+        if cmd.cancel_requested().unwrap() {
+            return true;
+        }
+        thread::yield_now();
+    }
+    false
+}
+
+#[test]
+fn stale_response_is_discarded() {
+    let target = SimTarget::new();
+    let stop = AtomicBool::new(false);
+    let handled = AtomicU32::new(0);
+
+    thread::scope(|s| {
+        let (cmd, rsp) = target.channels();
+        s.spawn(|| {
+            serve(cmd, rsp, &stop, &handled, |command, _| {
+                // Slow, and ignores cancellation
+                thread::sleep(Duration::from_millis(20));
+                Action::Respond(command.to_vec())
+            })
+        });
+
+        let _stop = StopOnDrop(&stop);
+        let (mut reader, mut writer) = (target.clone(), target.clone());
+        let mut client =
+            AsyncRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config());
+
+        // Drop the request once the command has been sent
+        {
+            let request = pin!(client.request(b"one"));
+            assert!(poll_times(request, 1).is_none());
+        }
+        assert!(block_on(client.is_outstanding()).unwrap());
+
+        assert_eq!(block_on(client.request(b"two")).unwrap(), b"two");
+        assert!(!block_on(client.is_outstanding()).unwrap());
+        assert_eq!(block_on(client.request(b"three")).unwrap(), b"three");
+    });
+    assert_eq!(handled.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn response_received_after_drop_is_discarded() {
+    let target = SimTarget::new();
+    let stop = AtomicBool::new(false);
+    let handled = AtomicU32::new(0);
+
+    thread::scope(|s| {
+        let (cmd, rsp) = target.channels();
+        s.spawn(|| {
+            serve(cmd, rsp, &stop, &handled, |command, _| {
+                Action::Respond(command.to_vec())
+            })
+        });
+
+        let _stop = StopOnDrop(&stop);
+        let (mut reader, mut writer) = (target.clone(), target.clone());
+        let mut client =
+            AsyncRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config());
+
+        // Drop the request after the Target has responded, but before the
+        // response is consumed
+        {
+            let request = pin!(client.request(b"one"));
+            assert!(poll_times(request, 1).is_none());
+            wait_until(|| handled.load(Ordering::SeqCst) == 1);
+        }
+        assert!(block_on(client.is_outstanding()).unwrap());
+
+        assert_eq!(block_on(client.request(b"two")).unwrap(), b"two");
+    });
+}
+
+#[test]
+fn completed_request_is_not_outstanding() {
+    let target = SimTarget::new();
+    let stop = AtomicBool::new(false);
+    let handled = AtomicU32::new(0);
+
+    thread::scope(|s| {
+        let (cmd, rsp) = target.channels();
+        s.spawn(|| {
+            serve(cmd, rsp, &stop, &handled, |command, _| {
+                Action::Respond(command.to_vec())
+            })
+        });
+
+        let _stop = StopOnDrop(&stop);
+        let (mut reader, mut writer) = (target.clone(), target.clone());
+        let mut client =
+            AsyncRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config());
+
+        assert_eq!(block_on(client.request(b"one")).unwrap(), b"one");
+        assert!(!block_on(client.is_outstanding()).unwrap());
+        assert_eq!(block_on(client.cancel()), Err(Error::InvalidOperation));
+    });
+}
+
+#[test]
+fn cancel_is_acknowledged() {
+    let target = SimTarget::new();
+    let stop = AtomicBool::new(false);
+    let handled = AtomicU32::new(0);
+
+    thread::scope(|s| {
+        let (cmd, rsp) = target.channels();
+        s.spawn(|| {
+            serve(cmd, rsp, &stop, &handled, |command, cmd| {
+                if command == b"slow" && wait_for_cancel(cmd) {
+                    Action::Cancelled
+                } else {
+                    Action::Respond(command.to_vec())
+                }
+            })
+        });
+
+        let _stop = StopOnDrop(&stop);
+        let (mut reader, mut writer) = (target.clone(), target.clone());
+        let mut client =
+            AsyncRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config());
+
+        {
+            let request = pin!(client.request(b"slow"));
+            assert!(poll_times(request, 3).is_none());
+        }
+        assert_eq!(block_on(client.cancel()), Err(Error::Cancelled));
+        assert!(!block_on(client.is_outstanding()).unwrap());
+
+        // A stale request is also cancelled by the next request
+        {
+            let request = pin!(client.request(b"slow"));
+            assert!(poll_times(request, 3).is_none());
+        }
+        assert_eq!(block_on(client.request(b"fast")).unwrap(), b"fast");
+    });
+}
+
+#[test]
+fn cancel_times_out_when_target_does_not_respond() {
+    let target = SimTarget::new();
+    let stop = AtomicBool::new(false);
+    let handled = AtomicU32::new(0);
+
+    thread::scope(|s| {
+        let (cmd, rsp) = target.channels();
+        s.spawn(|| serve(cmd, rsp, &stop, &handled, |_, _| Action::Ignore));
+
+        let _stop = StopOnDrop(&stop);
+        let (mut reader, mut writer) = (target.clone(), target.clone());
+        let mut client =
+            AsyncRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config())
+                .with_cancel_polls(10);
+
+        {
+            let request = pin!(client.request(b"lost"));
+            assert!(poll_times(request, 1).is_none());
+        }
+        assert_eq!(block_on(client.cancel()), Err(Error::Timeout));
+
+        // Still outstanding, so the next request tries to cancel it again
+        assert!(block_on(client.is_outstanding()).unwrap());
+        assert_eq!(block_on(client.request(b"next")), Err(Error::Timeout));
+
+        client.abandon();
+        assert!(!block_on(client.is_outstanding()).unwrap());
+    });
+}
+
+#[test]
+fn stale_response_is_discarded_with_direct_config() {
+    let target = SimTarget::new();
+    let stop = AtomicBool::new(false);
+    let handled = AtomicU32::new(0);
+
+    thread::scope(|s| {
+        // The Host initializes the channels, so the Target connects to them
+        s.spawn(|| {
+            let connect = |actor, addr| loop {
+                match OwnedChannel::from_target(target.io(), actor, addr) {
+                    Ok(channel) => break channel,
+                    Err(Error::Uninit) => thread::yield_now(),
+                    Err(e) => panic!("Target failed to connect: {e:?}"),
+                }
+            };
+            let cmd = connect(ChannelActor::Consumer, CMD_CH);
+            let rsp = connect(ChannelActor::Producer, RSP_CH);
+            serve(cmd, rsp, &stop, &handled, |command, _| {
+                thread::sleep(Duration::from_millis(20));
+                Action::Respond(command.to_vec())
+            })
+        });
+
+        let _stop = StopOnDrop(&stop);
+        let (mut reader, mut writer) = (target.clone(), target.clone());
+        let config = RpcClientConfig::Direct {
+            cmd_ch_ptr: CMD_CH,
+            cmd_ch_size: CH_SIZE,
+            rsp_ch_ptr: RSP_CH,
+            rsp_ch_size: CH_SIZE,
+        };
+        let mut client = AsyncRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, config);
+
+        assert_eq!(block_on(client.request(b"zero")).unwrap(), b"zero");
+        {
+            let request = pin!(client.request(b"one"));
+            assert!(poll_times(request, 1).is_none());
+        }
+        assert!(block_on(client.is_outstanding()).unwrap());
+        assert_eq!(block_on(client.request(b"two")).unwrap(), b"two");
+    });
+    assert_eq!(handled.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,91 @@
+//! Tests for the codecs, and how their errors are reported.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(any(feature = "postcard", feature = "cbor"))]
+
+use airfrog_rpc::Error;
+use airfrog_rpc::codec::Codec;
+use airfrog_rpc::server::{Status, encode_response};
+use serde::{Deserialize, Serialize, Serializer};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Reading {
+    channel: u8,
+    name: String,
+    values: Vec<u32>,
+}
+
+// Always fails to serialize
+struct Unencodable;
+
+impl Serialize for Unencodable {
+    fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom("unencodable"))
+    }
+}
+
+fn reading() -> Reading {
+    Reading {
+        channel: 3,
+        name: "a name longer than the CBOR codec's scratch buffer, to test chunking".into(),
+        values: vec![1, 1000, 100_000],
+    }
+}
+
+fn round_trip<C: Codec>() {
+    let mut buf = [0u8; 256];
+    let len = C::encode(&reading(), &mut buf).unwrap();
+    assert_eq!(C::decode::<Reading>(&buf[..len]).unwrap(), reading());
+    assert_eq!(C::decode::<Reading>(&buf[..len / 2]), Err(Error::Codec));
+}
+
+fn errors_are_distinct<C: Codec>() {
+    let mut buf = [0u8; 256];
+    assert_eq!(
+        C::encode(&reading(), &mut buf[..16]),
+        Err(Error::BufferTooSmall)
+    );
+    assert_eq!(C::encode(&Unencodable, &mut buf), Err(Error::Codec));
+
+    assert_eq!(
+        encode_response::<C, _>(&reading(), &mut buf[..16]),
+        Err(Status::ResponseTooLarge)
+    );
+    assert_eq!(
+        encode_response::<C, _>(&Unencodable, &mut buf),
+        Err(Status::HandlerError)
+    );
+}
+
+#[cfg(feature = "postcard")]
+mod postcard {
+    use airfrog_rpc::codec::PostcardCodec;
+
+    #[test]
+    fn round_trip() {
+        super::round_trip::<PostcardCodec>();
+    }
+
+    #[test]
+    fn errors_are_distinct() {
+        super::errors_are_distinct::<PostcardCodec>();
+    }
+}
+
+#[cfg(feature = "cbor")]
+mod cbor {
+    use airfrog_rpc::codec::CborCodec;
+
+    #[test]
+    fn round_trip() {
+        super::round_trip::<CborCodec>();
+    }
+
+    #[test]
+    fn errors_are_distinct() {
+        super::errors_are_distinct::<CborCodec>();
+    }
+}
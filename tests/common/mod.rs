@@ -0,0 +1,314 @@
+//! Simulated Target for Host-side tests.
+//!
+//! [`SimTarget`] is Target memory held on the Host.  The Host side accesses
+//! it through [`Reader`] and [`Writer`], as it would over SWD, and the Target
+//! side, typically run on its own thread, through [`AtomicChannelIo`].
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![allow(dead_code)]
+
+use std::future::Future;
+use std::pin::{Pin, pin};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::{AtomicChannelIo, ChannelActor, NoDeadline, OwnedChannel};
+use airfrog_rpc::client::{AsyncDelay, AsyncRpcClient, RpcClientConfig};
+use airfrog_rpc::io::{Reader, Writer};
+use airfrog_rpc::server::{Command, RpcServer};
+
+/// Base address of the simulated Target's memory
+pub const BASE: u32 = 0x2000_0000;
+
+/// Size of each channel
+pub const CH_SIZE: usize = 256;
+
+/// Command channel address
+pub const CMD_CH: u32 = BASE;
+
+/// Response channel address
+pub const RSP_CH: u32 = BASE + CH_SIZE as u32;
+
+/// Size of the simulated Target's memory
+pub const MEM_SIZE: usize = 4 * CH_SIZE;
+
+/// Target channel type
+pub type TargetChannel<'a> = OwnedChannel<AtomicChannelIo<'a>>;
+
+/// Simulated Target memory, shared between the Host and Target sides.
+/// Clones refer to the same memory.
+#[derive(Clone)]
+pub struct SimTarget {
+    mem: Arc<Vec<AtomicU32>>,
+    failing: Arc<AtomicBool>,
+}
+
+impl SimTarget {
+    pub fn new() -> Self {
+        Self {
+            mem: Arc::new((0..MEM_SIZE / 4).map(|_| AtomicU32::new(0)).collect()),
+            failing: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Target side access to the memory
+    pub fn io(&self) -> AtomicChannelIo<'_> {
+        AtomicChannelIo::new(&self.mem, BASE)
+    }
+
+    /// Make all Host accesses fail, as if the debug interface had lost the
+    /// Target
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+
+    /// Create the Target's command and response channels
+    pub fn channels(&self) -> (TargetChannel<'_>, TargetChannel<'_>) {
+        let cmd = OwnedChannel::new(self.io(), ChannelActor::Consumer, CMD_CH, CH_SIZE).unwrap();
+        let rsp = OwnedChannel::new(self.io(), ChannelActor::Producer, RSP_CH, CH_SIZE).unwrap();
+        (cmd, rsp)
+    }
+
+    /// Client configuration for the channels created by [`Self::channels()`]
+    pub fn config(&self) -> RpcClientConfig {
+        RpcClientConfig::FromTarget {
+            cmd_ch_ptr: CMD_CH,
+            rsp_ch_ptr: RSP_CH,
+        }
+    }
+
+    fn word(&self, addr: u32) -> Result<&AtomicU32, ()> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(());
+        }
+        let offset = addr.checked_sub(BASE).ok_or(())? as usize;
+        self.mem.get(offset / 4).ok_or(())
+    }
+
+    fn read_bytes(&self, addr: u32, buf: &mut [u8]) -> Result<(), ()> {
+        for (i, byte) in buf.iter_mut().enumerate() {
+            let addr = addr + i as u32;
+            let word = self.word(addr & !3)?.load(Ordering::Acquire);
+            *byte = word.to_le_bytes()[(addr & 3) as usize];
+        }
+        Ok(())
+    }
+
+    fn write_bytes(&self, addr: u32, data: &[u8]) -> Result<(), ()> {
+        for (i, chunk) in data.chunks(4).enumerate() {
+            let addr = addr + 4 * i as u32;
+            if addr & 3 == 0 && chunk.len() == 4 {
+                let value = u32::from_le_bytes(chunk.try_into().unwrap());
+                self.word(addr)?.store(value, Ordering::Release);
+            } else {
+                for (j, byte) in chunk.iter().enumerate() {
+                    let addr = addr + j as u32;
+                    let word = self.word(addr & !3)?;
+                    let mut bytes = word.load(Ordering::Acquire).to_le_bytes();
+                    bytes[(addr & 3) as usize] = *byte;
+                    word.store(u32::from_le_bytes(bytes), Ordering::Release);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Reader for SimTarget {
+    type Error = ();
+
+    fn read(
+        &mut self,
+        addr: u32,
+        buf: &mut [u8],
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        std::future::ready(self.read_bytes(addr, buf))
+    }
+
+    fn update_base_address(&mut self, _new_base: u32) {}
+}
+
+impl Writer for SimTarget {
+    type Error = ();
+
+    fn write(
+        &mut self,
+        addr: u32,
+        data: &[u8],
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        std::future::ready(self.write_bytes(addr, data))
+    }
+
+    fn update_base_address(&mut self, _new_base: u32) {}
+}
+
+/// Delay which sleeps briefly, and yields to the executor
+pub struct Delay;
+
+impl AsyncDelay for Delay {
+    async fn delay() {
+        thread::sleep(Duration::from_millis(1));
+        yield_now().await;
+    }
+}
+
+/// Sets a flag when dropped, including when a test panics, to stop the
+/// Target's thread so the test can end
+pub struct StopOnDrop<'a>(pub &'a AtomicBool);
+
+impl Drop for StopOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// What a Target run by [`serve()`] does with a command
+pub enum Action {
+    Respond(Vec<u8>),
+    Cancelled,
+    Ignore,
+}
+
+/// Runs a Target until `stop` is set, handling commands with `handle`,
+/// which is also given the command channel to check for cancellation.
+/// `handled` counts the commands handled.
+pub fn serve(
+    mut cmd: TargetChannel<'_>,
+    mut rsp: TargetChannel<'_>,
+    stop: &AtomicBool,
+    handled: &AtomicU32,
+    mut handle: impl FnMut(&[u8], &mut TargetChannel<'_>) -> Action,
+) {
+    let mut buf = [0u8; CH_SIZE];
+    while !stop.load(Ordering::SeqCst) {
+        let len = match cmd.consume_bytes(&mut buf) {
+            Ok(len) => len,
+            Err(Error::NoData) => {
+                thread::yield_now();
+                continue;
+            }
+            Err(e) => panic!("Target failed to consume command: {e:?}"),
+        };
+        match handle(&buf[..len], &mut cmd) {
+            Action::Respond(response) => rsp
+                .publish_bytes_when_ready(&response, thread::yield_now, NoDeadline)
+                .unwrap(),
+            Action::Cancelled => rsp.publish_cancelled().unwrap(),
+            Action::Ignore => (),
+        }
+        handled.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Runs an RpcServer with an N byte buffer and context `ctx` on `target`,
+/// serving `tables`, while `host` talks to it
+pub fn with_server<C: Send, const N: usize>(
+    target: &SimTarget,
+    ctx: &mut C,
+    tables: &[&'static [Command<C>]],
+    host: impl FnOnce(&mut AsyncRpcClient<'_, SimTarget, SimTarget, Delay>),
+) {
+    let stop = AtomicBool::new(false);
+    thread::scope(|s| {
+        let (cmd, rsp) = target.channels();
+        let stop = &stop;
+        s.spawn(move || {
+            let mut server: RpcServer<_, C, N, _> = RpcServer::new(cmd, rsp, tables[0]);
+            for table in &tables[1..] {
+                server.mount(table).unwrap();
+            }
+            while !stop.load(Ordering::SeqCst) {
+                if !server.poll(ctx).unwrap() {
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let _stop = StopOnDrop(stop);
+        let (mut reader, mut writer) = (target.clone(), target.clone());
+        let mut client = AsyncRpcClient::new(&mut reader, &mut writer, target.config());
+        host(&mut client);
+    });
+}
+
+/// Yields to the executor once
+pub async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// Waker which counts how many times it is woken
+#[derive(Default)]
+pub struct CountingWaker(pub AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn waker() -> Waker {
+    Waker::from(Arc::new(ThreadWaker(thread::current())))
+}
+
+/// Runs a future to completion on the current thread
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = waker();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::park_timeout(Duration::from_millis(1));
+    }
+}
+
+/// Polls a future `polls` times, returning its output if it completed.
+/// Dropping the future afterwards simulates it being cancelled, for example
+/// by a timeout.
+pub fn poll_times<F: Future>(mut future: Pin<&mut F>, polls: usize) -> Option<F::Output> {
+    let waker = waker();
+    let mut cx = Context::from_waker(&waker);
+    for _ in 0..polls {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return Some(output);
+        }
+    }
+    None
+}
+
+/// Waits until a condition is true, panicking after a few seconds
+pub fn wait_until(mut condition: impl FnMut() -> bool) {
+    for _ in 0..5000 {
+        if condition() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("Timed out waiting for condition");
+}
@@ -0,0 +1,94 @@
+//! Tests for DefmtCollector, receiving defmt frames from a simulated Target's
+//! log channel.
+//!
+//! The Target side is simulated by publishing messages in the wire format of
+//! `logger::defmt`, as its global logger can't be linked into a Host test.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(feature = "async")]
+
+mod common;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::{ChannelActor, OwnedChannel};
+use airfrog_rpc::logger::{DefmtCollector, FRAME_HEADER_SIZE, MESSAGE_HEADER_SIZE};
+
+use common::{BASE, CH_SIZE, Delay, SimTarget, TargetChannel, block_on};
+
+const LOG_CH: u32 = BASE + 2 * CH_SIZE as u32;
+
+// Builds a message - the dropped count, followed by length-prefixed frames
+fn message(dropped: u32, frames: &[&[u8]]) -> Vec<u8> {
+    let mut msg = dropped.to_le_bytes().to_vec();
+    assert_eq!(msg.len(), MESSAGE_HEADER_SIZE);
+    for frame in frames {
+        let len = (frame.len() as u16).to_le_bytes();
+        assert_eq!(len.len(), FRAME_HEADER_SIZE);
+        msg.extend_from_slice(&len);
+        msg.extend_from_slice(frame);
+    }
+    msg
+}
+
+fn log_channel(target: &SimTarget) -> TargetChannel<'_> {
+    OwnedChannel::new(target.io(), ChannelActor::Producer, LOG_CH, CH_SIZE).unwrap()
+}
+
+#[test]
+fn frames_are_returned_whole_and_in_order() {
+    let target = SimTarget::new();
+    let mut log_ch = log_channel(&target);
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut frames = DefmtCollector::<_, _, Delay>::new(&mut reader, &mut writer, LOG_CH);
+    assert_eq!(block_on(frames.try_next()), Ok(None));
+
+    log_ch
+        .publish_bytes(&message(0, &[&[1, 2, 3], &[], &[4; 5]]))
+        .unwrap();
+    assert_eq!(block_on(frames.next()), Ok(vec![1, 2, 3]));
+    assert_eq!(block_on(frames.next()), Ok(vec![]));
+    assert_eq!(block_on(frames.next()), Ok(vec![4; 5]));
+    assert_eq!(block_on(frames.try_next()), Ok(None));
+
+    log_ch.publish_bytes(&message(0, &[&[6]])).unwrap();
+    assert_eq!(block_on(frames.next()), Ok(vec![6]));
+    assert_eq!(frames.dropped(), 0);
+}
+
+#[test]
+fn dropped_counts_accumulate() {
+    let target = SimTarget::new();
+    let mut log_ch = log_channel(&target);
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut frames = DefmtCollector::<_, _, Delay>::new(&mut reader, &mut writer, LOG_CH);
+
+    log_ch.publish_bytes(&message(3, &[&[1]])).unwrap();
+    assert_eq!(block_on(frames.next()), Ok(vec![1]));
+    assert_eq!(frames.dropped(), 3);
+
+    log_ch.publish_bytes(&message(2, &[&[2]])).unwrap();
+    assert_eq!(block_on(frames.next()), Ok(vec![2]));
+    assert_eq!(frames.dropped(), 5);
+}
+
+#[test]
+fn truncated_frame_is_malformed() {
+    let target = SimTarget::new();
+    let mut log_ch = log_channel(&target);
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut frames = DefmtCollector::<_, _, Delay>::new(&mut reader, &mut writer, LOG_CH);
+
+    // A frame claiming more bytes than the message holds
+    let mut msg = message(1, &[&[1, 2, 3, 4]]);
+    msg.truncate(msg.len() - 1);
+    log_ch.publish_bytes(&msg).unwrap();
+    assert_eq!(block_on(frames.next()), Err(Error::Malformed));
+    assert_eq!(frames.dropped(), 1);
+
+    // The message was discarded, and collection continues
+    log_ch.publish_bytes(&message(0, &[&[5]])).unwrap();
+    assert_eq!(block_on(frames.next()), Ok(vec![5]));
+}
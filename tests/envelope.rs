@@ -0,0 +1,77 @@
+//! Tests for enveloped requests, against a simulated Target echoing request
+//! IDs.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(feature = "async")]
+
+mod common;
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::NoDeadline;
+use airfrog_rpc::client::{AsyncRpcClient, PipelinedRpcClient};
+
+use common::{CH_SIZE, Delay, SimTarget, StopOnDrop, block_on};
+
+#[test]
+fn request_ids_continue_from_earlier_clients() {
+    let target = SimTarget::new();
+    let stop = AtomicBool::new(false);
+    let ids = Mutex::new(Vec::new());
+
+    thread::scope(|s| {
+        // Echoes each payload, recording the request IDs
+        let (mut cmd, mut rsp) = target.channels();
+        let (stop_flag, ids) = (&stop, &ids);
+        s.spawn(move || {
+            let mut buf = [0u8; CH_SIZE];
+            while !stop_flag.load(Ordering::SeqCst) {
+                match cmd.consume_envelope(&mut buf) {
+                    Ok((envelope, len)) => {
+                        ids.lock().unwrap().push(envelope.request_id);
+                        while let Err(Error::Busy) = rsp.publish_response(&envelope, 0, &buf[..len])
+                        {
+                            thread::yield_now();
+                        }
+                    }
+                    Err(Error::NoData) => thread::yield_now(),
+                    Err(e) => panic!("Target failed to consume command: {e:?}"),
+                }
+            }
+        });
+
+        let _stop = StopOnDrop(&stop);
+        let (mut reader, mut writer) = (target.clone(), target.clone());
+
+        {
+            let mut client =
+                AsyncRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config());
+            block_on(client.request_enveloped(1, b"first")).unwrap();
+            block_on(client.request_enveloped(1, b"second")).unwrap();
+        }
+        {
+            let client = PipelinedRpcClient::<_, _, Delay>::new(
+                &mut reader,
+                &mut writer,
+                target.config(),
+                2,
+            );
+            let (envelope, payload) = block_on(client.request(2, b"third", NoDeadline)).unwrap();
+            assert_eq!(
+                (envelope.request_id, payload.as_slice()),
+                (2, &b"third"[..])
+            );
+        }
+        let mut client =
+            AsyncRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config());
+        let (envelope, _) = block_on(client.request_enveloped(3, b"fourth")).unwrap();
+        assert_eq!(envelope.request_id, 3);
+    });
+    assert_eq!(*ids.lock().unwrap(), [0, 1, 2, 3]);
+}
@@ -0,0 +1,94 @@
+//! Tests for EventStream, receiving events from a simulated Target's
+//! EventPublisher.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(feature = "stream")]
+
+mod common;
+
+use std::future::poll_fn;
+use std::pin::{Pin, pin};
+
+use airfrog_rpc::channel::{Channel, ChannelActor, OwnedChannel};
+use airfrog_rpc::client::{AsyncDelay, Event, EventStream, EventSubscriber};
+use airfrog_rpc::event::EventPublisher;
+use airfrog_rpc::io::{Reader, Writer};
+use futures_core::Stream;
+
+use common::{BASE, CH_SIZE, Delay, SimTarget, block_on, poll_times};
+
+const EVENT_CH: u32 = BASE + 2 * CH_SIZE as u32;
+
+const BUTTON: u16 = 1;
+const FAULT: u16 = 2;
+
+fn next<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+    block_on(poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)))
+}
+
+fn subscriber<'s, 'a, R: Reader, W: Writer, D: AsyncDelay>(
+    stream: &'s EventStream<'a, R, W, D>,
+) -> &'s EventSubscriber<'a, R, W, D> {
+    stream.subscriber().unwrap()
+}
+
+#[test]
+fn stream_reports_drops_with_next_event() {
+    let target = SimTarget::new();
+    let mut io = target.io();
+    let mut event_ch = Channel::new(&mut io, ChannelActor::Producer, EVENT_CH, CH_SIZE).unwrap();
+    let mut publisher = EventPublisher::new();
+
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut events = EventSubscriber::<_, _, Delay>::new(&mut reader, &mut writer, EVENT_CH)
+        .with_filter(&[FAULT])
+        .into_stream();
+
+    assert!(publisher.publish(&mut event_ch, FAULT, b"first").unwrap());
+    let event = next(&mut events).unwrap().unwrap();
+    assert_eq!((event.seq, event.payload.as_slice()), (0, &b"first"[..]));
+
+    // The second fault is dropped, as the button press, which is filtered,
+    // hasn't been consumed.  The drop isn't known until the next event.
+    assert!(publisher.publish(&mut event_ch, BUTTON, b"up").unwrap());
+    assert!(!publisher.publish(&mut event_ch, FAULT, b"second").unwrap());
+    assert_eq!(publisher.dropped(), 1);
+    assert_eq!(subscriber(&events).overflow(), 0);
+
+    let mut receiving = poll_fn(|cx| Pin::new(&mut events).poll_next(cx));
+    assert!(poll_times(pin!(&mut receiving), 3).is_none());
+    assert!(publisher.publish(&mut event_ch, FAULT, b"third").unwrap());
+    let event = block_on(receiving).unwrap().unwrap();
+    assert_eq!(
+        event,
+        Event {
+            seq: 3,
+            event_type: FAULT,
+            dropped: 1,
+            payload: b"third".to_vec(),
+        }
+    );
+    assert_eq!(subscriber(&events).overflow(), 1);
+    assert_eq!(subscriber(&events).gaps(), 0);
+}
+
+#[test]
+fn publisher_accepts_an_owning_channel() {
+    let target = SimTarget::new();
+    let mut event_ch =
+        OwnedChannel::new(target.io(), ChannelActor::Producer, EVENT_CH, CH_SIZE).unwrap();
+    let mut publisher = EventPublisher::new();
+
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut events = EventSubscriber::<_, _, Delay>::new(&mut reader, &mut writer, EVENT_CH);
+
+    assert!(publisher.publish(&mut event_ch, BUTTON, b"down").unwrap());
+    let event = block_on(events.next()).unwrap();
+    assert_eq!(
+        (event.event_type, event.payload.as_slice()),
+        (BUTTON, &b"down"[..])
+    );
+}
@@ -0,0 +1,101 @@
+//! Tests for IsrChannel, using the std critical-section implementation.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(feature = "isr")]
+
+mod common;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::{AtomicChannelIo, ChannelActor, IsrChannel, OwnedChannel};
+
+use common::{CH_SIZE, CMD_CH, SimTarget, TargetChannel};
+
+// Producer and consumer IsrChannels, sharing one channel
+fn isr_channels(
+    target: &SimTarget,
+) -> (
+    IsrChannel<AtomicChannelIo<'_>>,
+    IsrChannel<AtomicChannelIo<'_>>,
+) {
+    let producer =
+        TargetChannel::new(target.io(), ChannelActor::Producer, CMD_CH, CH_SIZE).unwrap();
+    let consumer = OwnedChannel::from_target(target.io(), ChannelActor::Consumer, CMD_CH).unwrap();
+    (
+        IsrChannel::from_channel(producer),
+        IsrChannel::from_channel(consumer),
+    )
+}
+
+#[test]
+fn operations_fail_before_init() {
+    let target = SimTarget::new();
+    let channel: IsrChannel<AtomicChannelIo<'_>> = IsrChannel::new();
+    let mut buf = [0; 16];
+
+    assert_eq!(channel.try_publish_bytes(b"hello"), Err(Error::Uninit));
+    assert_eq!(channel.try_consume_bytes(&mut buf), Err(Error::Uninit));
+    assert_eq!(channel.can_publish(), Err(Error::Uninit));
+    assert_eq!(channel.data_available(), Err(Error::Uninit));
+    assert!(channel.take().is_none());
+
+    let producer =
+        TargetChannel::new(target.io(), ChannelActor::Producer, CMD_CH, CH_SIZE).unwrap();
+    assert!(channel.init(producer).is_none());
+    assert_eq!(channel.try_publish_bytes(b"hello"), Ok(true));
+    assert!(channel.take().is_some());
+    assert_eq!(channel.can_publish(), Err(Error::Uninit));
+}
+
+#[test]
+fn publish_returns_false_while_busy() {
+    let target = SimTarget::new();
+    let (producer, consumer) = isr_channels(&target);
+    let mut buf = [0; 16];
+
+    assert_eq!(producer.can_publish(), Ok(true));
+    assert_eq!(producer.try_publish_bytes(b"first"), Ok(true));
+    assert_eq!(producer.can_publish(), Ok(false));
+    assert_eq!(producer.try_publish_bytes(b"second"), Ok(false));
+
+    // The data which couldn't be published hasn't replaced the first
+    assert_eq!(consumer.try_consume_bytes(&mut buf), Ok(Some(5)));
+    assert_eq!(&buf[..5], b"first");
+    assert_eq!(producer.try_publish_bytes(b"second"), Ok(true));
+}
+
+#[test]
+fn consume_returns_none_when_empty() {
+    let target = SimTarget::new();
+    let (producer, consumer) = isr_channels(&target);
+    let mut buf = [0; 16];
+    let mut words = [0; 4];
+
+    assert_eq!(consumer.data_available(), Ok(None));
+    assert_eq!(consumer.try_consume_bytes(&mut buf), Ok(None));
+    assert_eq!(consumer.try_consume_data(&mut words), Ok(None));
+
+    assert_eq!(
+        producer.try_publish_data(&[0x1234_5678, 0x9ABC_DEF0]),
+        Ok(true)
+    );
+    assert_eq!(consumer.data_available(), Ok(Some(8)));
+    assert_eq!(consumer.try_consume_data(&mut words), Ok(Some(2)));
+    assert_eq!(&words[..2], &[0x1234_5678, 0x9ABC_DEF0]);
+    assert_eq!(consumer.try_consume_bytes(&mut buf), Ok(None));
+}
+
+#[test]
+fn reentrant_with_is_busy() {
+    let target = SimTarget::new();
+    let (producer, _consumer) = isr_channels(&target);
+
+    let result = producer.with(|_| Ok(producer.try_publish_bytes(b"nested")));
+    assert_eq!(result, Ok(Err(Error::Busy)));
+    assert_eq!(producer.with(|_| producer.can_publish()), Err(Error::Busy));
+
+    // The outer borrow is released afterwards
+    assert_eq!(producer.try_publish_bytes(b"hello"), Ok(true));
+}
@@ -0,0 +1,97 @@
+//! Tests for ChannelLogger, shipping log records from a simulated Target to
+//! a LogCollector.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(all(feature = "logger", feature = "async"))]
+
+mod common;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::{AtomicChannelIo, ChannelActor, OwnedChannel};
+use airfrog_rpc::logger::{ChannelLogger, LogCollector, LogRecord};
+use log::{Level, Log, Record};
+
+use common::{BASE, CH_SIZE, Delay, SimTarget, block_on};
+
+const LOG_CH: u32 = BASE + 2 * CH_SIZE as u32;
+
+fn timestamp() -> u64 {
+    1234
+}
+
+fn log(logger: &impl Log, level: Level, message: &str) {
+    logger.log(
+        &Record::builder()
+            .args(format_args!("{message}"))
+            .level(level)
+            .target("app::sensor")
+            .build(),
+    );
+}
+
+fn record(level: Level, message: &str) -> LogRecord {
+    LogRecord {
+        timestamp: 1234,
+        level,
+        target: "app::sensor".into(),
+        message: message.into(),
+    }
+}
+
+#[test]
+fn records_logged_before_init_are_published() {
+    let target = SimTarget::new();
+    let logger: ChannelLogger<AtomicChannelIo<'_>, 256> =
+        ChannelLogger::new(target.io(), LOG_CH, timestamp);
+
+    log(&logger, Level::Info, "first");
+    log(&logger, Level::Warn, "second");
+    logger.init_channel(CH_SIZE).unwrap();
+    assert_eq!(logger.init_channel(CH_SIZE), Err(Error::InvalidOperation));
+    assert_eq!(logger.connect_channel(), Err(Error::InvalidOperation));
+    logger.flush();
+
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut logs = LogCollector::<_, _, Delay>::new(&mut reader, &mut writer, LOG_CH);
+    assert_eq!(block_on(logs.next()).unwrap(), record(Level::Info, "first"));
+    assert_eq!(
+        block_on(logs.next()).unwrap(),
+        record(Level::Warn, "second")
+    );
+    assert_eq!(block_on(logs.try_next()).unwrap(), None);
+
+    // Published as logged, now the channel exists
+    log(&logger, Level::Error, "third");
+    assert_eq!(
+        block_on(logs.next()).unwrap(),
+        record(Level::Error, "third")
+    );
+    assert_eq!(logs.dropped(), 0);
+}
+
+#[test]
+fn record_larger_than_record_size_is_dropped() {
+    let target = SimTarget::new();
+    OwnedChannel::new(target.io(), ChannelActor::Producer, LOG_CH, CH_SIZE).unwrap();
+    let logger: ChannelLogger<AtomicChannelIo<'_>, 256, 48> =
+        ChannelLogger::new(target.io(), LOG_CH, timestamp);
+    logger.connect_channel().unwrap();
+
+    // 12 byte header, 11 byte target, and the message
+    let longest = "x".repeat(48 - 12 - 11);
+    log(&logger, Level::Info, &format!("{longest}x"));
+    assert_eq!(logger.dropped(), 1);
+    log(&logger, Level::Info, &longest);
+    assert_eq!(logger.dropped(), 1);
+
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut logs = LogCollector::<_, _, Delay>::new(&mut reader, &mut writer, LOG_CH);
+    assert_eq!(
+        block_on(logs.next()).unwrap(),
+        record(Level::Info, &longest)
+    );
+    assert_eq!(logs.dropped(), 1);
+}
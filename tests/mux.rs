@@ -0,0 +1,281 @@
+//! Tests for the stream multiplexer, connecting a Target's Mux to a Host
+//! side Mux, or an AsyncMux, over simulated Target channels.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+mod common;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::{AtomicChannelIo, Channel, ChannelActor, MaxWaits, OwnedChannel};
+use airfrog_rpc::mux::{AsyncMux, Mux};
+
+use common::{CMD_CH, Delay, RSP_CH, SimTarget, block_on};
+
+// Small enough that a message can't carry all the data written
+const CH_SIZE: usize = 96;
+
+type TestMux<'a, const BUF: usize> = Mux<'a, AtomicChannelIo<'a>, 2, BUF, 256>;
+
+// Creates the Target's Mux, and a Mux for the Host side of the same channels
+fn muxes<'a, const BUF: usize>(
+    io: &'a mut [AtomicChannelIo<'a>; 4],
+) -> (TestMux<'a, BUF>, TestMux<'a, BUF>) {
+    let [t_rx, t_tx, h_rx, h_tx] = io;
+    let t_rx = Channel::new(t_rx, ChannelActor::Consumer, CMD_CH, CH_SIZE).unwrap();
+    let t_tx = Channel::new(t_tx, ChannelActor::Producer, RSP_CH, CH_SIZE).unwrap();
+    let h_rx = Channel::from_target(h_rx, ChannelActor::Consumer, RSP_CH).unwrap();
+    let h_tx = Channel::from_target(h_tx, ChannelActor::Producer, CMD_CH).unwrap();
+    (Mux::new(t_rx, t_tx), Mux::new(h_rx, h_tx))
+}
+
+// Polls both sides until neither makes progress
+fn pump<const BUF: usize>(a: &mut TestMux<'_, BUF>, b: &mut TestMux<'_, BUF>) {
+    for _ in 0..100 {
+        let a_moved = a.poll().unwrap();
+        let b_moved = b.poll().unwrap();
+        if !a_moved && !b_moved {
+            return;
+        }
+    }
+    panic!("Muxes did not settle");
+}
+
+#[test]
+fn data_is_shared_fairly_between_streams() {
+    let target = SimTarget::new();
+    let mut io = [target.io(); 4];
+    let (mut t, mut h) = muxes::<256>(&mut io);
+
+    t.open(0).unwrap();
+    t.open(1).unwrap();
+    pump(&mut t, &mut h);
+    assert!(h.is_open(0) && h.is_open(1));
+
+    assert_eq!(t.write(0, &[0xAA; 200]).unwrap(), 200);
+    assert_eq!(t.write(1, &[0xBB; 200]).unwrap(), 200);
+
+    // Each message carries a similar amount of each stream's data
+    let mut buf = [0u8; 256];
+    let mut total = [0; 2];
+    while total != [200, 200] {
+        assert!(t.poll().unwrap());
+        h.poll().unwrap();
+        let (len0, len1) = (h.read(0, &mut buf).unwrap(), h.read(1, &mut buf).unwrap());
+        assert!(len0.abs_diff(len1) <= 1, "{len0} and {len1} bytes");
+        total[0] += len0;
+        total[1] += len1;
+    }
+}
+
+#[test]
+fn mux_can_own_its_channels() {
+    // A Target application holding its Mux, without borrowing
+    struct App<'a> {
+        mux: Mux<'a, AtomicChannelIo<'a>, 2, 64, 256, AtomicChannelIo<'a>>,
+    }
+
+    let target = SimTarget::new();
+    let t_rx = OwnedChannel::new(target.io(), ChannelActor::Consumer, CMD_CH, CH_SIZE).unwrap();
+    let t_tx = OwnedChannel::new(target.io(), ChannelActor::Producer, RSP_CH, CH_SIZE).unwrap();
+    let mut app = App {
+        mux: Mux::new(t_rx, t_tx),
+    };
+
+    let mut io = [target.io(); 2];
+    let [h_rx, h_tx] = &mut io;
+    let h_rx = Channel::from_target(h_rx, ChannelActor::Consumer, RSP_CH).unwrap();
+    let h_tx = Channel::from_target(h_tx, ChannelActor::Producer, CMD_CH).unwrap();
+    let mut h: TestMux<'_, 64> = Mux::new(h_rx, h_tx);
+
+    app.mux.open(0).unwrap();
+    assert_eq!(app.mux.write(0, b"hello").unwrap(), 5);
+    for _ in 0..10 {
+        app.mux.poll().unwrap();
+        h.poll().unwrap();
+    }
+    let mut buf = [0u8; 64];
+    assert_eq!(h.read(0, &mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], b"hello");
+}
+
+#[test]
+fn slow_reader_stalls_only_its_own_stream() {
+    const BUF: usize = 64;
+    let target = SimTarget::new();
+    let mut io = [target.io(); 4];
+    let (mut t, mut h) = muxes::<BUF>(&mut io);
+
+    t.open(0).unwrap();
+    t.open(1).unwrap();
+    pump(&mut t, &mut h);
+
+    // The Host only reads stream 1
+    let data = (0..200).map(|i| i as u8).collect::<Vec<_>>();
+    let (mut sent, mut received) = ([0; 2], Vec::new());
+    let mut buf = [0u8; BUF];
+    for _ in 0..100 {
+        for stream in 0..2 {
+            sent[stream] += t.write(stream as u8, &data[sent[stream]..]).unwrap();
+        }
+        pump(&mut t, &mut h);
+        let len = h.read(1, &mut buf).unwrap();
+        received.extend_from_slice(&buf[..len]);
+    }
+    assert_eq!(received, data);
+
+    // Stream 0 sent only as much as it was given credit for
+    assert_eq!(h.available(0), BUF);
+    assert_eq!(sent[0], BUF * 2);
+
+    // Reading stream 0 lets it continue
+    assert_eq!(h.read(0, &mut buf).unwrap(), BUF);
+    pump(&mut t, &mut h);
+    assert_eq!(h.available(0), BUF);
+}
+
+#[test]
+fn reopened_stream_is_closed_first() {
+    let target = SimTarget::new();
+    let mut io = [target.io(); 4];
+    let (mut t, mut h) = muxes::<64>(&mut io);
+
+    t.open(0).unwrap();
+    pump(&mut t, &mut h);
+    assert!(h.is_open(0));
+
+    // Can't reopen until the Host has been told about the close
+    t.close(0).unwrap();
+    assert_eq!(t.open(0), Err(Error::Busy));
+    t.poll().unwrap();
+    h.poll().unwrap();
+    assert!(!h.is_open(0));
+
+    t.open(0).unwrap();
+    pump(&mut t, &mut h);
+    assert!(t.is_open(0) && h.is_open(0));
+}
+
+#[test]
+fn reopened_stream_ignores_credit_granted_before_close() {
+    let target = SimTarget::new();
+    let mut io = [target.io(); 4];
+    let [t_rx, t_tx, h_rx, h_tx] = &mut io;
+    let t_rx = Channel::new(t_rx, ChannelActor::Consumer, CMD_CH, CH_SIZE).unwrap();
+    let t_tx = Channel::new(t_tx, ChannelActor::Producer, RSP_CH, CH_SIZE).unwrap();
+    let mut t: TestMux<'_, 64> = Mux::new(t_rx, t_tx);
+
+    // The Host side sends raw frames, granting credit on stream 0
+    let mut h_rx = Channel::from_target(h_rx, ChannelActor::Consumer, RSP_CH).unwrap();
+    let mut h_tx = Channel::from_target(h_tx, ChannelActor::Producer, CMD_CH).unwrap();
+    const CREDIT: [u8; 8] = [0, 3, 4, 0, 64, 0, 0, 0];
+    let mut msg = [0u8; CH_SIZE];
+
+    // The Target's open carries its own credit
+    t.open(0).unwrap();
+    t.poll().unwrap();
+    assert_eq!(h_rx.consume_bytes(&mut msg), Ok(4 + CREDIT.len()));
+    h_tx.publish_bytes(&CREDIT).unwrap();
+    t.poll().unwrap();
+
+    t.close(0).unwrap();
+    t.poll().unwrap();
+    assert_eq!(h_rx.consume_bytes(&mut msg), Ok(4));
+    assert_eq!(&msg[..4], [0, 2, 0, 0]);
+
+    // Sent before the Host received the close
+    h_tx.publish_bytes(&CREDIT).unwrap();
+    t.poll().unwrap();
+
+    // No data is sent, as the Host hasn't granted credit since
+    t.open(0).unwrap();
+    assert_eq!(t.write(0, b"data").unwrap(), 4);
+    t.poll().unwrap();
+    assert_eq!(h_rx.consume_bytes(&mut msg), Ok(4 + CREDIT.len()));
+    assert_eq!(&msg[..6], [0, 1, 0, 0, 0, 3]);
+    assert!(!t.poll().unwrap());
+}
+
+#[test]
+fn close_by_other_side_reports_discarded_data() {
+    let target = SimTarget::new();
+    let mut io = [target.io(); 4];
+    let (mut t, mut h) = muxes::<64>(&mut io);
+
+    h.open(0).unwrap();
+    pump(&mut t, &mut h);
+    assert_eq!(t.write(0, b"unsent").unwrap(), 6);
+
+    // The Target receives the close before sending its data
+    h.close(0).unwrap();
+    h.poll().unwrap();
+    t.poll().unwrap();
+    assert!(!t.is_open(0));
+    assert_eq!(t.write(0, b"more"), Err(Error::StreamClosed));
+    assert_eq!(t.write(0, b"more"), Err(Error::InvalidOperation));
+
+    pump(&mut t, &mut h);
+    assert_eq!(h.available(0), 0);
+}
+
+#[test]
+fn oversized_message_is_discarded() {
+    let target = SimTarget::new();
+    let mut io = [target.io(); 4];
+    let [t_rx, t_tx, h_rx, h_tx] = &mut io;
+    let t_rx = Channel::new(t_rx, ChannelActor::Consumer, CMD_CH, CH_SIZE).unwrap();
+    let t_tx = Channel::new(t_tx, ChannelActor::Producer, RSP_CH, CH_SIZE).unwrap();
+    let mut t: Mux<'_, _, 2, 64, 32> = Mux::new(t_rx, t_tx);
+    let h_rx = Channel::from_target(h_rx, ChannelActor::Consumer, RSP_CH).unwrap();
+    let h_tx = Channel::from_target(h_tx, ChannelActor::Producer, CMD_CH).unwrap();
+    let mut h: TestMux<'_, 64> = Mux::new(h_rx, h_tx);
+
+    h.open(0).unwrap();
+    for _ in 0..10 {
+        t.poll().unwrap();
+        h.poll().unwrap();
+    }
+    assert!(t.is_open(0));
+
+    // The Host's message is larger than the Target's message buffer
+    assert_eq!(h.write(0, &[0xAA; 64]).unwrap(), 64);
+    assert!(h.poll().unwrap());
+    assert!(t.poll().unwrap());
+    assert_eq!(t.available(0), 0);
+
+    // Later messages are still received, in both directions
+    h.open(1).unwrap();
+    for _ in 0..10 {
+        t.poll().unwrap();
+        h.poll().unwrap();
+    }
+    assert_eq!(h.write(1, b"hello").unwrap(), 5);
+    assert_eq!(t.write(0, b"world").unwrap(), 5);
+    for _ in 0..10 {
+        t.poll().unwrap();
+        h.poll().unwrap();
+    }
+    let mut buf = [0u8; 64];
+    assert_eq!(t.read(1, &mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], b"hello");
+    assert_eq!(h.read(0, &mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], b"world");
+}
+
+#[test]
+fn async_write_times_out_without_credit() {
+    let target = SimTarget::new();
+    // The Target creates its channels, but never polls them
+    let _channels = target.channels();
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut mux = AsyncMux::<_, _, Delay>::new(&mut reader, &mut writer, target.config(), 2, 64);
+
+    block_on(mux.open(0, MaxWaits(3))).unwrap();
+    assert_eq!(
+        block_on(mux.write(0, b"data", MaxWaits(3))),
+        Err(Error::Timeout)
+    );
+    assert_eq!(block_on(mux.close(0, MaxWaits(3))), Err(Error::Timeout));
+    assert_eq!(block_on(mux.open(0, MaxWaits(3))), Err(Error::Timeout));
+}
@@ -0,0 +1,111 @@
+//! Tests for Pipe and AsyncPipe, connected to each other over a simulated
+//! Target's channels.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(all(feature = "pipe", feature = "async"))]
+
+mod common;
+
+use std::pin::pin;
+use std::thread;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::{Channel, ChannelActor, OwnedChannel};
+use airfrog_rpc::pipe::{AsyncPipe, Pipe};
+
+use common::{CH_SIZE, CMD_CH, Delay, RSP_CH, SimTarget, block_on, poll_times};
+
+#[test]
+fn target_publishes_buffered_data_while_reading() {
+    let target = SimTarget::new();
+    // Created before the Host connects
+    let (mut cmd_io, mut rsp_io) = (target.io(), target.io());
+    let cmd = Channel::new(&mut cmd_io, ChannelActor::Consumer, CMD_CH, CH_SIZE).unwrap();
+    let rsp = Channel::new(&mut rsp_io, ChannelActor::Producer, RSP_CH, CH_SIZE).unwrap();
+
+    thread::scope(|s| {
+        // Writes a prompt, then echoes a command in upper case
+        s.spawn(move || {
+            use embedded_io::{Read, Write};
+
+            let mut pipe: Pipe<_, CH_SIZE> = Pipe::new(cmd, rsp).unwrap();
+            pipe.write_all(b"ready").unwrap();
+            // Buffered, as the Host hasn't read the first write
+            pipe.write_all(b"-set").unwrap();
+
+            let mut buf = [0u8; 4];
+            pipe.read_exact(&mut buf).unwrap();
+            pipe.write_all(&buf.to_ascii_uppercase()).unwrap();
+            pipe.flush().unwrap();
+        });
+
+        use embedded_io_async::{Read, Write};
+
+        let (mut reader, mut writer) = (target.clone(), target.clone());
+        let mut pipe = AsyncPipe::<_, _, Delay>::new(&mut reader, &mut writer, target.config());
+
+        // Read in parts, across the Target's messages
+        let mut prompt = [0u8; 9];
+        let received = {
+            let (first, second) = prompt.split_at_mut(3);
+            let mut read = pin!(async {
+                pipe.read_exact(first).await?;
+                pipe.read_exact(second).await
+            });
+            (0..2000).find_map(|_| poll_times(read.as_mut(), 1))
+        };
+        // Unblock the Target even if the prompt didn't arrive
+        block_on(pipe.write_all(b"ping")).unwrap();
+        block_on(pipe.flush()).unwrap();
+        assert_eq!(received, Some(Ok(())), "Prompt not received");
+        assert_eq!(&prompt, b"ready-set");
+
+        let mut echo = [0u8; 4];
+        block_on(pipe.read_exact(&mut echo)).unwrap();
+        assert_eq!(&echo, b"PING");
+    });
+}
+
+#[test]
+fn pipe_can_own_its_channels() {
+    use embedded_io::{Read, Write};
+
+    let target = SimTarget::new();
+    let (cmd, rsp) = target.channels();
+    let mut t: Pipe<_, CH_SIZE, _> = Pipe::new(cmd, rsp).unwrap();
+
+    // A second Pipe on the Host side of the same channels
+    let h_rx = OwnedChannel::from_target(target.io(), ChannelActor::Consumer, RSP_CH).unwrap();
+    let h_tx = OwnedChannel::from_target(target.io(), ChannelActor::Producer, CMD_CH).unwrap();
+    let mut h: Pipe<_, CH_SIZE, _> = Pipe::new(h_rx, h_tx).unwrap();
+
+    t.write_all(b"hello").unwrap();
+    t.flush().unwrap();
+    let mut buf = [0u8; 5];
+    h.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+
+    h.write_all(b"world").unwrap();
+    h.flush().unwrap();
+    t.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"world");
+}
+
+#[test]
+fn buffer_must_fit_any_message() {
+    const CAPACITY: usize = CH_SIZE - 20;
+
+    let target = SimTarget::new();
+    let (mut cmd, rsp) = target.channels();
+    assert_eq!(cmd.data_capacity(), Ok(CAPACITY));
+    assert!(matches!(
+        Pipe::<_, { CAPACITY - 1 }, _>::new(cmd, rsp),
+        Err(Error::BufferTooSmall)
+    ));
+
+    let (cmd, rsp) = target.channels();
+    assert!(Pipe::<_, CAPACITY, _>::new(cmd, rsp).is_ok());
+}
@@ -0,0 +1,278 @@
+//! Tests for PipelinedRpcClient, with a simulated Target using a
+//! CommandQueue to answer commands out of order.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(feature = "async")]
+
+mod common;
+
+use std::pin::{Pin, pin};
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::{CommandQueue, MaxWaits, NoDeadline, Polled};
+use airfrog_rpc::client::PipelinedRpcClient;
+use airfrog_rpc::envelope::Envelope;
+
+use common::{Delay, SimTarget, TargetChannel, block_on, poll_times};
+
+const PAYLOADS: [&[u8]; 3] = [b"first", b"second", b"third"];
+
+type Queue = CommandQueue<4, 32>;
+
+// Polls each unfinished request once
+fn poll_all<F: Future>(requests: &mut [Pin<Box<F>>], results: &mut [Option<F::Output>]) {
+    for (request, result) in requests.iter_mut().zip(results) {
+        if result.is_none() {
+            *result = poll_times(request.as_mut(), 1);
+        }
+    }
+}
+
+// Request IDs of the queued commands
+fn queued(queue: &Queue) -> Vec<u32> {
+    queue
+        .iter()
+        .map(|(envelope, _)| envelope.request_id)
+        .collect()
+}
+
+// Responds to a queued command with its payload in upper case, polling
+// `poll` while the Host has not consumed the previous response
+fn respond(queue: &mut Queue, rsp: &mut TargetChannel<'_>, id: u32, mut poll: impl FnMut()) {
+    let response = queue.get(id).unwrap().1.to_ascii_uppercase();
+    for _ in 0..100 {
+        match queue.respond(rsp, id, 0, &response) {
+            Ok(()) => return,
+            Err(Error::Busy) => poll(),
+            Err(e) => panic!("Target failed to respond: {e:?}"),
+        }
+    }
+    panic!("Host did not consume response");
+}
+
+#[test]
+fn responses_out_of_order_reach_their_requests() {
+    let target = SimTarget::new();
+    let (mut cmd, mut rsp) = target.channels();
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let client =
+        PipelinedRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config(), 3);
+    let mut queue = Queue::new();
+
+    let mut requests =
+        [0, 1, 2].map(|i| Box::pin(client.request(i as u8, PAYLOADS[i], NoDeadline)));
+    let mut results = [None, None, None];
+
+    // All three commands are sent before any response
+    for _ in 0..100 {
+        poll_all(&mut requests, &mut results);
+        queue.poll(&mut cmd).unwrap();
+        if queue.len() == 3 {
+            break;
+        }
+    }
+    assert_eq!(queue.len(), 3);
+    assert_eq!(client.in_flight(), 3);
+
+    for id in queued(&queue).into_iter().rev() {
+        respond(&mut queue, &mut rsp, id, || {
+            poll_all(&mut requests, &mut results)
+        });
+    }
+    for _ in 0..100 {
+        poll_all(&mut requests, &mut results);
+    }
+
+    for (i, result) in results.into_iter().enumerate() {
+        let (envelope, payload) = result.unwrap().unwrap();
+        assert_eq!(envelope.msg_type, i as u8);
+        assert_eq!(payload, PAYLOADS[i].to_ascii_uppercase());
+    }
+    assert!(queue.is_empty());
+    assert_eq!(client.in_flight(), 0);
+}
+
+#[test]
+fn window_limits_requests_in_flight() {
+    let target = SimTarget::new();
+    let (mut cmd, mut rsp) = target.channels();
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let client =
+        PipelinedRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config(), 2);
+    let mut queue = Queue::new();
+
+    let mut requests =
+        [0, 1, 2].map(|i| Box::pin(client.request(i as u8, PAYLOADS[i], NoDeadline)));
+    let mut results = [None, None, None];
+    for _ in 0..20 {
+        poll_all(&mut requests, &mut results);
+        queue.poll(&mut cmd).unwrap();
+    }
+    assert_eq!(queue.len(), 2);
+    assert_eq!(client.in_flight(), 2);
+
+    // Answering one lets the third be sent
+    let answered = queued(&queue)[0];
+    respond(&mut queue, &mut rsp, answered, || {});
+    for _ in 0..20 {
+        poll_all(&mut requests, &mut results);
+        queue.poll(&mut cmd).unwrap();
+    }
+    assert_eq!(queue.len(), 2);
+    assert!(!queued(&queue).contains(&answered));
+    assert_eq!(results.iter().filter(|result| result.is_some()).count(), 1);
+}
+
+#[test]
+fn abandoned_request_times_out() {
+    let target = SimTarget::new();
+    let (mut cmd, mut rsp) = target.channels();
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let client =
+        PipelinedRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config(), 1)
+            .with_abandoned_timeout(5);
+    let mut queue = Queue::new();
+
+    // Dropped once sent
+    {
+        let mut first = pin!(client.request(0, PAYLOADS[0], NoDeadline));
+        for _ in 0..10 {
+            assert!(poll_times(first.as_mut(), 1).is_none());
+            queue.poll(&mut cmd).unwrap();
+            if queue.len() == 1 {
+                break;
+            }
+        }
+    }
+    assert_eq!(client.in_flight(), 1);
+    let first_id = queued(&queue)[0];
+
+    // The Target doesn't respond to the first command, so the second is sent
+    // once the first times out
+    let mut second = pin!(client.request(1, PAYLOADS[1], NoDeadline));
+    for _ in 0..20 {
+        assert!(poll_times(second.as_mut(), 1).is_none());
+        queue.poll(&mut cmd).unwrap();
+        if queue.len() == 2 {
+            break;
+        }
+    }
+    assert_eq!(queue.len(), 2);
+    let second_id = queued(&queue)
+        .into_iter()
+        .find(|&id| id != first_id)
+        .unwrap();
+
+    // The late response is discarded
+    respond(&mut queue, &mut rsp, first_id, || {});
+    let mut result = None;
+    respond(&mut queue, &mut rsp, second_id, || {
+        result = poll_times(second.as_mut(), 1)
+    });
+    for _ in 0..10 {
+        if result.is_some() {
+            break;
+        }
+        result = poll_times(second.as_mut(), 1);
+    }
+    let (_, payload) = result.unwrap().unwrap();
+    assert_eq!(payload, b"SECOND");
+    assert_eq!(client.in_flight(), 0);
+}
+
+#[test]
+fn abandoned_requests_can_be_forgotten() {
+    let target = SimTarget::new();
+    let (mut cmd, _rsp) = target.channels();
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let client =
+        PipelinedRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config(), 2);
+    let mut queue = Queue::new();
+
+    let mut requests = [0, 1].map(|i| Box::pin(client.request(i as u8, PAYLOADS[i], NoDeadline)));
+    let mut results = [None, None];
+    for _ in 0..20 {
+        poll_all(&mut requests, &mut results);
+        queue.poll(&mut cmd).unwrap();
+    }
+    assert_eq!(client.in_flight(), 2);
+
+    drop(requests);
+    assert_eq!(client.in_flight(), 2);
+    assert_eq!(client.forget_abandoned(), 2);
+    assert_eq!(client.in_flight(), 0);
+}
+
+#[test]
+fn oversized_command_is_discarded_and_answered() {
+    let target = SimTarget::new();
+    let (mut cmd, mut rsp) = target.channels();
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let client =
+        PipelinedRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config(), 2);
+    let mut queue = Queue::new();
+
+    let oversized = [0xAA; 33];
+    let mut request = pin!(client.request(7, &oversized, NoDeadline));
+    let envelope = loop {
+        assert!(poll_times(request.as_mut(), 1).is_none());
+        match queue.poll(&mut cmd).unwrap() {
+            Polled::Discarded(envelope) => break envelope,
+            Polled::Idle => continue,
+            Polled::Queued => panic!("Oversized command queued"),
+        }
+    };
+    assert_eq!((envelope.msg_type, envelope.length), (7, 33));
+    assert!(queue.is_empty());
+    assert_eq!(queue.poll(&mut cmd), Ok(Polled::Idle));
+
+    rsp.publish_response(&envelope, Envelope::FLAG_ERROR, &[])
+        .unwrap();
+    let (envelope, payload) = (0..10)
+        .find_map(|_| poll_times(request.as_mut(), 1))
+        .unwrap()
+        .unwrap();
+    assert_ne!(envelope.flags & Envelope::FLAG_ERROR, 0);
+    assert!(payload.is_empty());
+
+    // The queue continues with the next command
+    let mut request = pin!(client.request(8, PAYLOADS[0], NoDeadline));
+    for _ in 0..10 {
+        assert!(poll_times(request.as_mut(), 1).is_none());
+        if queue.poll(&mut cmd).unwrap() == Polled::Queued {
+            break;
+        }
+    }
+    assert_eq!(queue.len(), 1);
+}
+
+#[test]
+fn unanswered_request_times_out() {
+    let target = SimTarget::new();
+    let (mut cmd, _rsp) = target.channels();
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let client =
+        PipelinedRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config(), 1);
+    let mut queue = Queue::new();
+
+    // The Target queues the command, but never answers it
+    let mut request = pin!(client.request(0, PAYLOADS[0], MaxWaits(5)));
+    let result = (0..20).find_map(|_| {
+        queue.poll(&mut cmd).unwrap();
+        poll_times(request.as_mut(), 1)
+    });
+    assert_eq!(result, Some(Err(Error::Timeout)));
+    assert_eq!(queue.len(), 1);
+
+    // The request still counts against the window until forgotten
+    assert_eq!(client.in_flight(), 1);
+    assert_eq!(
+        block_on(client.request(1, PAYLOADS[1], MaxWaits(5))),
+        Err(Error::Timeout)
+    );
+    assert_eq!(client.forget_abandoned(), 1);
+    assert_eq!(client.in_flight(), 0);
+}
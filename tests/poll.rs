@@ -0,0 +1,52 @@
+//! Tests for Poller, waiting between polls of a Target.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(feature = "async")]
+
+mod common;
+
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::task::{Context, Waker};
+use std::time::Duration;
+
+use airfrog_rpc::channel::{AsyncDelay, PollPolicy, Poller};
+
+use common::CountingWaker;
+
+// Never completes, so the test fails if the Poller delays
+struct NeverDelay;
+
+impl AsyncDelay for NeverDelay {
+    async fn delay() {
+        std::future::pending::<()>().await
+    }
+}
+
+#[test]
+fn spinning_yields_once_per_wait() {
+    const SPINS: u32 = 3;
+    let counter = Arc::new(CountingWaker::default());
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let policy = PollPolicy::adaptive(SPINS, Duration::from_millis(1), Duration::from_millis(1));
+    let mut poller = Poller::new(policy);
+    for spin in 1..=SPINS {
+        let mut wait = pin!(poller.wait::<NeverDelay>());
+        assert!(wait.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(counter.0.load(Ordering::SeqCst), spin as usize);
+        assert!(wait.as_mut().poll(&mut cx).is_ready());
+    }
+    assert_eq!(poller.polls(), SPINS);
+
+    // Then delays
+    let mut wait = pin!(poller.wait::<NeverDelay>());
+    assert!(wait.as_mut().poll(&mut cx).is_pending());
+    assert!(wait.as_mut().poll(&mut cx).is_pending());
+    assert_eq!(counter.0.load(Ordering::SeqCst), SPINS as usize);
+}
@@ -0,0 +1,42 @@
+//! Tests for StaticChannelRegion and the static_channel macro.
+//!
+//! Channels can't be created from the regions here, as `RamChannelIo` needs
+//! the 32-bit address space of a Target.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+use airfrog_rpc::channel::{ChannelActor, OwnedRamChannel, StaticChannelRegion};
+use airfrog_rpc::{Result, static_channel};
+
+// Expands the macro, declaring the regions and exporting their lengths.
+// Never called, as creating the channels needs a Target.
+#[allow(dead_code)]
+fn target_channels() -> Result<(OwnedRamChannel, OwnedRamChannel)> {
+    let cmd = static_channel!(TEST_CMD_CH, 256, ChannelActor::Consumer)?;
+    let rsp = static_channel!(TEST_RSP_CH, 128, ChannelActor::Producer, ".uninit.test")?;
+    Ok((cmd, rsp))
+}
+
+unsafe extern "C" {
+    static TEST_CMD_CH_LEN: u32;
+    static TEST_RSP_CH_LEN: u32;
+}
+
+#[test]
+fn macro_exports_region_lengths() {
+    assert_eq!(unsafe { TEST_CMD_CH_LEN }, 256);
+    assert_eq!(unsafe { TEST_RSP_CH_LEN }, 128);
+}
+
+#[test]
+fn region_is_sized_and_aligned() {
+    static REGION: StaticChannelRegion<64> = StaticChannelRegion::new();
+
+    assert_eq!(StaticChannelRegion::<64>::SIZE, 64);
+    assert_eq!(REGION.len(), 64);
+    assert_eq!(size_of::<StaticChannelRegion<64>>(), 64);
+    assert_eq!(align_of::<StaticChannelRegion<64>>(), 4);
+    assert!((&raw const REGION as usize).is_multiple_of(4));
+}
@@ -0,0 +1,211 @@
+//! Tests for Router, passing messages between simulated Targets.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+mod common;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::{ChannelActor, OwnedChannel};
+use airfrog_rpc::client::{PortStats, RouteMatch, Router};
+
+use common::{CH_SIZE, CMD_CH, Delay, RSP_CH, SimTarget, TargetChannel, block_on};
+
+// Consumes a message from a Target's inbound channel, if one is available
+fn consume(inbound: &mut TargetChannel<'_>) -> Option<Vec<u8>> {
+    let mut buf = [0u8; CH_SIZE];
+    match inbound.consume_bytes(&mut buf) {
+        Ok(len) => Some(buf[..len].to_vec()),
+        Err(Error::NoData) => None,
+        Err(e) => panic!("Target failed to consume message: {e:?}"),
+    }
+}
+
+#[test]
+fn routes_messages_between_targets() {
+    let (a, b) = (SimTarget::new(), SimTarget::new());
+    let (mut a_in, mut a_out) = a.channels();
+    let (mut b_in, mut b_out) = b.channels();
+    let (mut a_reader, mut a_writer) = (a.clone(), a.clone());
+    let (mut b_reader, mut b_writer) = (b.clone(), b.clone());
+
+    let mut router = Router::<_, _, Delay>::new();
+    let port_a = router.add_port(&mut a_reader, &mut a_writer, a.config());
+    let port_b = router.add_port(&mut b_reader, &mut b_writer, b.config());
+    router.add_route(port_a, RouteMatch::Any, port_b).unwrap();
+    router.add_route(port_b, RouteMatch::Any, port_a).unwrap();
+
+    a_out.publish_bytes(b"to b").unwrap();
+    b_out.publish_bytes(b"to a").unwrap();
+    assert_eq!(block_on(router.poll()).unwrap(), 2);
+    assert_eq!(consume(&mut b_in).as_deref(), Some(&b"to b"[..]));
+    assert_eq!(consume(&mut a_in).as_deref(), Some(&b"to a"[..]));
+
+    // Nothing further to route
+    assert_eq!(block_on(router.poll()).unwrap(), 0);
+
+    let expected = PortStats {
+        received: 1,
+        bytes_received: 4,
+        delivered: 1,
+        bytes_delivered: 4,
+        dropped: 0,
+        busy: 0,
+    };
+    assert_eq!(router.stats(port_a), Some(&expected));
+    assert_eq!(router.stats(port_b), Some(&expected));
+    assert_eq!(router.stats(2), None);
+}
+
+#[test]
+fn first_matching_route_is_used() {
+    let (a, b, c) = (SimTarget::new(), SimTarget::new(), SimTarget::new());
+    let (_a_in, mut a_out) = a.channels();
+    let (mut b_in, _b_out) = b.channels();
+    let (mut c_in, _c_out) = c.channels();
+    let (mut a_reader, mut a_writer) = (a.clone(), a.clone());
+    let (mut b_reader, mut b_writer) = (b.clone(), b.clone());
+    let (mut c_reader, mut c_writer) = (c.clone(), c.clone());
+
+    let mut router = Router::<_, _, Delay>::new();
+    let port_a = router.add_port(&mut a_reader, &mut a_writer, a.config());
+    let port_b = router.add_port(&mut b_reader, &mut b_writer, b.config());
+    let port_c = router.add_port(&mut c_reader, &mut c_writer, c.config());
+    router
+        .add_route(port_a, RouteMatch::Stream(1), port_b)
+        .unwrap();
+    router
+        .add_route(port_a, RouteMatch::Address(0x100..0x200), port_c)
+        .unwrap();
+    router.add_route(port_a, RouteMatch::Any, port_b).unwrap();
+    assert_eq!(
+        router.add_route(port_a, RouteMatch::Any, 3),
+        Err(Error::InvalidOperation)
+    );
+
+    a_out.publish_bytes(&[1, 0x01, 0, 0]).unwrap();
+    block_on(router.poll()).unwrap();
+    assert_eq!(consume(&mut b_in), Some(vec![1, 0x01, 0, 0]));
+
+    a_out.publish_bytes(&[0, 0x01, 0, 0]).unwrap();
+    block_on(router.poll()).unwrap();
+    assert_eq!(consume(&mut c_in), Some(vec![0, 0x01, 0, 0]));
+
+    a_out.publish_bytes(&[0, 0x02, 0, 0]).unwrap();
+    block_on(router.poll()).unwrap();
+    assert_eq!(consume(&mut b_in), Some(vec![0, 0x02, 0, 0]));
+    assert_eq!(consume(&mut c_in), None);
+}
+
+#[test]
+fn unrouted_message_is_dropped() {
+    let (a, b) = (SimTarget::new(), SimTarget::new());
+    let (_a_in, mut a_out) = a.channels();
+    let (mut b_in, _b_out) = b.channels();
+    let (mut a_reader, mut a_writer) = (a.clone(), a.clone());
+    let (mut b_reader, mut b_writer) = (b.clone(), b.clone());
+
+    let mut router = Router::<_, _, Delay>::new();
+    let port_a = router.add_port(&mut a_reader, &mut a_writer, a.config());
+    let port_b = router.add_port(&mut b_reader, &mut b_writer, b.config());
+    router
+        .add_route(port_a, RouteMatch::Custom(|msg| msg.len() > 4), port_b)
+        .unwrap();
+
+    a_out.publish_bytes(b"tiny").unwrap();
+    assert_eq!(block_on(router.poll()).unwrap(), 0);
+    assert_eq!(consume(&mut b_in), None);
+    assert_eq!(router.stats(port_a).unwrap().dropped, 1);
+
+    a_out.publish_bytes(b"larger").unwrap();
+    assert_eq!(block_on(router.poll()).unwrap(), 1);
+    assert_eq!(consume(&mut b_in).as_deref(), Some(&b"larger"[..]));
+}
+
+#[test]
+fn busy_destination_holds_message_and_applies_backpressure() {
+    let (a, b) = (SimTarget::new(), SimTarget::new());
+    let (_a_in, mut a_out) = a.channels();
+    let (mut b_in, _b_out) = b.channels();
+    let (mut a_reader, mut a_writer) = (a.clone(), a.clone());
+    let (mut b_reader, mut b_writer) = (b.clone(), b.clone());
+
+    let mut router = Router::<_, _, Delay>::new();
+    let port_a = router.add_port(&mut a_reader, &mut a_writer, a.config());
+    let port_b = router.add_port(&mut b_reader, &mut b_writer, b.config());
+    router.add_route(port_a, RouteMatch::Any, port_b).unwrap();
+
+    a_out.publish_bytes(b"one").unwrap();
+    assert_eq!(block_on(router.poll()).unwrap(), 1);
+
+    // B has not consumed "one", so "two" is held
+    a_out.publish_bytes(b"two").unwrap();
+    assert_eq!(block_on(router.poll()).unwrap(), 0);
+    assert_eq!(router.stats(port_b).unwrap().busy, 1);
+
+    // "three" is not consumed from A while "two" is held
+    a_out.publish_bytes(b"three").unwrap();
+    assert_eq!(block_on(router.poll()).unwrap(), 0);
+    assert!(!a_out.can_publish().unwrap());
+
+    assert_eq!(consume(&mut b_in).as_deref(), Some(&b"one"[..]));
+    assert_eq!(block_on(router.poll()).unwrap(), 1);
+    assert_eq!(consume(&mut b_in).as_deref(), Some(&b"two"[..]));
+    assert_eq!(block_on(router.poll()).unwrap(), 1);
+    assert_eq!(consume(&mut b_in).as_deref(), Some(&b"three"[..]));
+
+    assert_eq!(router.stats(port_a).unwrap().received, 3);
+    assert_eq!(router.stats(port_b).unwrap().delivered, 3);
+    assert_eq!(router.stats(port_b).unwrap().busy, 2);
+}
+
+#[test]
+fn message_too_large_for_destination_is_dropped() {
+    let (a, b) = (SimTarget::new(), SimTarget::new());
+    let (_a_in, mut a_out) = a.channels();
+    // B's inbound channel is much smaller than A's outbound channel
+    let mut b_in = OwnedChannel::new(b.io(), ChannelActor::Consumer, CMD_CH, 64).unwrap();
+    let _b_out = OwnedChannel::new(b.io(), ChannelActor::Producer, RSP_CH, CH_SIZE).unwrap();
+    let (mut a_reader, mut a_writer) = (a.clone(), a.clone());
+    let (mut b_reader, mut b_writer) = (b.clone(), b.clone());
+
+    let mut router = Router::<_, _, Delay>::new();
+    let port_a = router.add_port(&mut a_reader, &mut a_writer, a.config());
+    let port_b = router.add_port(&mut b_reader, &mut b_writer, b.config());
+    router.add_route(port_a, RouteMatch::Any, port_b).unwrap();
+
+    a_out.publish_bytes(&[0xAA; 128]).unwrap();
+    assert_eq!(block_on(router.poll()).unwrap(), 0);
+    assert_eq!(router.stats(port_a).unwrap().dropped, 1);
+
+    // Later messages are still routed
+    a_out.publish_bytes(b"small").unwrap();
+    assert_eq!(block_on(router.poll()).unwrap(), 1);
+    assert_eq!(consume(&mut b_in).as_deref(), Some(&b"small"[..]));
+}
+
+#[test]
+fn message_is_held_while_destination_fails() {
+    let (a, b) = (SimTarget::new(), SimTarget::new());
+    let (_a_in, mut a_out) = a.channels();
+    let (mut b_in, _b_out) = b.channels();
+    let (mut a_reader, mut a_writer) = (a.clone(), a.clone());
+    let (mut b_reader, mut b_writer) = (b.clone(), b.clone());
+
+    let mut router = Router::<_, _, Delay>::new();
+    let port_a = router.add_port(&mut a_reader, &mut a_writer, a.config());
+    let port_b = router.add_port(&mut b_reader, &mut b_writer, b.config());
+    router.add_route(port_a, RouteMatch::Any, port_b).unwrap();
+
+    b.set_failing(true);
+    a_out.publish_bytes(b"held").unwrap();
+    assert!(block_on(router.poll()).is_err());
+    assert!(block_on(router.poll()).is_err());
+
+    b.set_failing(false);
+    assert_eq!(block_on(router.poll()).unwrap(), 1);
+    assert_eq!(consume(&mut b_in).as_deref(), Some(&b"held"[..]));
+    assert_eq!(router.stats(port_a).unwrap().received, 1);
+    assert_eq!(router.stats(port_a).unwrap().dropped, 0);
+}
@@ -0,0 +1,170 @@
+//! Tests for RpcServer, serving a Host over simulated Target channels.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+mod common;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::{ChannelActor, ChannelCb, ChannelIo, OwnedChannel};
+use airfrog_rpc::client::CommandDescription;
+use airfrog_rpc::server::{Command, Request, RpcServer, Status};
+use airfrog_rpc::system::ServerLimits;
+
+use common::{CMD_CH, RSP_CH, SimTarget, block_on, with_server};
+
+const ECHO: u8 = 0x01;
+
+fn echo(_: &mut (), req: &mut Request<'_>, rsp: &mut [u8]) -> Result<usize, Status> {
+    let payload = req.payload();
+    rsp.get_mut(..payload.len())
+        .ok_or(Status::ResponseTooLarge)?
+        .copy_from_slice(payload);
+    Ok(payload.len())
+}
+
+static COMMANDS: &[Command<()>] = &[Command::new(ECHO, "echo", echo)];
+
+static MORE_COMMANDS: &[Command<()>] = &[
+    Command::new(0x10, "echo_v2", echo)
+        .with_version(2)
+        .with_schema("[u8]", "[u8]"),
+    Command::new(0x11, "echo_typed", echo)
+        .with_version(3)
+        .with_schema("(v: u32)", "u32"),
+];
+
+// Name too long for a 32 byte response buffer
+static LONG_COMMANDS: &[Command<()>] = &[Command::new(
+    0x20,
+    "echo_with_a_very_long_descriptive_name",
+    echo,
+)];
+
+#[test]
+fn limits_are_those_of_the_server_buffer() {
+    let target = SimTarget::new();
+    with_server::<_, 32>(&target, &mut (), &[COMMANDS], |client| {
+        let limits = block_on(client.server_limits()).unwrap();
+        assert_eq!(
+            limits,
+            ServerLimits {
+                max_command: 31,
+                max_response: 31,
+            }
+        );
+
+        let payload = [0x55; 31];
+        assert_eq!(block_on(client.call(ECHO, &payload)).unwrap(), payload);
+        assert_eq!(
+            block_on(client.call(ECHO, &[0x55; 32])),
+            Err(Error::Status(Status::CommandTooLarge))
+        );
+
+        // The server is still usable afterwards
+        assert_eq!(block_on(client.call(ECHO, b"ok")).unwrap(), b"ok");
+    });
+}
+
+#[test]
+fn limits_are_those_of_the_channels() {
+    let target = SimTarget::new();
+    let capacity = target.channels().0.data_capacity().unwrap() as u32;
+    with_server::<_, 1024>(&target, &mut (), &[COMMANDS], |client| {
+        let limits = block_on(client.server_limits()).unwrap();
+        assert_eq!(limits.max_command, capacity - 1);
+        assert_eq!(limits.max_response, capacity - 1);
+    });
+}
+
+#[test]
+fn empty_and_unknown_commands_are_rejected() {
+    let target = SimTarget::new();
+    with_server::<_, 32>(&target, &mut (), &[COMMANDS], |client| {
+        assert_eq!(
+            block_on(client.request(&[])),
+            Ok(vec![Status::InvalidRequest as u8])
+        );
+        assert_eq!(
+            block_on(client.call(0x02, &[])),
+            Err(Error::Status(Status::UnknownCommand))
+        );
+    });
+}
+
+#[test]
+fn commands_larger_than_the_channel_are_discarded() {
+    let target = SimTarget::new();
+    let (cmd, rsp) = target.channels();
+    let mut server: RpcServer<_, (), 1024, _> = RpcServer::new(cmd, rsp, COMMANDS);
+
+    let mut host_cmd =
+        OwnedChannel::from_target(target.io(), ChannelActor::Producer, CMD_CH).unwrap();
+    let mut host_rsp =
+        OwnedChannel::from_target(target.io(), ChannelActor::Consumer, RSP_CH).unwrap();
+    let mut buf = [0; 64];
+
+    // A command claiming to be larger than the channel, but which would fit
+    // in the server's buffer
+    host_cmd.publish_bytes(&[ECHO, 1, 2, 3]).unwrap();
+    let capacity = host_cmd.data_capacity().unwrap() as u32;
+    target
+        .io()
+        .write_u32(CMD_CH + ChannelCb::data_size_offset(), capacity + 4)
+        .unwrap();
+
+    assert!(server.poll(&mut ()).unwrap());
+    let len = host_rsp.consume_bytes(&mut buf).unwrap();
+    assert_eq!(&buf[..len], [Status::CommandTooLarge as u8]);
+
+    // The command was discarded, so the server is still usable
+    host_cmd.publish_bytes(&[ECHO, 1, 2, 3]).unwrap();
+    assert!(server.poll(&mut ()).unwrap());
+    let len = host_rsp.consume_bytes(&mut buf).unwrap();
+    assert_eq!(&buf[..len], [Status::Ok as u8, 1, 2, 3]);
+}
+
+fn description(
+    id: u8,
+    version: u8,
+    name: &str,
+    request_schema: &str,
+    response_schema: &str,
+) -> CommandDescription {
+    CommandDescription {
+        id,
+        version,
+        name: name.into(),
+        request_schema: request_schema.into(),
+        response_schema: response_schema.into(),
+    }
+}
+
+#[test]
+fn describe_lists_commands_from_every_table() {
+    let target = SimTarget::new();
+    // Each description fits in the 32 byte response buffer, but together
+    // they don't, so are fetched one per request
+    with_server::<_, 32>(&target, &mut (), &[COMMANDS, MORE_COMMANDS], |client| {
+        assert_eq!(
+            block_on(client.describe()),
+            Ok(vec![
+                description(ECHO, 1, "echo", "", ""),
+                description(0x10, 2, "echo_v2", "[u8]", "[u8]"),
+                description(0x11, 3, "echo_typed", "(v: u32)", "u32"),
+            ])
+        );
+    });
+}
+
+#[test]
+fn describe_reports_descriptions_too_large_for_the_server() {
+    let target = SimTarget::new();
+    with_server::<_, 32>(&target, &mut (), &[COMMANDS, LONG_COMMANDS], |client| {
+        assert_eq!(
+            block_on(client.describe()),
+            Err(Error::Status(Status::ResponseTooLarge))
+        );
+    });
+}
@@ -0,0 +1,79 @@
+//! Tests for the client stubs generated by the service macro, talking to an
+//! RpcServer over simulated Target channels.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(all(feature = "macros", feature = "async"))]
+
+mod common;
+
+use airfrog_rpc::Error;
+
+use common::{SimTarget, block_on, with_server};
+
+#[airfrog_rpc::service(base = 0x10)]
+pub trait Led {
+    fn set(index: u8, on: bool);
+    fn get(index: u8) -> Option<bool>;
+}
+
+// Led with a changed argument type, and so a different fingerprint
+mod changed {
+    #[airfrog_rpc::service(base = 0x10)]
+    pub trait Led {
+        fn set(index: u16, on: bool);
+        fn get(index: u8) -> Option<bool>;
+    }
+}
+
+#[derive(Default)]
+struct App {
+    leds: [bool; 4],
+}
+
+impl Led for App {
+    fn set(&mut self, index: u8, on: bool) {
+        self.leds[index as usize] = on;
+    }
+
+    fn get(&mut self, index: u8) -> Option<bool> {
+        self.leds.get(index as usize).copied()
+    }
+}
+
+#[test]
+fn client_calls_service() {
+    let target = SimTarget::new();
+    let mut app = App::default();
+    with_server::<_, 64>(&target, &mut app, &[led::commands()], |client| {
+        let mut led = block_on(led::Client::connect(client)).unwrap();
+        block_on(led.set(2, true)).unwrap();
+        assert_eq!(block_on(led.get(2)), Ok(Some(true)));
+        assert_eq!(block_on(led.get(4)), Ok(None));
+    });
+    assert_eq!(app.leds, [false, false, true, false]);
+}
+
+#[test]
+fn connect_rejects_a_different_definition() {
+    let target = SimTarget::new();
+    with_server::<_, 64>(&target, &mut App::default(), &[led::commands()], |client| {
+        assert!(matches!(
+            block_on(changed::led::Client::connect(client)),
+            Err(Error::ServiceMismatch)
+        ));
+    });
+}
+
+#[test]
+fn connect_rejects_a_target_without_the_service() {
+    let target = SimTarget::new();
+    with_server::<_, 64>(&target, &mut (), &[&[]], |client| {
+        assert!(matches!(
+            block_on(led::Client::connect(client)),
+            Err(Error::ServiceMismatch)
+        ));
+    });
+}
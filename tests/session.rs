@@ -0,0 +1,197 @@
+//! Tests for SessionManager, driving several simulated Targets.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+mod common;
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use airfrog_rpc::Error;
+use airfrog_rpc::client::{SessionManager, TargetHealth};
+
+use common::{Action, Delay, SimTarget, StopOnDrop, block_on, serve};
+
+#[test]
+fn request_all_returns_results_in_target_order() {
+    let targets = [SimTarget::new(), SimTarget::new(), SimTarget::new()];
+    let stop = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        for (id, target) in targets.iter().enumerate() {
+            let (cmd, rsp) = target.channels();
+            let stop = &stop;
+            s.spawn(move || {
+                serve(cmd, rsp, stop, &AtomicU32::new(0), |command, _| {
+                    let mut response = vec![id as u8];
+                    response.extend_from_slice(command);
+                    Action::Respond(response)
+                })
+            });
+        }
+
+        let _stop = StopOnDrop(&stop);
+        let mut io = targets
+            .iter()
+            .map(|target| (target.clone(), target.clone()))
+            .collect::<Vec<_>>();
+        let mut sessions = SessionManager::<_, _, _, Delay>::new();
+        // Add out of order, to check results are in ID order
+        for (id, (reader, writer)) in io.iter_mut().enumerate().rev() {
+            sessions.add_target(id as u32, reader, writer, targets[id].config());
+        }
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(sessions.targets().copied().collect::<Vec<_>>(), [0, 1, 2]);
+
+        let results = block_on(sessions.request_all(b"ping"));
+        assert_eq!(
+            results,
+            [
+                (0, Ok(b"\x00ping".to_vec())),
+                (1, Ok(b"\x01ping".to_vec())),
+                (2, Ok(b"\x02ping".to_vec())),
+            ]
+        );
+        for id in 0..3 {
+            assert_eq!(sessions.health(&id).unwrap().successes, 1);
+        }
+
+        assert_eq!(block_on(sessions.request(&1, b"one")).unwrap(), b"\x01one");
+        assert_eq!(
+            block_on(sessions.request(&3, b"none")),
+            Err(Error::InvalidOperation)
+        );
+        assert!(sessions.remove_target(&1));
+        assert!(!sessions.remove_target(&1));
+        assert_eq!(sessions.targets().copied().collect::<Vec<_>>(), [0, 2]);
+    });
+}
+
+#[test]
+fn fan_out_runs_targets_concurrently() {
+    const TARGETS: u32 = 3;
+    let targets = [SimTarget::new(), SimTarget::new(), SimTarget::new()];
+    let stop = AtomicBool::new(false);
+    let received = AtomicU32::new(0);
+    let concurrent = AtomicU32::new(0);
+
+    thread::scope(|s| {
+        for target in &targets {
+            let (cmd, rsp) = target.channels();
+            let (stop, received, concurrent) = (&stop, &received, &concurrent);
+            s.spawn(move || {
+                serve(cmd, rsp, stop, &AtomicU32::new(0), |command, _| {
+                    // Only respond once every Target has its command, which
+                    // only happens if the requests are concurrent
+                    received.fetch_add(1, Ordering::SeqCst);
+                    let end = Instant::now() + Duration::from_secs(5);
+                    while received.load(Ordering::SeqCst) < TARGETS && Instant::now() < end {
+                        thread::yield_now();
+                    }
+                    if received.load(Ordering::SeqCst) == TARGETS {
+                        concurrent.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Action::Respond(command.to_vec())
+                })
+            });
+        }
+
+        let _stop = StopOnDrop(&stop);
+        let mut io = targets
+            .iter()
+            .map(|target| (target.clone(), target.clone()))
+            .collect::<Vec<_>>();
+        let mut sessions = SessionManager::<_, _, _, Delay>::new();
+        for (id, (reader, writer)) in io.iter_mut().enumerate() {
+            sessions.add_target(id, reader, writer, targets[id].config());
+        }
+
+        let results = block_on(sessions.fan_out(async |client| {
+            let response = client.request(b"sync").await?;
+            Ok(response.len())
+        }));
+        assert_eq!(results, [(0, Ok(4)), (1, Ok(4)), (2, Ok(4))]);
+    });
+    assert_eq!(concurrent.load(Ordering::SeqCst), TARGETS);
+}
+
+#[test]
+fn failing_target_becomes_unhealthy_at_threshold() {
+    let targets = [SimTarget::new(), SimTarget::new()];
+    let stop = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        for target in &targets {
+            let (cmd, rsp) = target.channels();
+            let stop = &stop;
+            s.spawn(move || {
+                serve(cmd, rsp, stop, &AtomicU32::new(0), |command, _| {
+                    Action::Respond(command.to_vec())
+                })
+            });
+        }
+
+        let _stop = StopOnDrop(&stop);
+        let mut io = targets
+            .iter()
+            .map(|target| (target.clone(), target.clone()))
+            .collect::<Vec<_>>();
+        let mut sessions = SessionManager::<_, _, _, Delay>::new().with_failure_threshold(2);
+        for (id, (reader, writer)) in io.iter_mut().enumerate() {
+            sessions.add_target(id, reader, writer, targets[id].config());
+        }
+
+        targets[1].set_failing(true);
+        let results = block_on(sessions.request_all(b"status"));
+        assert_eq!(results[0], (0, Ok(b"status".to_vec())));
+        let error = results[1].1.clone().unwrap_err();
+
+        // One failure is below the threshold
+        assert!(sessions.is_healthy(&0));
+        assert!(sessions.is_healthy(&1));
+        assert_eq!(sessions.unhealthy().count(), 0);
+
+        block_on(sessions.request_all(b"status"));
+        assert!(sessions.is_healthy(&0));
+        assert!(!sessions.is_healthy(&1));
+        assert_eq!(sessions.unhealthy().copied().collect::<Vec<_>>(), [1]);
+        assert_eq!(
+            sessions.health(&1),
+            Some(&TargetHealth {
+                successes: 0,
+                failures: 2,
+                consecutive_failures: 2,
+                last_error: Some(error),
+            })
+        );
+
+        // A success clears the consecutive failures, but not the totals
+        targets[1].set_failing(false);
+        assert_eq!(block_on(sessions.request(&1, b"back")).unwrap(), b"back");
+        assert!(sessions.is_healthy(&1));
+        let health = sessions.health(&1).unwrap();
+        assert_eq!((health.successes, health.failures), (1, 2));
+        assert_eq!(health.consecutive_failures, 0);
+
+        sessions.reset_health(&1);
+        assert_eq!(sessions.health(&1), Some(&TargetHealth::default()));
+        assert!(!sessions.is_healthy(&2));
+    });
+}
+
+#[test]
+fn failure_threshold_is_at_least_one() {
+    let target = SimTarget::new();
+    let _channels = target.channels();
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut sessions = SessionManager::<_, _, _, Delay>::new().with_failure_threshold(0);
+    sessions.add_target("target", &mut reader, &mut writer, target.config());
+    assert!(sessions.is_healthy(&"target"));
+
+    target.set_failing(true);
+    assert!(block_on(sessions.request(&"target", b"fail")).is_err());
+    assert!(!sessions.is_healthy(&"target"));
+}
@@ -0,0 +1,108 @@
+//! Tests for SharedIo, sharing a simulated Target between tasks.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(feature = "shared")]
+
+mod common;
+
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::NoDeadline;
+use airfrog_rpc::client::{AsyncRpcClient, SharedIo};
+
+use common::{CH_SIZE, CountingWaker, Delay, SimTarget, StopOnDrop, block_on};
+
+#[test]
+fn waiting_task_sleeps_until_lock_released() {
+    let target = SimTarget::new();
+    let shared = SharedIo::new(target.clone(), target.clone());
+    let (a, b) = (shared.handle(), shared.handle());
+
+    let counter = Arc::new(CountingWaker::default());
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let held = a.try_lock().unwrap();
+    assert!(b.try_lock().is_none());
+    let mut waiting = pin!(b.lock());
+    for _ in 0..3 {
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+    }
+    // Not woken while the lock is held, so the task isn't polled again
+    assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+
+    // The lock is reentrant, so only the final release wakes the waiter
+    let again = block_on(a.lock());
+    drop(held);
+    assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+    drop(again);
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+    let Poll::Ready(lock) = waiting.as_mut().poll(&mut cx) else {
+        panic!("Lock not acquired after release");
+    };
+    assert!(shared.is_locked());
+    assert!(a.try_lock().is_none());
+    drop(lock);
+    assert!(!shared.is_locked());
+}
+
+#[test]
+fn requests_from_tasks_are_atomic() {
+    const TASKS: usize = 4;
+    const REQUESTS: usize = 20;
+    let target = SimTarget::new();
+    let shared = SharedIo::new(target.clone(), target.clone());
+    let stop = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        // Echoes each command
+        let (mut cmd, mut rsp) = target.channels();
+        let stop_flag = &stop;
+        s.spawn(move || {
+            let mut buf = [0u8; CH_SIZE];
+            while !stop_flag.load(Ordering::SeqCst) {
+                match cmd.consume_bytes(&mut buf) {
+                    Ok(len) => rsp
+                        .publish_bytes_when_ready(&buf[..len], thread::yield_now, NoDeadline)
+                        .unwrap(),
+                    Err(Error::NoData) => thread::yield_now(),
+                    Err(e) => panic!("Target failed to consume command: {e:?}"),
+                }
+            }
+        });
+
+        let _stop = StopOnDrop(&stop);
+        let tasks = (0..TASKS)
+            .map(|task| {
+                let (shared, target) = (&shared, &target);
+                s.spawn(move || {
+                    let handle = shared.handle();
+                    let (mut reader, mut writer) = (handle.reader(), handle.writer());
+                    let mut client = AsyncRpcClient::<_, _, Delay>::new(
+                        &mut reader,
+                        &mut writer,
+                        target.config(),
+                    );
+                    for request in 0..REQUESTS {
+                        let command = [task as u8, request as u8];
+                        let response = block_on(handle.request(&mut client, &command)).unwrap();
+                        assert_eq!(response, command);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.join().unwrap();
+        }
+    });
+    assert!(!shared.is_locked());
+}
@@ -0,0 +1,174 @@
+//! Tests for ChannelStream and ChannelSink, exchanging messages with a
+//! simulated Target.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(feature = "stream")]
+
+mod common;
+
+use std::future::poll_fn;
+use std::pin::{Pin, pin};
+use std::thread;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::channel::{
+    AsyncChannel, ChannelActor, NoDeadline, OwnedChannel, ReaderWriterChannelIo,
+};
+use futures_core::Stream;
+use futures_sink::Sink;
+
+use common::{BASE, CH_SIZE, Delay, SimTarget, TargetChannel, block_on, poll_times, wait_until};
+
+const DATA_CH: u32 = BASE + 2 * CH_SIZE as u32;
+
+fn target_channel(target: &SimTarget, actor: ChannelActor) -> TargetChannel<'_> {
+    OwnedChannel::new(target.io(), actor, DATA_CH, CH_SIZE).unwrap()
+}
+
+// Waits for the Target to receive a message
+fn receive(ch: &mut TargetChannel<'_>) -> Vec<u8> {
+    let mut buf = [0u8; CH_SIZE];
+    let mut len = 0;
+    wait_until(|| match ch.consume_bytes(&mut buf) {
+        Ok(consumed) => {
+            len = consumed;
+            true
+        }
+        Err(Error::NoData) => false,
+        Err(e) => panic!("Target failed to consume: {e:?}"),
+    });
+    buf[..len].to_vec()
+}
+
+#[test]
+fn stream_returns_messages_in_order() {
+    let target = SimTarget::new();
+    let mut data_ch = target_channel(&target, ChannelActor::Producer);
+
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut io = ReaderWriterChannelIo::new(&mut reader, &mut writer);
+    let channel = block_on(AsyncChannel::from_target(
+        &mut io,
+        ChannelActor::Consumer,
+        DATA_CH,
+    ))
+    .unwrap();
+    let mut messages = channel.into_stream::<Delay>().unwrap();
+
+    thread::scope(|s| {
+        s.spawn(move || {
+            for i in 0..10u8 {
+                data_ch
+                    .publish_bytes_when_ready(&[i; 3], thread::yield_now, NoDeadline)
+                    .unwrap();
+            }
+        });
+
+        for i in 0..10u8 {
+            let message = block_on(poll_fn(|cx| Pin::new(&mut messages).poll_next(cx)));
+            assert_eq!(message, Some(Ok(vec![i; 3])));
+        }
+    });
+
+    // Waits for more, rather than ending
+    let mut next = poll_fn(|cx| Pin::new(&mut messages).poll_next(cx));
+    assert!(poll_times(pin!(&mut next), 3).is_none());
+}
+
+#[test]
+fn sink_waits_for_the_previous_message_to_be_consumed() {
+    let target = SimTarget::new();
+    let mut data_ch = target_channel(&target, ChannelActor::Consumer);
+
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut io = ReaderWriterChannelIo::new(&mut reader, &mut writer);
+    let channel = block_on(AsyncChannel::from_target(
+        &mut io,
+        ChannelActor::Producer,
+        DATA_CH,
+    ))
+    .unwrap();
+    let mut sink = pin!(channel.into_sink::<Delay>().unwrap());
+
+    block_on(poll_fn(|cx| sink.as_mut().poll_ready(cx))).unwrap();
+    sink.as_mut().start_send(b"first").unwrap();
+    block_on(poll_fn(|cx| sink.as_mut().poll_flush(cx))).unwrap();
+
+    // The first message hasn't been consumed, so the second waits
+    block_on(poll_fn(|cx| sink.as_mut().poll_ready(cx))).unwrap();
+    sink.as_mut().start_send(b"second").unwrap();
+    let mut flush = poll_fn(|cx| sink.as_mut().poll_flush(cx));
+    assert!(poll_times(pin!(&mut flush), 3).is_none());
+
+    assert_eq!(receive(&mut data_ch), b"first");
+    block_on(flush).unwrap();
+    assert_eq!(receive(&mut data_ch), b"second");
+}
+
+#[test]
+fn start_send_while_busy_keeps_the_message_being_sent() {
+    let target = SimTarget::new();
+    let mut data_ch = target_channel(&target, ChannelActor::Consumer);
+
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut io = ReaderWriterChannelIo::new(&mut reader, &mut writer);
+    let channel = block_on(AsyncChannel::from_target(
+        &mut io,
+        ChannelActor::Producer,
+        DATA_CH,
+    ))
+    .unwrap();
+    let mut sink = pin!(channel.into_sink::<Delay>().unwrap());
+
+    sink.as_mut().start_send(b"first").unwrap();
+    block_on(poll_fn(|cx| sink.as_mut().poll_flush(cx))).unwrap();
+    sink.as_mut().start_send(b"second").unwrap();
+    let mut ready = poll_fn(|cx| sink.as_mut().poll_ready(cx));
+    assert!(poll_times(pin!(&mut ready), 3).is_none());
+
+    // Sending without waiting for poll_ready() is rejected, without
+    // affecting the message being sent
+    assert_eq!(sink.as_mut().start_send(b"third"), Err(Error::Busy));
+
+    assert_eq!(receive(&mut data_ch), b"first");
+    block_on(poll_fn(|cx| sink.as_mut().poll_flush(cx))).unwrap();
+    assert_eq!(receive(&mut data_ch), b"second");
+
+    // The sink is still usable
+    sink.as_mut().start_send(b"fourth").unwrap();
+    block_on(poll_fn(|cx| sink.as_mut().poll_flush(cx))).unwrap();
+    assert_eq!(receive(&mut data_ch), b"fourth");
+}
+
+#[test]
+fn adapters_require_the_right_actor() {
+    let target = SimTarget::new();
+    target_channel(&target, ChannelActor::Producer);
+
+    let (mut reader, mut writer) = (target.clone(), target.clone());
+    let mut io = ReaderWriterChannelIo::new(&mut reader, &mut writer);
+    let producer = block_on(AsyncChannel::from_target(
+        &mut io,
+        ChannelActor::Producer,
+        DATA_CH,
+    ))
+    .unwrap();
+    assert!(matches!(
+        producer.into_stream::<Delay>(),
+        Err(Error::InvalidOperation)
+    ));
+
+    let consumer = block_on(AsyncChannel::from_target(
+        &mut io,
+        ChannelActor::Consumer,
+        DATA_CH,
+    ))
+    .unwrap();
+    assert!(matches!(
+        consumer.into_sink::<Delay>(),
+        Err(Error::InvalidOperation)
+    ));
+}
@@ -0,0 +1,131 @@
+//! Tests for the system service, served by an RpcServer over simulated
+//! Target channels.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(feature = "async")]
+
+mod common;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::client::FirmwareVersion;
+use airfrog_rpc::server::{Command, Status};
+use airfrog_rpc::system::{self, MemoryInfo, System, VersionInfo};
+
+use common::{SimTarget, block_on, with_server};
+
+#[derive(Default)]
+struct App {
+    resettable: bool,
+    reset_requested: bool,
+    unix_ms: Option<u64>,
+}
+
+impl System for App {
+    fn version(&self) -> VersionInfo<'_> {
+        VersionInfo {
+            version: "1.2.3",
+            build: "abc123",
+        }
+    }
+
+    fn uptime_ms(&mut self) -> u64 {
+        12_345_678_901
+    }
+
+    fn memory(&mut self) -> MemoryInfo {
+        MemoryInfo {
+            free_ram: 0x1234,
+            stack_high_water: 0x567,
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), Status> {
+        if !self.resettable {
+            return Err(Status::HandlerError);
+        }
+        self.reset_requested = true;
+        Ok(())
+    }
+
+    fn set_time(&mut self, unix_ms: u64) {
+        self.unix_ms = Some(unix_ms);
+    }
+}
+
+static NO_COMMANDS: &[Command<App>] = &[];
+
+#[test]
+fn system_commands_round_trip() {
+    let target = SimTarget::new();
+    let mut app = App {
+        resettable: true,
+        ..App::default()
+    };
+    let tables = [NO_COMMANDS, system::commands()];
+    with_server::<_, 64>(&target, &mut app, &tables, |client| {
+        block_on(client.ping(b"hello")).unwrap();
+        block_on(client.ping(&[])).unwrap();
+        assert_eq!(
+            block_on(client.firmware_version()),
+            Ok(FirmwareVersion {
+                version: "1.2.3".into(),
+                build: "abc123".into(),
+            })
+        );
+        assert_eq!(block_on(client.uptime_ms()), Ok(12_345_678_901));
+        assert_eq!(
+            block_on(client.memory_info()),
+            Ok(MemoryInfo {
+                free_ram: 0x1234,
+                stack_high_water: 0x567,
+            })
+        );
+        block_on(client.request_reset()).unwrap();
+        block_on(client.set_time(1_760_000_000_000)).unwrap();
+    });
+    assert!(app.reset_requested);
+    assert_eq!(app.unix_ms, Some(1_760_000_000_000));
+}
+
+#[test]
+fn unsupported_reset_and_invalid_time_are_rejected() {
+    let target = SimTarget::new();
+    let mut app = App::default();
+    let tables = [NO_COMMANDS, system::commands()];
+    with_server::<_, 64>(&target, &mut app, &tables, |client| {
+        assert_eq!(
+            block_on(client.request_reset()),
+            Err(Error::Status(Status::HandlerError))
+        );
+        assert_eq!(
+            block_on(client.call(system::SET_TIME, &[1, 2, 3])),
+            Err(Error::Status(Status::InvalidRequest))
+        );
+    });
+    assert!(!app.reset_requested);
+    assert_eq!(app.unix_ms, None);
+}
+
+#[test]
+fn version_strings_are_truncated_on_a_character_boundary() {
+    // 254 bytes, then a 2 byte character straddling the 255 byte limit
+    let version = format!("{}é", "v".repeat(254));
+    let info = VersionInfo {
+        version: &version,
+        build: "abc123",
+    };
+
+    let mut buf = [0u8; 512];
+    let len = info.encode(&mut buf).unwrap();
+    assert_eq!(len, 1 + 254 + 1 + 6);
+
+    let decoded = VersionInfo::decode(&buf[..len]).unwrap();
+    assert_eq!(decoded.version, &version[..254]);
+    assert_eq!(decoded.build, "abc123");
+
+    // Too small a buffer is reported, rather than truncating further
+    assert_eq!(info.encode(&mut buf[..100]), None);
+}
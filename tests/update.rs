@@ -0,0 +1,188 @@
+//! Tests for the firmware update service, updating a simulated Target.
+
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+#![cfg(feature = "update")]
+
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use airfrog_rpc::Error;
+use airfrog_rpc::client::AsyncRpcClient;
+use airfrog_rpc::server::{RpcServer, Status};
+use airfrog_rpc::update::{self, FirmwareUpdate, ImageVerifier, UpdateState, Updater};
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+use common::{Delay, SimTarget, StopOnDrop, block_on};
+
+const FLASH_SIZE: usize = 4096;
+const BANK_OFFSET: u32 = 2048;
+const BANK_SIZE: u32 = 2048;
+
+#[derive(Debug)]
+struct FlashError;
+
+impl NorFlashError for FlashError {
+    fn kind(&self) -> NorFlashErrorKind {
+        NorFlashErrorKind::Other
+    }
+}
+
+// Flash held in memory, with 4 byte writes and 256 byte sectors
+struct Flash {
+    data: Vec<u8>,
+}
+
+impl ErrorType for Flash {
+    type Error = FlashError;
+}
+
+impl ReadNorFlash for Flash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), FlashError> {
+        let offset = offset as usize;
+        bytes.copy_from_slice(&self.data[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl NorFlash for Flash {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = 256;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), FlashError> {
+        self.data[from as usize..to as usize].fill(0xFF);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), FlashError> {
+        let offset = offset as usize;
+        self.data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+// Verifies the wrapping sum of the image's bytes
+#[derive(Default)]
+struct SumVerifier {
+    sum: u32,
+}
+
+impl ImageVerifier for SumVerifier {
+    fn reset(&mut self) {
+        self.sum = 0;
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.sum = self.sum.wrapping_add(*byte as u32);
+        }
+    }
+
+    fn verify(&mut self, expected: &[u8]) -> bool {
+        expected == self.sum.to_le_bytes()
+    }
+}
+
+struct App {
+    updater: Updater<Flash, SumVerifier>,
+    bootable: Option<u32>,
+}
+
+impl FirmwareUpdate for App {
+    type Flash = Flash;
+    type Verifier = SumVerifier;
+
+    fn updater(&mut self) -> &mut Updater<Flash, SumVerifier> {
+        &mut self.updater
+    }
+
+    fn mark_bootable(&mut self, image_size: u32) -> Result<(), Status> {
+        self.bootable = Some(image_size);
+        Ok(())
+    }
+}
+
+fn flash() -> Flash {
+    Flash {
+        data: vec![0; FLASH_SIZE],
+    }
+}
+
+fn digest(image: &[u8]) -> [u8; 4] {
+    let mut verifier = SumVerifier::default();
+    verifier.update(image);
+    verifier.sum.to_le_bytes()
+}
+
+#[test]
+fn image_is_sent_in_chunks_the_server_can_receive() {
+    let target = SimTarget::new();
+    let stop = AtomicBool::new(false);
+    let image = (0..500).map(|i| i as u8).collect::<Vec<_>>();
+
+    let mut app = thread::scope(|s| {
+        let (cmd, rsp) = target.channels();
+        let server = s.spawn(|| {
+            // The server's buffer is much smaller than the channels
+            let mut server: RpcServer<_, App, 32, _> = RpcServer::new(cmd, rsp, update::commands());
+            let updater = Updater::new(flash(), SumVerifier::default(), BANK_OFFSET, BANK_SIZE);
+            let mut app = App {
+                updater: updater.unwrap(),
+                bootable: None,
+            };
+            while !stop.load(Ordering::SeqCst) {
+                if !server.poll(&mut app).unwrap() {
+                    thread::yield_now();
+                }
+            }
+            app
+        });
+
+        let stop = StopOnDrop(&stop);
+        let (mut reader, mut writer) = (target.clone(), target.clone());
+        let mut client =
+            AsyncRpcClient::<_, _, Delay>::new(&mut reader, &mut writer, target.config());
+
+        let mut progress = Vec::new();
+        block_on(client.update_firmware(&image, &digest(&image), |status| {
+            progress.push(status.written)
+        }))
+        .unwrap();
+
+        // 31 byte payloads, less the 4 byte offset, in whole 4 byte writes
+        let expected = (1..=20).map(|i| i * 24).chain([500]).collect::<Vec<_>>();
+        assert_eq!(progress, expected);
+
+        drop(stop);
+        server.join().unwrap()
+    });
+
+    assert_eq!(app.bootable, Some(500));
+    let bank = &app.updater.flash().data[BANK_OFFSET as usize..];
+    assert_eq!(&bank[..500], &image[..]);
+    assert_eq!(app.updater.status().state, UpdateState::Complete);
+}
+
+#[test]
+fn bank_must_fit_in_flash() {
+    let new = |offset, size| Updater::new(flash(), SumVerifier::default(), offset, size).err();
+    assert_eq!(new(BANK_OFFSET, BANK_SIZE), None);
+    assert_eq!(
+        new(BANK_OFFSET, BANK_SIZE + 256),
+        Some(Error::InvalidOperation)
+    );
+    assert_eq!(new(u32::MAX - 255, 512), Some(Error::InvalidOperation));
+    assert_eq!(new(BANK_OFFSET + 1, BANK_SIZE), Some(Error::NotAligned));
+}